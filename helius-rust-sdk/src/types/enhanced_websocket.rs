@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use solana_rpc_client_api::response::{
+    Response as RpcResponse, RpcKeyedAccount, RpcLogsResponse, RpcSignatureResult, SlotInfo,
+};
 use solana_sdk::pubkey::Pubkey;
 use solana_transaction_status::EncodedTransactionWithStatusMeta;
 
@@ -98,3 +101,17 @@ pub struct TransactionNotification {
     pub signature: String,
     pub slot: u64,
 }
+
+// Websocket responses of the standard solana subscriptions
+
+/// `logsSubscribe`: the logs of a transaction, in the context of the slot it was processed in
+pub type LogsNotification = RpcResponse<RpcLogsResponse>;
+
+/// `programSubscribe`: an account owned by the program, as it changed
+pub type ProgramNotification = RpcResponse<RpcKeyedAccount>;
+
+/// `slotSubscribe`: a slot, its parent and the current root
+pub type SlotNotification = SlotInfo;
+
+/// `signatureSubscribe`: received, if enabled, and then processed at the commitment subscribed with
+pub type SignatureNotification = RpcResponse<RpcSignatureResult>;
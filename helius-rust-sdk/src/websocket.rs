@@ -1,5 +1,9 @@
 use crate::error::{HeliusError, Result};
-use crate::types::{RpcTransactionsConfig, TransactionNotification};
+use crate::types::{
+    LogsNotification, ProgramNotification, RpcTransactionsConfig, SignatureNotification, SlotNotification,
+    TransactionNotification,
+};
+use chrono::{DateTime, Utc};
 use futures_util::{
     future::{ready, BoxFuture, FutureExt},
     sink::SinkExt,
@@ -8,16 +12,24 @@ use futures_util::{
 use serde::de::DeserializeOwned;
 use serde_json::{json, Map, Value};
 use solana_account_decoder::UiAccount;
-use solana_rpc_client_api::config::RpcAccountInfoConfig;
-use solana_rpc_client_api::{error_object::RpcErrorObject, response::Response as RpcResponse};
-use solana_sdk::pubkey::Pubkey;
+use solana_rpc_client_api::config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSignatureSubscribeConfig, RpcTransactionLogsConfig,
+    RpcTransactionLogsFilter,
+};
+use solana_rpc_client_api::{
+    error_object::RpcErrorObject,
+    response::{Response as RpcResponse, RpcSignatureResult},
+};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
 use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio::{
     net::TcpStream,
-    sync::{mpsc, oneshot, RwLock},
+    sync::{mpsc, oneshot, watch, RwLock},
     task::JoinHandle,
-    time::{sleep, Duration},
+    time::{interval, interval_at, sleep, timeout, Duration, Instant, MissedTickBehavior},
 };
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_tungstenite::{
@@ -30,35 +42,335 @@ use tokio_tungstenite::{
 };
 
 pub const ENHANCED_WEBSOCKET_URL: &str = "wss://atlas-mainnet.helius-rpc.com/?api-key=";
+/// Serves the standard solana subscriptions: logs, program, slot and signature
+pub const STANDARD_WEBSOCKET_URL: &str = "wss://mainnet.helius-rpc.com/?api-key=";
 pub const DEFAULT_PING_DURATION_SECONDS: u64 = 10;
 pub const DEFAULT_MAX_FAILED_PINGS: usize = 3;
 
 // pub type Result<T = ()> = Result<T, HeliusError>;
 
-type UnsubscribeFn = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
-type SubscribeResponseMsg = Result<(mpsc::UnboundedReceiver<Value>, UnsubscribeFn)>;
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+pub type UnsubscribeFn = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+type SubscribeResponseMsg = Result<(mpsc::UnboundedReceiver<StreamEvent<Value>>, UnsubscribeFn)>;
 type SubscribeRequestMsg = (String, Value, oneshot::Sender<SubscribeResponseMsg>);
 type SubscribeResult<'a, T> = Result<(BoxStream<'a, T>, UnsubscribeFn)>;
 type RequestMsg = (String, Value, oneshot::Sender<Result<Value>>);
+type UnsubscribeMsg = (u64, oneshot::Sender<()>);
+
+/// How the websocket reconnects once its connection is lost, e.g. on a pong timeout
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Backoff before the first attempt, doubled after every failed one
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up after this many failed attempts in a row, which ends every stream. Never if None
+    pub max_attempts: Option<u32>,
+    pub connect_timeout: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None,
+            connect_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Exponential backoff with jitter: between half and all of `initial_backoff * 2^attempt`, capped at `max_backoff`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        backoff / 2 + backoff.mul_f64(rand::random::<f64>() / 2.0)
+    }
+}
+
+/// Where a subscription stream may have missed events, between losing the connection and subscribing again
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamGap {
+    pub disconnected_at: DateTime<Utc>,
+    pub resubscribed_at: DateTime<Utc>,
+    /// Why the connection was lost
+    pub reason: String,
+}
+
+/// A notification that did not deserialize into the type of its stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationParseError {
+    /// The subscription, e.g. `transaction`
+    pub operation: String,
+    pub error: String,
+    /// The notification as received
+    pub value: Value,
+}
+
+/// Why a subscription stream ended while the websocket lives on: the server rejected subscribing again after a
+/// reconnect. It is the last event of the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamClosed {
+    pub disconnected_at: DateTime<Utc>,
+    /// The error answered to the resubscribe
+    pub reason: String,
+}
+
+/// An item of a subscription stream that reports gaps
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent<T> {
+    Notification(T),
+    Gap(StreamGap),
+    ParseError(NotificationParseError),
+    Closed(StreamClosed),
+}
+
+/// Whether the websocket is connected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Connected,
+    /// Lost, `attempt` is the reconnect attempt in progress, counted from 1
+    Reconnecting { attempt: u32 },
+    /// Shut down, or given up reconnecting. Every stream has ended
+    Closed,
+}
+
+/// Health of the websocket, published on state changes and every second while connected
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WsHealth {
+    pub state: ConnectionState,
+    /// When the last connection was established
+    pub connected_at: Option<DateTime<Utc>>,
+    /// Connections reestablished so far
+    pub reconnects: u64,
+    /// Round trip of the last ping answered
+    pub pong_rtt: Option<Duration>,
+    /// Messages received over the last second, 0 while not connected
+    pub messages_per_second: f64,
+    /// Slot of the last notification of any subscription, and when it was received
+    pub last_notification_slot: Option<u64>,
+    pub last_notification_at: Option<DateTime<Utc>>,
+    /// Notifications that did not deserialize, on every stream
+    pub parse_errors: u64,
+}
+
+// Tracks the connection for `WsHealth`, published by `report` to spare the watchers a wake up per message
+struct HealthMonitor {
+    sender: watch::Sender<WsHealth>,
+    parse_errors: Arc<AtomicU64>, // counted by the streams
+    messages: u64,                // since the last report
+    last_report: Instant,
+    ping_sent_at: Option<Instant>,
+    last_notification: Option<(u64, DateTime<Utc>)>,
+}
+
+impl HealthMonitor {
+    fn new(sender: watch::Sender<WsHealth>, parse_errors: Arc<AtomicU64>) -> Self {
+        Self {
+            sender,
+            parse_errors,
+            messages: 0,
+            last_report: Instant::now(),
+            ping_sent_at: None,
+            last_notification: None,
+        }
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        if state == ConnectionState::Connected {
+            self.messages = 0;
+            self.last_report = Instant::now();
+            self.ping_sent_at = None;
+        }
+        self.sender.send_modify(|health| {
+            if health.state != ConnectionState::Connected && state == ConnectionState::Connected {
+                health.reconnects += 1;
+                health.connected_at = Some(Utc::now());
+            }
+            if state != ConnectionState::Connected {
+                health.messages_per_second = 0.0;
+            }
+            health.state = state;
+        });
+    }
+
+    fn message(&mut self) {
+        self.messages += 1;
+    }
+
+    // Every notification carries its slot, in `result.context` or in `result` itself
+    fn notification(&mut self, result: &Value) {
+        let slot = result
+            .get("context")
+            .and_then(|context| context.get("slot"))
+            .or_else(|| result.get("slot"))
+            .and_then(Value::as_u64);
+        if let Some(slot) = slot {
+            self.last_notification = Some((slot, Utc::now()));
+        }
+    }
+
+    fn ping(&mut self) {
+        self.ping_sent_at = Some(Instant::now());
+    }
+
+    fn pong(&mut self) {
+        if let Some(ping_sent_at) = self.ping_sent_at.take() {
+            let rtt = ping_sent_at.elapsed();
+            self.sender.send_modify(|health| health.pong_rtt = Some(rtt));
+        }
+    }
+
+    fn report(&mut self) {
+        let elapsed = self.last_report.elapsed().as_secs_f64();
+        let messages_per_second = if elapsed > 0.0 {
+            self.messages as f64 / elapsed
+        } else {
+            0.0
+        };
+        self.messages = 0;
+        self.last_report = Instant::now();
+        let parse_errors = self.parse_errors.load(Ordering::Relaxed);
+        let last_notification = self.last_notification;
+        self.sender.send_modify(|health| {
+            health.messages_per_second = messages_per_second;
+            health.parse_errors = parse_errors;
+            if let Some((slot, received_at)) = last_notification {
+                health.last_notification_slot = Some(slot);
+                health.last_notification_at = Some(received_at);
+            }
+        });
+    }
+}
+
+/// A subscription that outlives the connections it is made on
+struct ActiveSubscription {
+    operation: String,
+    params: Value,
+    notifications_sender: mpsc::UnboundedSender<StreamEvent<Value>>,
+    /// Id given by the server on the current connection, None until (re)subscribed
+    sid: Option<u64>,
+    /// When and why the connection was lost, if events were missed since the last subscribe
+    disconnected: Option<(DateTime<Utc>, String)>,
+}
+
+enum PendingSubscribe {
+    New(String, Value, oneshot::Sender<SubscribeResponseMsg>),
+    Resubscribe { key: u64, operation: String },
+}
+
+/// State kept across reconnects
+#[derive(Default)]
+struct WsState {
+    request_id: u64,
+    next_key: u64,
+    subscriptions: BTreeMap<u64, ActiveSubscription>, // by key, the id handed to the caller
+    sids: BTreeMap<u64, u64>,                         // key of each server id of the current connection
+    requests_subscribe: BTreeMap<u64, PendingSubscribe>,
+    requests_unsubscribe: BTreeMap<u64, oneshot::Sender<()>>,
+    other_requests: BTreeMap<u64, oneshot::Sender<Result<Value>>>,
+}
+
+impl WsState {
+    fn next_request_id(&mut self) -> u64 {
+        self.request_id += 1;
+        self.request_id
+    }
+
+    // Every subscription lost its server id, requests sent on the connection will not be answered
+    fn disconnect(&mut self, reason: &str) {
+        let now = Utc::now();
+        for subscription in self.subscriptions.values_mut() {
+            subscription.sid = None;
+            subscription
+                .disconnected
+                .get_or_insert_with(|| (now, reason.to_string()));
+        }
+        self.sids.clear();
+        // a new subscribe is sent again on the next connection, a resubscribe with every active subscription
+        self.requests_subscribe
+            .retain(|_, pending| matches!(pending, PendingSubscribe::New(..)));
+        for (_, response_sender) in std::mem::take(&mut self.requests_unsubscribe) {
+            let _ = response_sender.send(());
+        }
+        self.other_requests.clear();
+    }
+}
+
+// The `{operation}Subscribe` request, answered with the server id of the subscription
+async fn send_subscribe(ws: &mut WsStream, request_id: u64, operation: &str, params: &Value) -> Result<()> {
+    let method = format!("{operation}Subscribe");
+    let body = json!({"jsonrpc":"2.0","id":request_id,"method":method,"params":params});
+    ws.send(Message::Text(body.to_string())).await?;
+    Ok(())
+}
+
+async fn send_unsubscribe(ws: &mut WsStream, request_id: u64, operation: &str, sid: u64) -> Result<()> {
+    let method = format!("{operation}Unsubscribe");
+    let text = json!({"jsonrpc":"2.0","id":request_id,"method":method,"params":[sid]}).to_string();
+    ws.send(Message::Text(text)).await?;
+    Ok(())
+}
+
+// The last notification of a signature subscription, a received one comes before it
+fn is_processed(notification: &SignatureNotification) -> bool {
+    matches!(notification.value, RpcSignatureResult::ProcessedSignature(_))
+}
+
+// Whether the server ends the subscription with this notification
+fn is_last_notification(operation: &str, result: &Value) -> bool {
+    operation == "signature"
+        && serde_json::from_value::<SignatureNotification>(result.clone())
+            .is_ok_and(|notification| is_processed(&notification))
+}
+
+/// How a connection ended
+enum WsExit {
+    Shutdown,
+    Lost(String),
+}
 
 /// A client for subscribing to transaction or account updates from a Helius (Geyser) enhanced websocket server.
+/// A lost connection is reestablished and every active subscription is made again with its original params, on
+/// the same stream.
 ///
 /// Forked from Solana's [`PubsubClient`].
 pub struct EnhancedWebsocket {
     subscribe_sender: mpsc::UnboundedSender<SubscribeRequestMsg>,
     shutdown_sender: oneshot::Sender<()>,
     node_version: RwLock<Option<semver::Version>>,
+    health_receiver: watch::Receiver<WsHealth>,
+    parse_errors: Arc<AtomicU64>,
     ws: JoinHandle<Result<()>>,
 }
 
 impl EnhancedWebsocket {
     /// Expects enhanced websocket endpoint: wss://atlas-mainnet.helius-rpc.com?api-key=<API_KEY>
     pub async fn new(url: &str, ping_interval_secs: Option<u64>, pong_timeout_secs: Option<u64>) -> Result<Self> {
+        Self::new_with_reconnect(url, ping_interval_secs, pong_timeout_secs, ReconnectPolicy::default()).await
+    }
+
+    /// Like `new`, reconnecting with `reconnect`. The first connection is not retried.
+    pub async fn new_with_reconnect(
+        url: &str,
+        ping_interval_secs: Option<u64>,
+        pong_timeout_secs: Option<u64>,
+        reconnect: ReconnectPolicy,
+    ) -> Result<Self> {
         let (ws, _response) = connect_async(url).await.map_err(HeliusError::Tungstenite)?;
 
         let (subscribe_sender, subscribe_receiver) = mpsc::unbounded_channel();
         let (_request_sender, request_receiver) = mpsc::unbounded_channel();
         let (shutdown_sender, shutdown_receiver) = oneshot::channel();
+        let connected = WsHealth {
+            connected_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        let (health_sender, health_receiver) = watch::channel(connected);
+        let parse_errors = Arc::new(AtomicU64::new(0));
 
         let ping_interval = ping_interval_secs
             .filter(|interval: &u64| *interval != 0)
@@ -77,13 +389,18 @@ impl EnhancedWebsocket {
             subscribe_sender,
             shutdown_sender,
             node_version: RwLock::new(None),
-            ws: tokio::spawn(EnhancedWebsocket::run_ws(
+            health_receiver,
+            parse_errors: parse_errors.clone(),
+            ws: tokio::spawn(EnhancedWebsocket::run(
+                url.to_string(),
                 ws,
                 subscribe_receiver,
                 request_receiver,
                 shutdown_receiver,
                 ping_interval,
                 max_failed_pings,
+                reconnect,
+                HealthMonitor::new(health_sender, parse_errors),
             )),
         })
     }
@@ -99,30 +416,63 @@ impl EnhancedWebsocket {
         Ok(())
     }
 
-    async fn subscribe<'a, T: DeserializeOwned + Send + Debug + 'a>(
+    /// Health of the connection: its state, the pong round trip, messages per second and the last notification
+    /// slot. `changed()` on the receiver waits for the next update.
+    pub fn health(&self) -> watch::Receiver<WsHealth> {
+        self.health_receiver.clone()
+    }
+
+    async fn subscribe_with_gaps<'a, T: DeserializeOwned + Send + Debug + 'a>(
         &self,
         operation: &str,
         params: Value,
-    ) -> SubscribeResult<'a, T> {
+    ) -> SubscribeResult<'a, StreamEvent<T>> {
         let (response_sender, response_receiver) = oneshot::channel();
         self.subscribe_sender
             .send((operation.to_string(), params, response_sender))
             .map_err(|err| HeliusError::WebsocketClosed(err.to_string()))?;
 
-        let (notifications, unsubscribe) = response_receiver
+        let (events, unsubscribe) = response_receiver
             .await
             .map_err(|err| HeliusError::WebsocketClosed(err.to_string()))??;
+        let operation = operation.to_string();
+        let parse_errors = self.parse_errors.clone();
         Ok((
-            UnboundedReceiverStream::new(notifications)
-                .filter_map(|value| match serde_json::from_value::<T>(value.clone()) {
-                    Err(e) => {
-                        eprintln!(
-                            "Failed to parse websocket notification: {:#?} for value: {:#?}",
-                            e, value
-                        );
-                        ready(None)
-                    }
-                    Ok(res) => ready(Some(res)),
+            UnboundedReceiverStream::new(events)
+                .map(move |event| match event {
+                    StreamEvent::Notification(value) => match serde_json::from_value::<T>(value.clone()) {
+                        Ok(res) => StreamEvent::Notification(res),
+                        Err(e) => {
+                            parse_errors.fetch_add(1, Ordering::Relaxed);
+                            StreamEvent::ParseError(NotificationParseError {
+                                operation: operation.clone(),
+                                error: e.to_string(),
+                                value,
+                            })
+                        }
+                    },
+                    StreamEvent::Gap(gap) => StreamEvent::Gap(gap),
+                    StreamEvent::ParseError(parse_error) => StreamEvent::ParseError(parse_error),
+                    StreamEvent::Closed(closed) => StreamEvent::Closed(closed),
+                })
+                .boxed(),
+            unsubscribe,
+        ))
+    }
+
+    // The notifications alone, gaps and parse errors are only counted in `health`, a stream closed by the server
+    // just ends
+    async fn subscribe<'a, T: DeserializeOwned + Send + Debug + 'a>(
+        &self,
+        operation: &str,
+        params: Value,
+    ) -> SubscribeResult<'a, T> {
+        let (events, unsubscribe) = self.subscribe_with_gaps::<T>(operation, params).await?;
+        Ok((
+            events
+                .filter_map(|event| match event {
+                    StreamEvent::Notification(res) => ready(Some(res)),
+                    StreamEvent::Gap(_) | StreamEvent::ParseError(_) | StreamEvent::Closed(_) => ready(None),
                 })
                 .boxed(),
             unsubscribe,
@@ -165,6 +515,16 @@ impl EnhancedWebsocket {
         self.subscribe("transaction", params).await
     }
 
+    /// Like `transaction_subscribe`, with a `StreamEvent::Gap` wherever transactions may have been missed while
+    /// reconnecting. The stream does not borrow the websocket, it can be moved to a task of its own.
+    pub async fn transaction_subscribe_with_gaps(
+        &self,
+        config: RpcTransactionsConfig,
+    ) -> SubscribeResult<'static, StreamEvent<TransactionNotification>> {
+        let params = json!([config.filter, config.options]);
+        self.subscribe_with_gaps("transaction", params).await
+    }
+
     /// Stream accounts with numerous configurations and filters to choose from.
     ///
     /// # Example
@@ -198,34 +558,268 @@ impl EnhancedWebsocket {
         self.subscribe("account", params).await
     }
 
-    async fn run_ws(
-        mut ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    /// Like `account_subscribe`, with a `StreamEvent::Gap` wherever updates may have been missed while reconnecting
+    pub async fn account_subscribe_with_gaps(
+        &self,
+        pubkey: &Pubkey,
+        config: Option<RpcAccountInfoConfig>,
+    ) -> SubscribeResult<'static, StreamEvent<RpcResponse<UiAccount>>> {
+        let params = json!([pubkey.to_string(), config]);
+        self.subscribe_with_gaps("account", params).await
+    }
+
+    /// Stream the logs of transactions, e.g. those mentioning a program. Like the other standard solana
+    /// subscriptions below it is served on `STANDARD_WEBSOCKET_URL`, not on the enhanced endpoint.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use helius::error::Result;
+    /// use helius::websocket::{EnhancedWebsocket, STANDARD_WEBSOCKET_URL};
+    /// use solana_rpc_client_api::config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+    /// use solana_sdk::commitment_config::CommitmentConfig;
+    /// use tokio_stream::StreamExt;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///   let url = format!("{}{}", STANDARD_WEBSOCKET_URL, "your_api_key");
+    ///   let ws = EnhancedWebsocket::new(&url, None, None).await?;
+    ///   let filter = RpcTransactionLogsFilter::Mentions(vec!["6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P".to_string()]);
+    ///   let config = RpcTransactionLogsConfig { commitment: Some(CommitmentConfig::processed()) };
+    ///   let (mut stream, _unsub) = ws.logs_subscribe(filter, config).await?;
+    ///   while let Some(event) = stream.next().await {
+    ///     println!("{} {:?}", event.value.signature, event.value.logs);
+    ///   }
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn logs_subscribe(
+        &self,
+        filter: RpcTransactionLogsFilter,
+        config: RpcTransactionLogsConfig,
+    ) -> SubscribeResult<'_, LogsNotification> {
+        let params = json!([filter, config]);
+        self.subscribe("logs", params).await
+    }
+
+    /// Like `logs_subscribe`, with a `StreamEvent::Gap` wherever logs may have been missed while reconnecting
+    pub async fn logs_subscribe_with_gaps(
+        &self,
+        filter: RpcTransactionLogsFilter,
+        config: RpcTransactionLogsConfig,
+    ) -> SubscribeResult<'static, StreamEvent<LogsNotification>> {
+        let params = json!([filter, config]);
+        self.subscribe_with_gaps("logs", params).await
+    }
+
+    /// Stream every change of the accounts owned by a program, narrowed by the `filters` of the config
+    pub async fn program_subscribe(
+        &self,
+        program_id: &Pubkey,
+        config: Option<RpcProgramAccountsConfig>,
+    ) -> SubscribeResult<'_, ProgramNotification> {
+        let params = json!([program_id.to_string(), config]);
+        self.subscribe("program", params).await
+    }
+
+    /// Like `program_subscribe`, with a `StreamEvent::Gap` wherever changes may have been missed while reconnecting
+    pub async fn program_subscribe_with_gaps(
+        &self,
+        program_id: &Pubkey,
+        config: Option<RpcProgramAccountsConfig>,
+    ) -> SubscribeResult<'static, StreamEvent<ProgramNotification>> {
+        let params = json!([program_id.to_string(), config]);
+        self.subscribe_with_gaps("program", params).await
+    }
+
+    /// Stream every slot the node processes
+    pub async fn slot_subscribe(&self) -> SubscribeResult<'_, SlotNotification> {
+        self.subscribe("slot", json!([])).await
+    }
+
+    /// Like `slot_subscribe`, with a `StreamEvent::Gap` wherever slots may have been missed while reconnecting
+    pub async fn slot_subscribe_with_gaps(&self) -> SubscribeResult<'static, StreamEvent<SlotNotification>> {
+        self.subscribe_with_gaps("slot", json!([])).await
+    }
+
+    /// Wait for a transaction to be processed at the commitment of the config, and, with
+    /// `enable_received_notification`, to be received first. The server ends the subscription once the signature is
+    /// processed, so does the stream.
+    pub async fn signature_subscribe(
+        &self,
+        signature: &Signature,
+        config: Option<RpcSignatureSubscribeConfig>,
+    ) -> SubscribeResult<'_, SignatureNotification> {
+        let params = json!([signature.to_string(), config]);
+        let (notifications, unsubscribe) = self.subscribe::<SignatureNotification>("signature", params).await?;
+        Ok((
+            notifications
+                .scan(false, |processed, notification| {
+                    if *processed {
+                        return ready(None);
+                    }
+                    *processed = is_processed(&notification);
+                    ready(Some(notification))
+                })
+                .boxed(),
+            unsubscribe,
+        ))
+    }
+
+    /// Like `signature_subscribe`, with a `StreamEvent::Gap` if the transaction may have been processed while
+    /// reconnecting, in which case the notification may already have been missed
+    pub async fn signature_subscribe_with_gaps(
+        &self,
+        signature: &Signature,
+        config: Option<RpcSignatureSubscribeConfig>,
+    ) -> SubscribeResult<'static, StreamEvent<SignatureNotification>> {
+        let params = json!([signature.to_string(), config]);
+        let (events, unsubscribe) = self
+            .subscribe_with_gaps::<SignatureNotification>("signature", params)
+            .await?;
+        Ok((
+            events
+                .scan(false, |processed, event| {
+                    if *processed {
+                        return ready(None);
+                    }
+                    *processed =
+                        matches!(&event, StreamEvent::Notification(notification) if is_processed(notification));
+                    ready(Some(event))
+                })
+                .boxed(),
+            unsubscribe,
+        ))
+    }
+
+    // Serves connections until shutdown, reconnecting with `reconnect` whenever one is lost
+    #[allow(clippy::too_many_arguments)]
+    async fn run(
+        url: String,
+        mut ws: WsStream,
         mut subscribe_receiver: mpsc::UnboundedReceiver<SubscribeRequestMsg>,
         mut request_receiver: mpsc::UnboundedReceiver<RequestMsg>,
         mut shutdown_receiver: oneshot::Receiver<()>,
         ping_duration_seconds: u64,
         max_failed_pings: usize,
+        reconnect: ReconnectPolicy,
+        mut health: HealthMonitor,
     ) -> Result<()> {
-        let mut request_id: u64 = 0;
+        let mut state = WsState::default();
+        let (unsubscribe_sender, mut unsubscribe_receiver) = mpsc::unbounded_channel();
+
+        loop {
+            let exit = EnhancedWebsocket::run_ws(
+                &mut ws,
+                &mut state,
+                &mut subscribe_receiver,
+                &mut request_receiver,
+                &mut shutdown_receiver,
+                &unsubscribe_sender,
+                &mut unsubscribe_receiver,
+                &mut health,
+                ping_duration_seconds,
+                max_failed_pings,
+            )
+            .await;
+            let reason = match exit {
+                Ok(WsExit::Shutdown) => {
+                    health.set_state(ConnectionState::Closed);
+                    return Ok(());
+                }
+                Ok(WsExit::Lost(reason)) => reason,
+                Err(err) => err.to_string(),
+            };
+            // the reason reaches every stream with its gap marker
+            state.disconnect(&reason);
+
+            let mut attempt: u32 = 0;
+            ws = loop {
+                health.set_state(ConnectionState::Reconnecting { attempt: attempt + 1 });
+                tokio::select! {
+                  _ = &mut shutdown_receiver => {
+                    health.set_state(ConnectionState::Closed);
+                    return Ok(());
+                  },
+                  () = sleep(reconnect.backoff(attempt)) => {},
+                }
+                // a failed attempt shows in the health state as the next attempt
+                if let Ok(Ok((ws, _response))) = timeout(reconnect.connect_timeout, connect_async(url.as_str())).await {
+                    break ws;
+                }
+                attempt += 1;
+                if reconnect
+                    .max_attempts
+                    .is_some_and(|max_attempts| attempt >= max_attempts)
+                {
+                    health.set_state(ConnectionState::Closed);
+                    return Err(HeliusError::WebsocketClosed(format!(
+                        "Failed to reconnect after {} attempts: {}",
+                        attempt, reason
+                    )));
+                }
+            };
+            health.set_state(ConnectionState::Connected);
+        }
+    }
+
+    // Serves one connection, after sending the subscribes still pending and resubscribing every active subscription
+    #[allow(clippy::too_many_arguments)]
+    async fn run_ws(
+        ws: &mut WsStream,
+        state: &mut WsState,
+        subscribe_receiver: &mut mpsc::UnboundedReceiver<SubscribeRequestMsg>,
+        request_receiver: &mut mpsc::UnboundedReceiver<RequestMsg>,
+        shutdown_receiver: &mut oneshot::Receiver<()>,
+        unsubscribe_sender: &mpsc::UnboundedSender<UnsubscribeMsg>,
+        unsubscribe_receiver: &mut mpsc::UnboundedReceiver<UnsubscribeMsg>,
+        health: &mut HealthMonitor,
+        ping_duration_seconds: u64,
+        max_failed_pings: usize,
+    ) -> Result<WsExit> {
         let mut unmatched_pings: usize = 0;
+        let ping_period = Duration::from_secs(ping_duration_seconds);
+        let mut ping_interval = interval_at(Instant::now() + ping_period, ping_period);
+        let mut report_interval = interval(Duration::from_secs(1));
+        ping_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        report_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-        let mut requests_subscribe = BTreeMap::new();
-        let mut requests_unsubscribe = BTreeMap::<u64, oneshot::Sender<()>>::new();
-        let mut other_requests = BTreeMap::new();
-        let mut subscriptions = BTreeMap::new();
-        let (unsubscribe_sender, mut unsubscribe_receiver) = mpsc::unbounded_channel();
+        for pending in std::mem::take(&mut state.requests_subscribe).into_values() {
+            let request_id = state.next_request_id();
+            state.requests_subscribe.insert(request_id, pending);
+        }
+        let resubscribes: Vec<(u64, String)> = state
+            .subscriptions
+            .iter()
+            .map(|(key, subscription)| (*key, subscription.operation.clone()))
+            .collect();
+        for (key, operation) in resubscribes {
+            let request_id = state.next_request_id();
+            state
+                .requests_subscribe
+                .insert(request_id, PendingSubscribe::Resubscribe { key, operation });
+        }
+        for (request_id, pending) in &state.requests_subscribe {
+            let (operation, params) = match pending {
+                PendingSubscribe::New(operation, params, _) => (operation, params),
+                PendingSubscribe::Resubscribe { key, .. } => match state.subscriptions.get(key) {
+                    Some(subscription) => (&subscription.operation, &subscription.params),
+                    None => continue,
+                },
+            };
+            send_subscribe(ws, *request_id, operation, params).await?;
+        }
 
         loop {
             tokio::select! {
               // Send close on shutdown signal
-              _ = &mut shutdown_receiver => {
+              _ = &mut *shutdown_receiver => {
                 let frame = CloseFrame { code: CloseCode::Normal, reason: "".into() };
-                ws.send(Message::Close(Some(frame))).await?;
-                ws.flush().await?;
-                break;
+                let _ = ws.send(Message::Close(Some(frame))).await;
+                let _ = ws.flush().await;
+                return Ok(WsExit::Shutdown);
               },
-              // Send `Message::Ping` each 10s if no any other communication
-              () = sleep(Duration::from_secs(ping_duration_seconds)) => {
+              // Send `Message::Ping` every `ping_duration_seconds`, the pong gives the round trip
+              _ = ping_interval.tick() => {
                 // Check if we've exceeded our failed ping threshold
                 if unmatched_pings >= max_failed_pings {
                   let frame = CloseFrame {
@@ -233,46 +827,53 @@ impl EnhancedWebsocket {
                     reason: format!("No pong received after {} pings", max_failed_pings).into()
                   };
 
-                  ws.send(Message::Close(Some(frame))).await?;
-                  ws.flush().await?;
+                  let _ = ws.send(Message::Close(Some(frame))).await;
+                  let _ = ws.flush().await;
 
-                  return Err(HeliusError::WebsocketClosed(
+                  return Ok(WsExit::Lost(
                     format!("Connection timeout: no pong received after {} pings", max_failed_pings)
                   ));
                 }
 
                 ws.send(Message::Ping(Vec::new())).await?;
+                health.ping();
                 unmatched_pings += 1;
               },
-              // Read message for subscribe
+              _ = report_interval.tick() => health.report(),
+              // Read message for subscribe, kept until answered so it is sent again on the next connection
               Some((operation, params, response_sender)) = subscribe_receiver.recv() => {
-                request_id += 1;
-                let method = format!("{operation}Subscribe");
-                let body = json!({"jsonrpc":"2.0","id":request_id,"method":method,"params":params});
-                ws.send(Message::Text(body.to_string())).await?;
-                requests_subscribe.insert(request_id, (operation, response_sender));
+                let request_id = state.next_request_id();
+                let pending = PendingSubscribe::New(operation.clone(), params.clone(), response_sender);
+                state.requests_subscribe.insert(request_id, pending);
+                send_subscribe(ws, request_id, &operation, &params).await?;
               },
               // Read message for unsubscribe
-              Some((operation, sid, response_sender)) = unsubscribe_receiver.recv() => {
-                subscriptions.remove(&sid);
-                request_id += 1;
-                let method = format!("{operation}Unsubscribe");
-                let text = json!({"jsonrpc":"2.0","id":request_id,"method":method,"params":[sid]}).to_string();
-                ws.send(Message::Text(text)).await?;
-                requests_unsubscribe.insert(request_id, response_sender);
+              Some((key, response_sender)) = unsubscribe_receiver.recv() => {
+                match state.subscriptions.remove(&key) {
+                  Some(ActiveSubscription { operation, sid: Some(sid), .. }) => {
+                    state.sids.remove(&sid);
+                    let request_id = state.next_request_id();
+                    state.requests_unsubscribe.insert(request_id, response_sender);
+                    send_unsubscribe(ws, request_id, &operation, sid).await?;
+                  },
+                  // not subscribed on this connection, nothing to tell the server
+                  _ => {
+                    let _ = response_sender.send(());
+                  },
+                }
               },
               // Read message for other requests
               Some((method, params, response_sender)) = request_receiver.recv() => {
-                request_id += 1;
+                let request_id = state.next_request_id();
                 let text = json!({"jsonrpc":"2.0","id":request_id,"method":method,"params":params}).to_string();
+                state.other_requests.insert(request_id, response_sender);
                 ws.send(Message::Text(text)).await?;
-                other_requests.insert(request_id, response_sender);
               }
               // Read incoming WebSocket message
               next_msg = ws.next() => {
                 let msg = match next_msg {
                   Some(msg) => msg?,
-                  None => break,
+                  None => return Ok(WsExit::Lost("Connection closed".to_string())),
                 };
 
                 // Reset unmatched_pings on any received frame
@@ -287,12 +888,14 @@ impl EnhancedWebsocket {
                       continue
                   },
                   Message::Pong(_data) => {
+                    health.pong();
                     continue;
                   },
-                  Message::Close(_frame) => break,
+                  Message::Close(frame) => return Ok(WsExit::Lost(format!("Connection closed by server: {:?}", frame))),
                   Message::Frame(_frame) => continue,
                 };
 
+                health.message();
                 let mut json: Map<String, Value> = serde_json::from_str(&text)?;
 
                 // Subscribe/Unsubscribe response, example:
@@ -315,7 +918,7 @@ impl EnhancedWebsocket {
                       }
                   });
 
-                  if let Some(response_sender) = other_requests.remove(&id) {
+                  if let Some(response_sender) = state.other_requests.remove(&id) {
                     match err {
                       Some(reason) => {
                         let _ = response_sender.send(Err(HeliusError::EnhancedWebsocket { reason, message: text.clone()}));
@@ -324,23 +927,27 @@ impl EnhancedWebsocket {
                         let json_result = json.get("result").ok_or_else(|| {
                             HeliusError::EnhancedWebsocket { reason: "missing `result` field".into(), message: text.clone() }
                         })?;
-                        if response_sender.send(Ok(json_result.clone())).is_err() {
-                            break;
-                        }
+                        let _ = response_sender.send(Ok(json_result.clone())); // do not care if receiver is closed
                       }
                     }
-                  } else if let Some(response_sender) = requests_unsubscribe.remove(&id) {
+                  } else if let Some(response_sender) = state.requests_unsubscribe.remove(&id) {
                     let _ = response_sender.send(()); // do not care if receiver is closed
-                  } else if let Some((operation, response_sender)) = requests_subscribe.remove(&id) {
-                    match err {
-                      Some(reason) => {
-                        let _ = response_sender.send(Err(HeliusError::EnhancedWebsocket { reason, message: text.clone()}));
+                  } else if let Some(pending) = state.requests_subscribe.remove(&id) {
+                    // Subscribe Id
+                    let sid = match err {
+                      Some(reason) => Err(HeliusError::EnhancedWebsocket { reason, message: text.clone() }),
+                      None => json.get("result").and_then(Value::as_u64).ok_or_else(|| {
+                        HeliusError::EnhancedWebsocket { reason: "invalid `result` field".into(), message: text.clone() }
+                      }),
+                    };
+
+                    match (pending, sid) {
+                      (PendingSubscribe::New(_, _, response_sender), Err(err)) => {
+                        let _ = response_sender.send(Err(err));
                       },
-                      None => {
-                        // Subscribe Id
-                        let sid = json.get("result").and_then(Value::as_u64).ok_or_else(|| {
-                          HeliusError::EnhancedWebsocket { reason: "invalid `result` field".into(), message: text.clone() }
-                        })?;
+                      (PendingSubscribe::New(operation, params, response_sender), Ok(sid)) => {
+                        let key = state.next_key;
+                        state.next_key += 1;
 
                         // Create notifications channel and unsubscribe function
                         let (notifications_sender, notifications_receiver) = mpsc::unbounded_channel();
@@ -348,20 +955,53 @@ impl EnhancedWebsocket {
                         let unsubscribe = Box::new(move || async move {
                           let (response_sender, response_receiver) = oneshot::channel();
                           // do nothing if ws already closed
-                          if unsubscribe_sender.send((operation, sid, response_sender)).is_ok() {
+                          if unsubscribe_sender.send((key, response_sender)).is_ok() {
                             let _ = response_receiver.await; // channel can be closed only if ws is closed
                           }
                         }.boxed());
 
-                        if response_sender.send(Ok((notifications_receiver, unsubscribe))).is_err() {
-                            break;
+                        // a caller that went away is unsubscribed with the first notification
+                        let _ = response_sender.send(Ok((notifications_receiver, unsubscribe)));
+                        state.sids.insert(sid, key);
+                        state.subscriptions.insert(key, ActiveSubscription {
+                          operation,
+                          params,
+                          notifications_sender,
+                          sid: Some(sid),
+                          disconnected: None,
+                        });
+                      },
+                      (PendingSubscribe::Resubscribe { key, operation }, Err(err)) => {
+                        // the last event, carrying the rejection, dropping the sender ends the stream
+                        if let Some(subscription) = state.subscriptions.remove(&key) {
+                          let disconnected_at = subscription.disconnected.map_or_else(Utc::now, |(at, _)| at);
+                          let reason = format!("{operation}Subscribe rejected: {err}");
+                          let closed = StreamClosed { disconnected_at, reason };
+                          let _ = subscription.notifications_sender.send(StreamEvent::Closed(closed));
                         }
-                        subscriptions.insert(sid, notifications_sender);
-                      }
+                      },
+                      (PendingSubscribe::Resubscribe { key, operation }, Ok(sid)) => {
+                        match state.subscriptions.get_mut(&key) {
+                          Some(subscription) => {
+                            subscription.sid = Some(sid);
+                            state.sids.insert(sid, key);
+                            if let Some((disconnected_at, reason)) = subscription.disconnected.take() {
+                              let gap = StreamGap { disconnected_at, resubscribed_at: Utc::now(), reason };
+                              let _ = subscription.notifications_sender.send(StreamEvent::Gap(gap));
+                            }
+                          },
+                          // unsubscribed while resubscribing
+                          None => {
+                            let request_id = state.next_request_id();
+                            let (response_sender, _response_receiver) = oneshot::channel();
+                            state.requests_unsubscribe.insert(request_id, response_sender);
+                            send_unsubscribe(ws, request_id, &operation, sid).await?;
+                          },
+                        }
+                      },
                     }
                   } else {
                       eprintln!("Unknown request id: {}", id);
-                      break;
                   }
                   continue;
                 }
@@ -370,23 +1010,36 @@ impl EnhancedWebsocket {
                 // `{"jsonrpc":"2.0","method":"logsNotification","params":{"result":{...},"subscription":3114862}}`
                 if let Some(Value::Object(params)) = json.get_mut("params") {
                   if let Some(sid) = params.get("subscription").and_then(Value::as_u64) {
-                    let mut unsubscribe_required = false;
+                    let subscription = state.sids.get(&sid).and_then(|key| state.subscriptions.get(key));
+                    let mut finished = false;
+                    let delivered = match (subscription, params.remove("result")) {
+                      (Some(subscription), Some(result)) => {
+                        health.notification(&result);
+                        finished = is_last_notification(&subscription.operation, &result);
+                        subscription.notifications_sender.send(StreamEvent::Notification(result)).is_ok()
+                      },
+                      (Some(_), None) => true,
+                      (None, _) => false,
+                    };
 
-                    if let Some(notifications_sender) = subscriptions.get(&sid) {
-                      if let Some(result) = params.remove("result") {
-                        if notifications_sender.send(result).is_err() {
-                          unsubscribe_required = true;
-                        }
+                    // nothing to resubscribe or unsubscribe, dropping the sender ends the stream
+                    if delivered && finished {
+                      if let Some(key) = state.sids.remove(&sid) {
+                        state.subscriptions.remove(&key);
                       }
-                    } else {
-                      unsubscribe_required = true;
                     }
 
-                    if unsubscribe_required {
+                    // the stream was dropped, or the server id is unknown
+                    if !delivered {
+                      if let Some(key) = state.sids.remove(&sid) {
+                        state.subscriptions.remove(&key);
+                      }
                       if let Some(Value::String(method)) = json.remove("method") {
                         if let Some(operation) = method.strip_suffix("Notification") {
+                          let request_id = state.next_request_id();
                           let (response_sender, _response_receiver) = oneshot::channel();
-                          let _ = unsubscribe_sender.send((operation.to_string(), sid, response_sender));
+                          state.requests_unsubscribe.insert(request_id, response_sender);
+                          send_unsubscribe(ws, request_id, operation, sid).await?;
                         }
                       }
                     }
@@ -395,7 +1048,5 @@ impl EnhancedWebsocket {
               }
             }
         }
-
-        Ok(())
     }
 }
@@ -0,0 +1,296 @@
+use futures_util::{SinkExt, StreamExt};
+use helius::types::{
+    RpcTransactionsConfig, SignatureNotification, TransactionSubscribeFilter, TransactionSubscribeOptions,
+};
+use helius::websocket::{ConnectionState, EnhancedWebsocket, ReconnectPolicy, StreamEvent};
+use serde_json::{json, Value};
+use solana_rpc_client_api::config::{RpcSignatureSubscribeConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+use solana_rpc_client_api::response::RpcSignatureResult;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+fn notification(sid: u64, signature: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "transactionNotification",
+        "params": {
+            "subscription": sid,
+            "result": {
+                "transaction": {"transaction": ["AQ==", "base64"], "meta": null},
+                "signature": signature,
+                "slot": 1
+            }
+        }
+    })
+    .to_string()
+}
+
+#[test]
+fn test_backoff_is_capped() {
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+        ..Default::default()
+    };
+    let first = policy.backoff(0);
+    assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+    let last = policy.backoff(40);
+    assert!(last >= Duration::from_millis(500) && last <= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_resubscribe_after_reconnect() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (params_sender, mut params_receiver) = mpsc::unbounded_channel::<Value>();
+
+    // answers the subscribe of each connection, sends one notification, then drops the first connection
+    tokio::spawn(async move {
+        for (sid, signature) in [(1, "sig1"), (7, "sig2")] {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            while let Some(Ok(message)) = ws.next().await {
+                let Message::Text(text) = message else {
+                    continue;
+                };
+                let request: Value = serde_json::from_str(&text).unwrap();
+                assert_eq!("transactionSubscribe", request["method"]);
+                params_sender.send(request["params"].clone()).unwrap();
+                let response = json!({"jsonrpc": "2.0", "result": sid, "id": request["id"]});
+                ws.send(Message::Text(response.to_string())).await.unwrap();
+                ws.send(Message::Text(notification(sid, signature))).await.unwrap();
+                break;
+            }
+            if sid == 7 {
+                // keep the second connection open
+                while ws.next().await.is_some() {}
+            }
+        }
+    });
+
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let ws = EnhancedWebsocket::new_with_reconnect(&url, None, None, policy)
+        .await
+        .unwrap();
+    let config = RpcTransactionsConfig {
+        filter: TransactionSubscribeFilter::standard(&Pubkey::new_unique()),
+        options: TransactionSubscribeOptions::default(),
+    };
+    let (mut stream, _unsubscribe) = ws.transaction_subscribe_with_gaps(config).await.unwrap();
+
+    match stream.next().await {
+        Some(StreamEvent::Notification(notification)) => assert_eq!("sig1", notification.signature),
+        other => panic!("expected a notification, got {:?}", other),
+    }
+    assert!(matches!(stream.next().await, Some(StreamEvent::Gap(_))));
+    match stream.next().await {
+        Some(StreamEvent::Notification(notification)) => assert_eq!("sig2", notification.signature),
+        other => panic!("expected a notification, got {:?}", other),
+    }
+
+    // subscribed again with the original params
+    let first = params_receiver.recv().await.unwrap();
+    let second = params_receiver.recv().await.unwrap();
+    assert_eq!(first, second);
+}
+
+#[tokio::test]
+async fn test_rejected_resubscribe_closes_stream() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+
+    // answers the subscribe of the first connection then drops it, rejects the resubscribe on the second
+    tokio::spawn(async move {
+        for accepted in [true, false] {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            while let Some(Ok(message)) = ws.next().await {
+                let Message::Text(text) = message else {
+                    continue;
+                };
+                let request: Value = serde_json::from_str(&text).unwrap();
+                let response = if accepted {
+                    json!({"jsonrpc": "2.0", "result": 1, "id": request["id"]})
+                } else {
+                    json!({"jsonrpc": "2.0", "error": {"code": -32602, "message": "Invalid params"}, "id": request["id"]})
+                };
+                ws.send(Message::Text(response.to_string())).await.unwrap();
+                break;
+            }
+            if !accepted {
+                while ws.next().await.is_some() {}
+            }
+        }
+    });
+
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let ws = EnhancedWebsocket::new_with_reconnect(&url, None, None, policy)
+        .await
+        .unwrap();
+    let config = RpcTransactionsConfig {
+        filter: TransactionSubscribeFilter::standard(&Pubkey::new_unique()),
+        options: TransactionSubscribeOptions::default(),
+    };
+    let (mut stream, _unsubscribe) = ws.transaction_subscribe_with_gaps(config).await.unwrap();
+
+    match stream.next().await {
+        Some(StreamEvent::Closed(closed)) => {
+            assert!(
+                closed.reason.starts_with("transactionSubscribe rejected"),
+                "{}",
+                closed.reason
+            );
+            assert!(closed.reason.contains("Invalid params"), "{}", closed.reason);
+        }
+        other => panic!("expected the stream to be closed, got {:?}", other),
+    }
+    assert!(stream.next().await.is_none());
+}
+
+// answers the first subscribe with `sid`, then sends `results` as notifications of `method`
+async fn serve_subscription(
+    method: &'static str,
+    sid: u64,
+    results: Vec<Value>,
+) -> (String, mpsc::UnboundedReceiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (request_sender, request_receiver) = mpsc::unbounded_channel::<Value>();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        while let Some(Ok(message)) = ws.next().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let request: Value = serde_json::from_str(&text).unwrap();
+            request_sender.send(request.clone()).unwrap();
+            let response = json!({"jsonrpc": "2.0", "result": sid, "id": request["id"]});
+            ws.send(Message::Text(response.to_string())).await.unwrap();
+            for result in &results {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": method,
+                    "params": {"subscription": sid, "result": result}
+                });
+                ws.send(Message::Text(notification.to_string())).await.unwrap();
+            }
+        }
+    });
+    (url, request_receiver)
+}
+
+#[tokio::test]
+async fn test_logs_subscribe() {
+    let result = json!({
+        "context": {"slot": 5},
+        "value": {"signature": "sig1", "err": null, "logs": ["Program log: Instruction: Buy"]}
+    });
+    let (url, mut requests) = serve_subscription("logsNotification", 3, vec![result]).await;
+    let ws = EnhancedWebsocket::new(&url, None, None).await.unwrap();
+
+    let filter = RpcTransactionLogsFilter::Mentions(vec!["6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P".to_string()]);
+    let config = RpcTransactionLogsConfig {
+        commitment: Some(CommitmentConfig::processed()),
+    };
+    let (mut stream, _unsubscribe) = ws.logs_subscribe(filter, config).await.unwrap();
+    let notification = stream.next().await.unwrap();
+    assert_eq!(5, notification.context.slot);
+    assert_eq!("sig1", notification.value.signature);
+    assert_eq!(vec!["Program log: Instruction: Buy"], notification.value.logs);
+
+    let request = requests.recv().await.unwrap();
+    assert_eq!("logsSubscribe", request["method"]);
+    assert_eq!(
+        json!([{"mentions": ["6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P"]}, {"commitment": "processed"}]),
+        request["params"]
+    );
+}
+
+#[tokio::test]
+async fn test_signature_stream_ends_once_processed() {
+    let results = vec![
+        json!({"context": {"slot": 5}, "value": "receivedSignature"}),
+        json!({"context": {"slot": 6}, "value": {"err": null}}),
+    ];
+    let (url, mut requests) = serve_subscription("signatureNotification", 4, results).await;
+    let ws = EnhancedWebsocket::new(&url, None, None).await.unwrap();
+
+    let config = RpcSignatureSubscribeConfig {
+        commitment: Some(CommitmentConfig::confirmed()),
+        enable_received_notification: Some(true),
+    };
+    let (stream, unsubscribe) = ws
+        .signature_subscribe(&Signature::default(), Some(config))
+        .await
+        .unwrap();
+    let notifications: Vec<SignatureNotification> = stream.collect().await;
+    assert_eq!(2, notifications.len());
+    assert!(matches!(
+        notifications[0].value,
+        RpcSignatureResult::ReceivedSignature(_)
+    ));
+    assert!(matches!(
+        notifications[1].value,
+        RpcSignatureResult::ProcessedSignature(_)
+    ));
+
+    let request = requests.recv().await.unwrap();
+    assert_eq!("signatureSubscribe", request["method"]);
+
+    // dropped once processed, the server is not asked to unsubscribe
+    unsubscribe().await;
+    assert!(requests.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_parse_errors_and_health() {
+    let results = vec![
+        json!({"unexpected": true}),
+        json!({
+            "transaction": {"transaction": ["AQ==", "base64"], "meta": null},
+            "signature": "sig1",
+            "slot": 42
+        }),
+    ];
+    let (url, _requests) = serve_subscription("transactionNotification", 9, results).await;
+    let ws = EnhancedWebsocket::new(&url, None, None).await.unwrap();
+    let mut health = ws.health();
+    assert_eq!(ConnectionState::Connected, health.borrow().state);
+
+    let config = RpcTransactionsConfig {
+        filter: TransactionSubscribeFilter::standard(&Pubkey::new_unique()),
+        options: TransactionSubscribeOptions::default(),
+    };
+    let (mut stream, _unsubscribe) = ws.transaction_subscribe_with_gaps(config).await.unwrap();
+    match stream.next().await {
+        Some(StreamEvent::ParseError(parse_error)) => {
+            assert_eq!("transaction", parse_error.operation);
+            assert_eq!(json!({"unexpected": true}), parse_error.value);
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+    assert!(matches!(stream.next().await, Some(StreamEvent::Notification(_))));
+
+    // published with the next report, every second
+    let reported = tokio::time::timeout(
+        Duration::from_secs(3),
+        health.wait_for(|health| health.last_notification_slot == Some(42) && health.parse_errors == 1),
+    )
+    .await
+    .unwrap()
+    .unwrap()
+    .clone();
+    assert!(reported.last_notification_at.is_some());
+    assert!(reported.connected_at <= reported.last_notification_at);
+}
@@ -1,17 +1,87 @@
 use csv::WriterBuilder;
+use flate2::write::GzEncoder;
+use helius::types::enhanced_websocket::TransactionCommitment;
+use log::error;
 use serde::{Deserialize, Serialize};
-use std::{fs::File, path::Path, time::Instant};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
+use crate::pumpfun_instruction_parser::PayloadSource;
+
+/// Writes `BatchCsvRecord`s into csv files under `base_dir`. A file is written as
+/// `<name>.partial` and renamed to `<name>` once closed, so a file without the suffix is
+/// complete. A crash leaves the `.partial` file behind, holding every record up to the last flush.
 pub struct BatchCsvWriter {
     base_dir: String,
-    start: Instant,
-    record_interval: u64,
+    options: BatchCsvWriterOptions,
+    last_flush: Instant,
     current_record_interval: u64,
-    seconds_interval: u64,
-    csv_writer: csv::Writer<File>,
+    csv_file: Option<CsvFile>,
+}
+
+/// When a `BatchCsvWriter` flushes and rotates, and how it compresses
+#[derive(Debug, Clone)]
+pub struct BatchCsvWriterOptions {
+    pub flush_records: u64,          // flush after this many records
+    pub flush_interval: Duration,    // or once this long has passed since the last flush
+    pub max_file_bytes: Option<u64>, // rotate once this many csv bytes are written, before compression
+    pub max_file_age: Duration,      // rotate once the file is this old
+    pub compression: CsvCompression,
+}
+
+impl Default for BatchCsvWriterOptions {
+    fn default() -> Self {
+        Self {
+            flush_records: 1000,
+            flush_interval: Duration::from_secs(5),
+            max_file_bytes: None,
+            max_file_age: Duration::from_secs(3600),
+            compression: CsvCompression::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CsvCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl CsvCompression {
+    fn extension(&self) -> &'static str {
+        match self {
+            CsvCompression::None => "csv",
+            CsvCompression::Gzip => "csv.gz",
+            CsvCompression::Zstd => "csv.zst",
+        }
+    }
+}
+
+impl FromStr for CsvCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(CsvCompression::None),
+            "gzip" => Ok(CsvCompression::Gzip),
+            "zstd" => Ok(CsvCompression::Zstd),
+            _ => Err(format!(
+                "unknown compression {}, expected none, gzip or zstd",
+                s
+            )),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct BatchCsvRecord {
     pub action: String,
     pub slot: u64,
@@ -31,6 +101,155 @@ pub struct BatchCsvRecord {
 
     pub bonding_curve: Option<String>,
     pub associated_bonding_curve: Option<String>,
+
+    pub router: Option<String>,
+
+    pub sol_amount: Option<u64>,
+    pub token_amount: Option<u64>,
+    pub virtual_sol_reserves: Option<u64>,
+    pub virtual_token_reserves: Option<u64>,
+    pub trade_timestamp: Option<i64>,
+
+    pub error_code: Option<u32>,
+    pub error_name: Option<String>,
+    pub compute_units_consumed: Option<u64>,
+
+    pub fee_recipient: Option<String>,
+    pub fee_basis_points: Option<u64>,
+    pub initial_virtual_token_reserves: Option<u64>,
+    pub initial_virtual_sol_reserves: Option<u64>,
+    pub initial_real_token_reserves: Option<u64>,
+    pub token_total_supply: Option<u64>,
+
+    pub token_delta: Option<i64>,
+    pub sol_delta: Option<i64>,
+
+    pub cu_limit: Option<u32>,
+    pub priority_fee_micro_lamports: Option<u64>,
+    pub jito_tip_lamports: Option<u64>,
+
+    pub source: Option<PayloadSource>,
+    pub commitment: Option<TransactionCommitment>,
+    pub received_at: Option<i64>,
+    pub block_time: Option<i64>,
+    pub is_paper_trade: Option<bool>,
+}
+
+// Counts the bytes the csv writer hands down, before compression
+struct CountingWriter<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+enum CsvSink {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl CsvSink {
+    fn new(file: File, compression: CsvCompression) -> io::Result<Self> {
+        Ok(match compression {
+            CsvCompression::None => CsvSink::Plain(file),
+            CsvCompression::Gzip => {
+                CsvSink::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            CsvCompression::Zstd => CsvSink::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    // write the compression trailer
+    fn finish(self) -> io::Result<File> {
+        match self {
+            CsvSink::Plain(file) => Ok(file),
+            CsvSink::Gzip(encoder) => encoder.finish(),
+            CsvSink::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl Write for CsvSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CsvSink::Plain(file) => file.write(buf),
+            CsvSink::Gzip(encoder) => encoder.write(buf),
+            CsvSink::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    // gzip and zstd flush a complete block, so a crash loses nothing already flushed
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CsvSink::Plain(file) => file.flush(),
+            CsvSink::Gzip(encoder) => encoder.flush(),
+            CsvSink::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+struct CsvFile {
+    partial_path: PathBuf,
+    final_path: PathBuf,
+    opened: Instant,
+    csv_writer: csv::Writer<CountingWriter<CsvSink>>,
+}
+
+impl CsvFile {
+    fn create(base_dir: &str, compression: CsvCompression) -> anyhow::Result<Self> {
+        let name = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let extension = compression.extension();
+        // a size rotation can open several files within a second
+        let mut final_path = Path::new(base_dir).join(format!("{}.{}", name, extension));
+        let mut n = 1;
+        while final_path.exists() || partial_path(&final_path).exists() {
+            final_path = Path::new(base_dir).join(format!("{}-{}.{}", name, n, extension));
+            n += 1;
+        }
+        let partial_path = partial_path(&final_path);
+
+        let file = File::create(&partial_path)?;
+        let sink = CountingWriter {
+            inner: CsvSink::new(file, compression)?,
+            bytes: 0,
+        };
+
+        Ok(Self {
+            partial_path,
+            final_path,
+            opened: Instant::now(),
+            csv_writer: WriterBuilder::new().from_writer(sink),
+        })
+    }
+
+    fn bytes(&self) -> u64 {
+        self.csv_writer.get_ref().bytes
+    }
+
+    fn close(self) -> anyhow::Result<()> {
+        let sink = self.csv_writer.into_inner().map_err(|e| e.into_error())?;
+        let file = sink.inner.finish()?;
+        file.sync_all()?;
+        fs::rename(&self.partial_path, &self.final_path)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
 }
 
 impl BatchCsvWriter {
@@ -39,57 +258,245 @@ impl BatchCsvWriter {
         record_interval: u64,
         seconds_interval: u64,
     ) -> anyhow::Result<Self> {
-        assert!(Path::new(&base_dir).exists());
+        let options = BatchCsvWriterOptions {
+            flush_records: record_interval,
+            max_file_age: Duration::from_secs(seconds_interval),
+            ..Default::default()
+        };
+        Self::with_options(base_dir, options)
+    }
 
-        let file = Self::create_csv_file(&base_dir)?;
+    pub fn with_options(base_dir: String, options: BatchCsvWriterOptions) -> anyhow::Result<Self> {
+        if !Path::new(&base_dir).is_dir() {
+            anyhow::bail!("csv directory {} does not exist", base_dir);
+        }
+
+        let csv_file = CsvFile::create(&base_dir, options.compression)?;
 
         Ok(Self {
-            start: Instant::now(),
-            record_interval,
-            current_record_interval: 0,
-            seconds_interval,
             base_dir,
-            csv_writer: WriterBuilder::new().from_writer(file),
+            options,
+            last_flush: Instant::now(),
+            current_record_interval: 0,
+            csv_file: Some(csv_file),
         })
     }
 
+    /// Flushes and rotates as configured, both checked on each write and on `tick`.
     pub fn write(&mut self, record: BatchCsvRecord) -> anyhow::Result<()> {
+        let csv_file = match self.csv_file.as_mut() {
+            Some(csv_file) => csv_file,
+            None => self
+                .csv_file
+                .insert(CsvFile::create(&self.base_dir, self.options.compression)?),
+        };
+        csv_file.csv_writer.serialize(record)?;
         self.current_record_interval += 1;
-        self.csv_writer.serialize(record)?;
 
-        // check every 100 records
-        if self.current_record_interval > self.record_interval {
-            if self.start.elapsed().as_secs() > self.seconds_interval {
-                self.reset()?;
-            }
-            self.current_record_interval = 0;
+        if self.current_record_interval >= self.options.flush_records {
+            self.flush()?;
+        }
+        self.tick()
+    }
+
+    /// Flushes and rotates once due by time. Call it every second or so, so an idle stream is
+    /// still flushed and its `.partial` file closed.
+    pub fn tick(&mut self) -> anyhow::Result<()> {
+        if self.current_record_interval > 0
+            && self.last_flush.elapsed() >= self.options.flush_interval
+        {
+            self.flush()?;
+        }
+        if self.rotation_due() {
+            self.reset()?;
         }
+        Ok(())
+    }
 
+    // the size only counts flushed bytes, the csv writer buffers the rest
+    fn rotation_due(&self) -> bool {
+        self.csv_file.as_ref().is_some_and(|csv_file| {
+            csv_file.opened.elapsed() >= self.options.max_file_age
+                || self
+                    .options
+                    .max_file_bytes
+                    .is_some_and(|max_file_bytes| csv_file.bytes() >= max_file_bytes)
+        })
+    }
+
+    /// Flush the written records through the compressor into the `.partial` file.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some(csv_file) = self.csv_file.as_mut() {
+            csv_file.csv_writer.flush()?;
+        }
+        self.last_flush = Instant::now();
+        self.current_record_interval = 0;
         Ok(())
     }
 
-    // New helper function to create a CSV file
-    fn create_csv_file(base_dir: &str) -> anyhow::Result<File> {
-        let file_name = format!("{}.csv", chrono::Local::now().format("%Y%m%d-%H%M%S"));
-        let file_path = Path::new(base_dir).join(file_name);
-        let file = File::create(file_path)?;
-        Ok(file)
+    /// Close the current file and rename it to its final name.
+    pub fn close(mut self) -> anyhow::Result<()> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some(csv_file) = self.csv_file.take() {
+            csv_file.close()?;
+        }
+        Ok(())
     }
 
     fn reset(&mut self) -> anyhow::Result<()> {
-        let _ = self.csv_writer.flush();
+        self.finish()?;
 
-        self.start = Instant::now();
+        self.last_flush = Instant::now();
         self.current_record_interval = 0;
-
-        let file = Self::create_csv_file(&self.base_dir)?;
-        self.csv_writer = WriterBuilder::new().from_writer(file);
+        // the next file is opened by the next write
 
         Ok(())
     }
 
-    // get the current record count
+    // get the count of records written since the last flush
     pub fn current_count(&self) -> u64 {
         self.current_record_interval
     }
 }
+
+impl Drop for BatchCsvWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            error!("Failed to close csv file: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pumpfun_instruction_parser::WrapPayload;
+    use std::io::Read;
+
+    fn record(slot: u64) -> BatchCsvRecord {
+        let mut record: BatchCsvRecord = WrapPayload::Unknown.into();
+        record.slot = slot;
+        record
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn read_slots(content: &[u8]) -> Vec<u64> {
+        csv::Reader::from_reader(content)
+            .deserialize::<BatchCsvRecord>()
+            .map(|record| record.unwrap().slot)
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_csv_writer_partial_until_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer =
+            BatchCsvWriter::new(dir.path().to_str().unwrap().to_string(), 2, 3600).unwrap();
+        for slot in 0..3 {
+            writer.write(record(slot)).unwrap();
+        }
+
+        // the first two records were flushed into the partial file
+        let names = file_names(dir.path());
+        assert_eq!(1, names.len());
+        assert!(names[0].ends_with(".csv.partial"));
+        let partial = fs::read(dir.path().join(&names[0])).unwrap();
+        assert_eq!(vec![0, 1], read_slots(&partial));
+
+        drop(writer);
+        let names = file_names(dir.path());
+        assert_eq!(1, names.len());
+        assert!(names[0].ends_with(".csv"));
+        let content = fs::read(dir.path().join(&names[0])).unwrap();
+        assert_eq!(vec![0, 1, 2], read_slots(&content));
+    }
+
+    #[test]
+    fn test_batch_csv_writer_rotates_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = BatchCsvWriterOptions {
+            flush_records: 1,
+            max_file_bytes: Some(1),
+            ..Default::default()
+        };
+        let mut writer =
+            BatchCsvWriter::with_options(dir.path().to_str().unwrap().to_string(), options)
+                .unwrap();
+        for slot in 0..3 {
+            writer.write(record(slot)).unwrap();
+        }
+        writer.close().unwrap();
+
+        // one file per record
+        let names = file_names(dir.path());
+        assert_eq!(3, names.len());
+        assert!(names.iter().all(|name| name.ends_with(".csv")));
+    }
+
+    #[test]
+    fn test_batch_csv_writer_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = BatchCsvWriterOptions {
+            compression: CsvCompression::Gzip,
+            ..Default::default()
+        };
+        let mut writer =
+            BatchCsvWriter::with_options(dir.path().to_str().unwrap().to_string(), options)
+                .unwrap();
+        for slot in 0..3 {
+            writer.write(record(slot)).unwrap();
+        }
+        writer.close().unwrap();
+
+        let names = file_names(dir.path());
+        assert_eq!(1, names.len());
+        assert!(names[0].ends_with(".csv.gz"));
+        let mut content = vec![];
+        flate2::read::GzDecoder::new(File::open(dir.path().join(&names[0])).unwrap())
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(vec![0, 1, 2], read_slots(&content));
+    }
+
+    #[test]
+    fn test_batch_csv_writer_tick() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = BatchCsvWriterOptions {
+            flush_interval: Duration::ZERO,
+            max_file_age: Duration::from_millis(500),
+            ..Default::default()
+        };
+        let mut writer =
+            BatchCsvWriter::with_options(dir.path().to_str().unwrap().to_string(), options)
+                .unwrap();
+        writer.write(record(0)).unwrap();
+        assert_eq!(0, writer.current_count());
+
+        // idle, the file is closed once it is old enough
+        writer.tick().unwrap();
+        assert!(file_names(dir.path())[0].ends_with(".csv.partial"));
+        std::thread::sleep(Duration::from_millis(500));
+        writer.tick().unwrap();
+        let names = file_names(dir.path());
+        assert_eq!(1, names.len());
+        assert!(names[0].ends_with(".csv"));
+        let content = fs::read(dir.path().join(&names[0])).unwrap();
+        assert_eq!(vec![0], read_slots(&content));
+    }
+
+    #[test]
+    fn test_batch_csv_writer_missing_dir() {
+        assert!(BatchCsvWriter::new("/nonexistent/csv".to_string(), 2, 3600).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use arrow::datatypes::{DataType, FieldRef, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use log::error;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde_arrow::schema::{SchemaLike, TracingOptions};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{fs, fs::File, path::Path, time::Instant};
+
+use crate::batch_csv_writer::{partial_path, BatchCsvRecord};
+
+/// Writes `BatchCsvRecord`s into parquet files, rotated like `BatchCsvWriter`: every
+/// `record_interval` records the buffered rows are written as a row group, and a new file is
+/// started once `seconds_interval` has passed, on a write or a `tick`. A file is written as
+/// `<name>.parquet.partial` and renamed once it is closed, by rotation, `close` or drop.
+pub struct BatchParquetWriter {
+    base_dir: String,
+    record_interval: u64,
+    current_record_interval: u64,
+    unclosed_records: u64, // in the current file, lost by a crash until it is closed
+    seconds_interval: u64,
+    schema: SchemaRef,
+    records: Vec<BatchCsvRecord>,
+    parquet_file: Option<ParquetFile>,
+}
+
+/// The arrow schema of a `BatchCsvRecord`, traced from its serde fields, in field order
+pub fn batch_record_schema() -> anyhow::Result<Schema> {
+    let options = TracingOptions::default()
+        .strings_as_large_utf8(false)
+        .enums_without_data_as_strings(true);
+    let fields: Vec<FieldRef> = Vec::<FieldRef>::from_type::<BatchCsvRecord>(options)?
+        .into_iter()
+        .map(|field| match field.data_type() {
+            // enums are written with their serde name, the same text as in the csv files
+            DataType::Dictionary(_, value) => {
+                let data_type = value.as_ref().clone();
+                Arc::new(field.as_ref().clone().with_data_type(data_type))
+            }
+            _ => field,
+        })
+        .collect();
+    Ok(Schema::new(fields))
+}
+
+fn to_record_batch(schema: &Schema, records: &[BatchCsvRecord]) -> anyhow::Result<RecordBatch> {
+    Ok(serde_arrow::to_record_batch(schema.fields(), records)?)
+}
+
+struct ParquetFile {
+    partial_path: PathBuf,
+    final_path: PathBuf,
+    opened: Instant,
+    arrow_writer: ArrowWriter<File>,
+}
+
+impl ParquetFile {
+    fn create(base_dir: &str, schema: SchemaRef) -> anyhow::Result<Self> {
+        let name = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        // a time rotation on every tick can open several files within a second
+        let mut final_path = Path::new(base_dir).join(format!("{}.parquet", name));
+        let mut n = 1;
+        while final_path.exists() || partial_path(&final_path).exists() {
+            final_path = Path::new(base_dir).join(format!("{}-{}.parquet", name, n));
+            n += 1;
+        }
+        let partial_path = partial_path(&final_path);
+
+        let file = File::create(&partial_path)?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+
+        Ok(Self {
+            partial_path,
+            final_path,
+            opened: Instant::now(),
+            arrow_writer: ArrowWriter::try_new(file, schema, Some(properties))?,
+        })
+    }
+
+    fn close(self) -> anyhow::Result<()> {
+        let file = self.arrow_writer.into_inner()?;
+        file.sync_all()?;
+        fs::rename(&self.partial_path, &self.final_path)?;
+        Ok(())
+    }
+}
+
+impl BatchParquetWriter {
+    pub fn new(
+        base_dir: String,
+        record_interval: u64,
+        seconds_interval: u64,
+    ) -> anyhow::Result<Self> {
+        if !Path::new(&base_dir).is_dir() {
+            anyhow::bail!("parquet directory {} does not exist", base_dir);
+        }
+
+        let schema: SchemaRef = Arc::new(batch_record_schema()?);
+        let parquet_file = ParquetFile::create(&base_dir, schema.clone())?;
+
+        Ok(Self {
+            record_interval,
+            current_record_interval: 0,
+            unclosed_records: 0,
+            seconds_interval,
+            base_dir,
+            schema,
+            records: Vec::with_capacity(record_interval as usize),
+            parquet_file: Some(parquet_file),
+        })
+    }
+
+    pub fn write(&mut self, record: BatchCsvRecord) -> anyhow::Result<()> {
+        if self.parquet_file.is_none() {
+            self.parquet_file = Some(ParquetFile::create(&self.base_dir, self.schema.clone())?);
+        }
+        self.current_record_interval += 1;
+        self.unclosed_records += 1;
+        self.records.push(record);
+
+        if self.current_record_interval >= self.record_interval {
+            self.flush()?;
+            self.current_record_interval = 0;
+        }
+        self.tick()
+    }
+
+    /// Rotates once the file is `seconds_interval` old. Call it every second or so, so the records
+    /// of an idle stream are still closed into a readable file.
+    pub fn tick(&mut self) -> anyhow::Result<()> {
+        let expired = self
+            .parquet_file
+            .as_ref()
+            .is_some_and(|file| file.opened.elapsed().as_secs() >= self.seconds_interval);
+        if self.unclosed_records > 0 && expired {
+            self.reset()?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered records as a row group.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if self.records.is_empty() {
+            return Ok(());
+        }
+        let Some(parquet_file) = self.parquet_file.as_mut() else {
+            anyhow::bail!("parquet writer is closed");
+        };
+        let batch = to_record_batch(&self.schema, &self.records)?;
+        parquet_file.arrow_writer.write(&batch)?;
+        parquet_file.arrow_writer.flush()?;
+        self.records.clear();
+        Ok(())
+    }
+
+    /// Write the buffered records and the parquet footer, and give the file its final name.
+    pub fn close(mut self) -> anyhow::Result<()> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.flush()?;
+        if let Some(parquet_file) = self.parquet_file.take() {
+            parquet_file.close()?;
+        }
+        self.unclosed_records = 0;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> anyhow::Result<()> {
+        self.finish()?;
+
+        self.current_record_interval = 0;
+        // the next file is opened by the next write
+
+        Ok(())
+    }
+
+    // get the current record count
+    pub fn current_count(&self) -> u64 {
+        self.current_record_interval
+    }
+
+    // get the count of records written since the last file was closed
+    pub fn unclosed_count(&self) -> u64 {
+        self.unclosed_records
+    }
+}
+
+impl Drop for BatchParquetWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            error!("Failed to close parquet file: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pumpfun_instruction_parser::PayloadSource;
+    use arrow::array::{Int64Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn record(slot: u64) -> BatchCsvRecord {
+        BatchCsvRecord {
+            action: "buy".to_string(),
+            slot,
+            signature: "sig".to_string(),
+            mint_pk: Some("8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump".to_string()),
+            user_pk: Some("DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47".to_string()),
+            name: None,
+            symbol: None,
+            uri: None,
+            amount_buy: Some(35758322578),
+            max_sol_cost: Some(37546238706),
+            amount_sell: None,
+            min_sol_output: None,
+            bonding_curve: None,
+            associated_bonding_curve: None,
+            router: None,
+            sol_amount: Some(37546238),
+            token_amount: Some(35758322578),
+            virtual_sol_reserves: None,
+            virtual_token_reserves: None,
+            trade_timestamp: Some(1735689600),
+            error_code: None,
+            error_name: None,
+            compute_units_consumed: None,
+            fee_recipient: None,
+            fee_basis_points: None,
+            initial_virtual_token_reserves: None,
+            initial_virtual_sol_reserves: None,
+            initial_real_token_reserves: None,
+            token_total_supply: None,
+            token_delta: Some(35758322578),
+            sol_delta: Some(-37546238),
+            cu_limit: Some(63909),
+            priority_fee_micro_lamports: Some(140999),
+            jito_tip_lamports: Some(0),
+            source: Some(PayloadSource::HeliusWs),
+            commitment: None,
+            received_at: Some(1735689600123),
+            block_time: Some(1735689600),
+            is_paper_trade: Some(false),
+        }
+    }
+
+    #[test]
+    fn test_batch_parquet_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_dir = dir.path().to_str().unwrap().to_string();
+
+        let mut writer = BatchParquetWriter::new(base_dir, 2, 3600).unwrap();
+        for slot in 0..5 {
+            writer.write(record(slot)).unwrap();
+        }
+        writer.close().unwrap();
+
+        let path = std::fs::read_dir(dir.path())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(5, batches.iter().map(|b| b.num_rows()).sum::<usize>());
+
+        let batch = &batches[0];
+        assert_eq!(
+            batch_record_schema().unwrap().fields(),
+            batch.schema().fields()
+        );
+        let source = batch
+            .column_by_name("source")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!("HeliusWs", source.value(0));
+        let sol_delta = batch
+            .column_by_name("sol_delta")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(-37546238, sol_delta.value(0));
+    }
+
+    #[test]
+    fn test_batch_parquet_writer_tick() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_dir = dir.path().to_str().unwrap().to_string();
+
+        let mut writer = BatchParquetWriter::new(base_dir, 1000, 1).unwrap();
+        writer.write(record(0)).unwrap();
+        writer.tick().unwrap();
+        assert_eq!(1, writer.unclosed_count());
+
+        // idle, the file is closed once it is old enough
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        writer.tick().unwrap();
+        assert_eq!(0, writer.unclosed_count());
+        let path = std::fs::read_dir(dir.path())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            1,
+            reader.map(|batch| batch.unwrap().num_rows()).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_batch_parquet_writer_file_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_dir = dir.path().to_str().unwrap().to_string();
+
+        // rotated on every write, several files within the same second
+        let mut writer = BatchParquetWriter::new(base_dir, 1000, 0).unwrap();
+        for slot in 0..4 {
+            writer.write(record(slot)).unwrap();
+        }
+        assert_eq!(0, writer.unclosed_count());
+
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(4, names.len());
+        assert!(names.iter().all(|name| name.ends_with(".parquet")));
+    }
+
+    #[test]
+    fn test_batch_parquet_writer_missing_dir() {
+        assert!(BatchParquetWriter::new("/nonexistent/parquet".to_string(), 2, 3600).is_err());
+    }
+}
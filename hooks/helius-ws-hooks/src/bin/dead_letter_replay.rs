@@ -0,0 +1,98 @@
+use clap::Parser;
+use helius_ws_hooks::dead_letter::{DeadLetter, DeadLetterReason, Replay};
+use helius_ws_hooks::ingest::config::load_pipeline_config;
+use helius_ws_hooks::ingest::sink::build_sink;
+use helius_ws_hooks::redis_queue::{RedisProducer, RedisTransport};
+use log::{error, info, Level};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Resubmits dead letters to a queue, once whatever failed them is fixed. A letter of a failed
+/// sink goes only to that sink, of the `--pipeline` that wrote it.
+#[derive(Parser)]
+struct Args {
+    #[clap(short = 'r', default_value = "redis://127.0.0.1/")]
+    redis_url: String,
+    /// Redis list the dead letters were pushed to
+    #[clap(short = 'k', long, default_value = "dead_letters")]
+    dead_letter_key: String,
+    /// Read the dead letters from a json lines file instead, which is left as is
+    #[clap(short = 'f', long)]
+    file: Option<String>,
+    /// Only replay dead letters of this reason: deserialize, decode or sink
+    #[clap(long)]
+    reason: Option<DeadLetterReason>,
+    /// Queue the payloads are resubmitted to
+    #[clap(short = 'q', default_value = "events")]
+    redis_queue: String,
+    /// Queue transport: list (RPUSH/BLPOP) or stream (XADD/XREADGROUP)
+    #[clap(long, default_value = "list")]
+    transport: RedisTransport,
+    /// Approximate cap on the stream length
+    #[clap(long, default_value = "1000000")]
+    max_len: usize,
+    /// The ingest pipeline whose sinks the sink letters are replayed to, kept without it
+    #[clap(long)]
+    pipeline: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
+    let args = Args::parse();
+
+    let redis_client = redis::Client::open(args.redis_url.as_str())?;
+    let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut sinks = vec![];
+    if let Some(pipeline) = &args.pipeline {
+        for sink in &load_pipeline_config(pipeline)?.sinks {
+            sinks.push(build_sink(sink).await?);
+        }
+    }
+    let mut replay = Replay {
+        reason: args.reason,
+        queue: Some(RedisProducer::new(
+            redis_conn.clone(),
+            args.redis_queue.clone(),
+            args.transport,
+            args.max_len,
+        )),
+        sinks,
+    };
+
+    let result = match &args.file {
+        Some(file) => replay_file(&mut replay, file).await,
+        None => {
+            replay
+                .replay_redis(&mut redis_conn, &args.dead_letter_key)
+                .await
+        }
+    };
+    // the sinks keep what was replayed before an error
+    replay.close().await?;
+    let (replayed, kept) = result?;
+
+    info!(
+        "Replayed {} dead letters to {}, kept {}",
+        replayed, args.redis_queue, kept
+    );
+    Ok(())
+}
+
+// the file is left as is
+async fn replay_file(replay: &mut Replay, file: &str) -> anyhow::Result<(usize, usize)> {
+    let (mut replayed, mut kept) = (0, 0);
+    for line in BufReader::new(File::open(file)?).lines() {
+        let line = line?;
+        match serde_json::from_str::<DeadLetter>(&line) {
+            Ok(letter) => match replay.replay(&letter).await? {
+                true => replayed += 1,
+                false => kept += 1,
+            },
+            Err(e) => error!("Skipping a line that is not a dead letter: {:?}", e),
+        }
+    }
+    Ok((replayed, kept))
+}
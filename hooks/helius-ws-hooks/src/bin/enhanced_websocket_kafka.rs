@@ -1,22 +1,15 @@
 use clap::Parser;
-use helius::{
-    error::Result,
-    types::{
-        Cluster, RpcTransactionsConfig, TransactionSubscribeFilter, TransactionSubscribeOptions,
-    },
-    Helius,
-};
-use helius_ws_hooks::pumpfun_instruction_parser::parse_notification;
-use rdkafka::{
-    config::ClientConfig,
-    message::{Header, OwnedHeaders},
-    producer::{FutureProducer, FutureRecord},
-};
-use solana_program::pubkey;
-use std::str::FromStr;
-use std::time::Duration;
-use tokio_stream::StreamExt;
+use helius::types::TransactionCommitment;
+use helius_ws_hooks::ingest::config::PipelineConfig;
+use helius_ws_hooks::ingest::pipeline::Pipeline;
+use helius_ws_hooks::ingest::sink::{SinkConfig, SinkFormat};
+use helius_ws_hooks::ingest::source::SourceConfig;
+use helius_ws_hooks::kafka_producer::TopicRouting;
+use helius_ws_hooks::pumpfun_instruction_parser::parse_commitment;
+use log::Level;
 
+/// Produces the payloads of the program's transactions to kafka, as json. An `ingest` pipeline
+/// with a helius source and a kafka sink.
 #[derive(Parser)]
 struct Args {
     /// The program ID to subscribe to
@@ -24,63 +17,51 @@ struct Args {
     program_id: String,
     #[clap(default_value = "5d166540-f22e-4f66-bb70-8349844d4a0e")]
     api_key: String,
-    #[clap(default_value = "pumpfun-create")]
+    /// The topic, or the topic prefix with `--routing action`
+    #[clap(default_value = "pumpfun")]
     topic_name: String,
+    /// Comma separated list of kafka brokers
+    #[clap(short = 'b', long, default_value = "localhost")]
+    brokers: String,
+    /// Topic routing: single, or action to produce to `<topic_name>-<action>`, e.g. pumpfun-buy
+    #[clap(long, default_value = "single")]
+    routing: TopicRouting,
+    /// Sends awaiting their delivery at most, a further send first waits for one to complete
+    #[clap(long, default_value = "1000")]
+    max_in_flight: usize,
+    /// Transaction encoding to subscribe with: jsonParsed, base58 or base64
+    #[clap(short = 'e', long, default_value = "jsonParsed")]
+    encoding: String,
+    /// Commitment to subscribe with, also recorded on every payload: processed, confirmed or
+    /// finalized
+    #[clap(long, default_value = "confirmed", value_parser = parse_commitment)]
+    commitment: TransactionCommitment,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
     let args = Args::parse();
-    let program_id = pubkey::Pubkey::from_str(&args.program_id).expect("Invalid program ID");
-    let api_key = args.api_key.clone();
-    let topic_name = args.topic_name;
 
-    // init helius
-    let cluster: Cluster = Cluster::MainnetBeta;
-    let helius: Helius = Helius::new_with_ws_with_timeouts(&api_key, cluster, Some(15), Some(45))
-        .await
-        .unwrap();
-    let config: RpcTransactionsConfig = RpcTransactionsConfig {
-        filter: TransactionSubscribeFilter::standard(&program_id),
-        options: TransactionSubscribeOptions::default(),
+    let source = SourceConfig::HeliusWs {
+        api_key: Some(args.api_key),
+        upstreams: vec![],
+        encoding: args.encoding,
+        commitment: Some(args.commitment),
+    };
+    let sink = SinkConfig::Kafka {
+        brokers: args.brokers,
+        topic: args.topic_name,
+        format: SinkFormat::Payload,
+        routing: args.routing,
+        max_in_flight: args.max_in_flight,
+    };
+    let config = PipelineConfig {
+        program_id: args.program_id,
+        ..PipelineConfig::new(source, vec![sink])
     };
 
-    if let Some(ws) = helius.ws() {
-        // init kafka
-        let brokers = "localhost";
-        let producer: &FutureProducer = &ClientConfig::new()
-            .set("bootstrap.servers", brokers)
-            .set("message.timeout.ms", "5000")
-            .create()
-            .expect("Producer creation error");
-
-        let (mut stream, _unsub) = ws.transaction_subscribe(config).await.unwrap();
-        while let Some(notification) = stream.next().await {
-            if let Some(wrap_payload) = parse_notification(&notification, &program_id.to_string()) {
-                let topic_name = topic_name.clone();
-                let futures = async move {
-                    // The send operation on the topic returns a future, which will be
-                    // completed once the result or failure from Kafka is received.
-                    producer
-                        .send(
-                            FutureRecord::to(&topic_name)
-                                .payload(&format!(
-                                    "Payload: {}",
-                                    serde_json::to_string(&wrap_payload).unwrap()
-                                ))
-                                .key(&format!("Key {}", 0))
-                                .headers(OwnedHeaders::new().insert(Header {
-                                    key: "header_key",
-                                    value: Some("header_value"),
-                                })),
-                            Duration::from_secs(0),
-                        )
-                        .await
-                };
-                let _t = futures.await;
-            }
-        }
-    }
-
-    Ok(())
+    Pipeline::new(config).await?.run().await
 }
@@ -1,16 +1,15 @@
 use clap::Parser;
-use helius::types::TransactionNotification;
-use helius_ws_hooks::batch_csv_writer::{BatchCsvRecord, BatchCsvWriter};
-use helius_ws_hooks::pumpfun_instruction_parser::parse_notification;
-use lazy_static::lazy_static;
-use redis::AsyncCommands;
-use solana_program::pubkey;
-use std::str::FromStr;
-
-lazy_static! {
-    static ref EVENT_PROCESSED: &'static str = "event_processed";
-}
+use helius_ws_hooks::batch_csv_writer::CsvCompression;
+use helius_ws_hooks::dead_letter::DeadLetterConfig;
+use helius_ws_hooks::ingest::config::PipelineConfig;
+use helius_ws_hooks::ingest::pipeline::Pipeline;
+use helius_ws_hooks::ingest::sink::SinkConfig;
+use helius_ws_hooks::ingest::source::SourceConfig;
+use helius_ws_hooks::redis_queue::RedisTransport;
+use log::Level;
 
+/// Records the messages of a redis queue as csv files, acked once flushed. An `ingest` pipeline
+/// with a redis source and a csv sink.
 #[derive(Parser)]
 struct Args {
     #[clap(
@@ -24,39 +23,58 @@ struct Args {
     redis_url: String,
     #[clap(short = 'q', default_value = "events")]
     redis_queue: String,
+    /// Queue transport: list (RPUSH/BLPOP) or stream (XADD/XREADGROUP)
+    #[clap(long, default_value = "list")]
+    transport: RedisTransport,
+    /// Consumer group reading the stream, each binary should have its own
+    #[clap(short = 'g', long, default_value = "csv_recorder")]
+    group: String,
+    /// Consumer name within the group, defaults to `<hostname>-<pid>`
+    #[clap(long)]
+    consumer: Option<String>,
+    /// Redis list that notifications which fail to deserialize or decode are moved to
+    #[clap(long, default_value = "dead_letters")]
+    dead_letter_key: String,
+    /// Also record failed pump.fun transactions with their program error, from json messages;
+    /// binary ones were decoded by the producer, which needs `--capture-failed` itself
+    #[clap(long)]
+    capture_failed: bool,
+    /// Compress the csv files: none, gzip or zstd
+    #[clap(long, default_value = "none")]
+    compression: CsvCompression,
+    /// Also start a new csv file once this many MB are written
+    #[clap(long)]
+    max_file_mb: Option<u64>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
     let args = Args::parse();
-    let csv_dir = args.csv_dir.clone();
-    let redis_url = args.redis_url.clone();
-    let program_id = pubkey::Pubkey::from_str(&args.program_id).expect("Invalid program ID");
-    let redis_queue = args.redis_queue;
-
-    let mut my_writer = BatchCsvWriter::new(csv_dir, 1000, 3600)?;
-
-    let redis_client = redis::Client::open(redis_url)?;
-    let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
-
-    if let Err(e) = redis_conn.set::<&str, i32, ()>(&EVENT_PROCESSED, 0).await {
-        eprintln!("Failed to increment event counter in Redis: {:?}", e);
-    }
-
-    loop {
-        let event: Option<(String, String)> = redis_conn.blpop(redis_queue.clone(), 0.0).await?;
 
-        if let Err(e) = redis_conn.incr::<&str, i32, ()>(&EVENT_PROCESSED, 1).await {
-            eprintln!("Failed to increment event counter in Redis: {:?}", e);
-        }
+    let source = SourceConfig::Redis {
+        redis_url: args.redis_url.clone(),
+        queue: args.redis_queue,
+        transport: args.transport,
+        group: args.group,
+        consumer: args.consumer,
+    };
+    let sink = SinkConfig::Csv {
+        dir: args.csv_dir,
+        compression: args.compression,
+        max_file_mb: args.max_file_mb,
+    };
+    let config = PipelineConfig {
+        program_id: args.program_id,
+        capture_failed: args.capture_failed,
+        dead_letter: Some(DeadLetterConfig::Redis {
+            redis_url: args.redis_url,
+            key: args.dead_letter_key,
+        }),
+        ..PipelineConfig::new(source, vec![sink])
+    };
 
-        if let Some((_key, event_str)) = event {
-            let notification: TransactionNotification =
-                serde_json::from_str(event_str.as_str()).unwrap();
-            if let Some(wrap_payload) = parse_notification(&notification, &program_id.to_string()) {
-                let batch_csv_record: BatchCsvRecord = wrap_payload.into();
-                my_writer.write(batch_csv_record).unwrap();
-            }
-        }
-    }
+    Pipeline::new(config).await?.run().await
 }
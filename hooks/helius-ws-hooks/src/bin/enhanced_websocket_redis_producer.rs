@@ -1,23 +1,17 @@
-use chrono::prelude::Utc;
 use clap::Parser;
-use helius::{
-    error::{HeliusError, Result},
-    types::{
-        Cluster, RpcTransactionsConfig, TransactionSubscribeFilter, TransactionSubscribeOptions,
-    },
-    Helius,
-};
-use lazy_static::lazy_static;
-use redis::AsyncCommands;
-use solana_program::pubkey;
-use std::str::FromStr;
-use tokio::time::{sleep, timeout, Duration};
-use tokio_stream::StreamExt;
-
-lazy_static! {
-    static ref EVENT_IN: &'static str = "event_in";
-}
+use helius::types::TransactionCommitment;
+use helius_ws_hooks::dead_letter::DeadLetterConfig;
+use helius_ws_hooks::ingest::config::PipelineConfig;
+use helius_ws_hooks::ingest::pipeline::Pipeline;
+use helius_ws_hooks::ingest::sink::{SinkConfig, SinkFormat};
+use helius_ws_hooks::ingest::source::SourceConfig;
+use helius_ws_hooks::payload_codec::QueueFormat;
+use helius_ws_hooks::pumpfun_instruction_parser::parse_commitment;
+use helius_ws_hooks::redis_queue::RedisTransport;
+use log::Level;
 
+/// Pushes the program's transactions to a redis queue, for the redis consumers. An `ingest`
+/// pipeline with a helius source and a redis sink.
 #[derive(Parser)]
 struct Args {
     /// The program ID to subscribe to
@@ -32,112 +26,75 @@ struct Args {
     redis_url: String,
     #[clap(short = 'q', default_value = "events")]
     redis_queue: String,
+    /// Queue transport: list (RPUSH/BLPOP) or stream (XADD/XREADGROUP)
+    #[clap(long, default_value = "list")]
+    transport: RedisTransport,
+    /// Approximate cap on the stream length
+    #[clap(long, default_value = "1000000")]
+    max_len: usize,
+    /// Transaction encoding to subscribe with: jsonParsed, base58 or base64
+    #[clap(short = 'e', long, default_value = "jsonParsed")]
+    encoding: String,
+    /// Commitment to subscribe with, also recorded on every payload: processed, confirmed or
+    /// finalized
+    #[clap(long, default_value = "confirmed", value_parser = parse_commitment)]
+    commitment: TransactionCommitment,
+    /// Queue format: binary pushes the payloads decoded here, json the whole notification
+    #[clap(long, default_value = "binary")]
+    format: QueueFormat,
+    /// Also push every notification, as json, to this queue
+    #[clap(long)]
+    archive_queue: Option<String>,
+    /// Also decode failed pump.fun transactions, with the binary format
+    #[clap(long)]
+    capture_failed: bool,
+    /// Redis list that notifications which fail to decode are moved to
+    #[clap(long, default_value = "dead_letters")]
+    dead_letter_key: String,
 }
 
 #[tokio::main]
-async fn main() {
-    if let Err(e) = entrypoint().await {
-        eprintln!("Error: {:?}", e);
-    }
-}
-
-async fn subscribe_and_process(
-    api_key: &str,
-    program_id: &pubkey::Pubkey,
-    redis_url: &str,
-    redis_queue: &str,
-) -> Result<()> {
-    let redis_client =
-        redis::Client::open(redis_url).map_err(|e| HeliusError::EnhancedWebsocket {
-            reason: "Redis connection error".to_string(),
-            message: e.to_string(),
-        })?;
-    let mut redis_conn = redis_client
-        .get_multiplexed_async_connection()
-        .await
-        .map_err(|e| HeliusError::EnhancedWebsocket {
-            reason: "Redis connection error".to_string(),
-            message: e.to_string(),
-        })?;
-    redis_conn
-        .set::<&str, i32, ()>(&EVENT_IN, 0)
-        .await
-        .map_err(|e| HeliusError::EnhancedWebsocket {
-            reason: format!("Failed to set event counter in Redis: {:?}", e),
-            message: e.to_string(),
-        })?;
-
-    // wss://atlas-mainnet.helius-rpc.com/?api-key=<API_KEY>
-    let cluster: Cluster = Cluster::MainnetBeta;
-    // Uses custom ping-pong timeouts to ping every 15s and timeout after 45s of no pong
-    let helius: Helius =
-        Helius::new_with_ws_with_timeouts(api_key, cluster, Some(15), Some(45)).await?;
-
-    dbg!(&helius.config.endpoints);
-    dbg!(&helius.config.cluster);
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
+    let args = Args::parse();
 
-    let config: RpcTransactionsConfig = RpcTransactionsConfig {
-        filter: TransactionSubscribeFilter::standard(program_id),
-        options: TransactionSubscribeOptions::default(),
+    let source = SourceConfig::HeliusWs {
+        api_key: Some(args.api_key),
+        upstreams: vec![],
+        encoding: args.encoding,
+        commitment: Some(args.commitment),
     };
-
-    if let Some(ws) = helius.ws() {
-        let (mut stream, _unsub) = ws.transaction_subscribe(config).await?;
-        println!("Stream subscribe success, {}", Utc::now());
-
-        let mut notify_count: usize = 0;
-
-        // There may be a timeout while waiting for the next notification from the stream.
-        // This can happen if the downstream is hanged or if there are no new events within the specified duration.
-        while let Some(notification) = timeout(Duration::from_secs(60), stream.next())
-            .await
-            .unwrap_or(None)
-        {
-            let notification_str = serde_json::to_string(&notification).unwrap();
-
-            // add event counter in redis
-            redis_conn
-                .incr::<&str, i32, ()>(&EVENT_IN, 1)
-                .await
-                .map_err(|e| HeliusError::EnhancedWebsocket {
-                    reason: format!("Failed to increment event counter in Redis: {:?}", e),
-                    message: e.to_string(),
-                })?;
-            redis_conn
-                .rpush::<&str, &str, ()>(redis_queue, &notification_str)
-                .await
-                .map_err(|e| HeliusError::EnhancedWebsocket {
-                    reason: format!("Failed to send notification to Redis: {:?}", e),
-                    message: e.to_string(),
-                })?;
-            notify_count += 1;
-        }
-        println!("notify counter: {}", notify_count);
+    let format = match args.format {
+        QueueFormat::Binary => SinkFormat::Binary,
+        QueueFormat::Json => SinkFormat::Notification,
+    };
+    let mut sinks = vec![SinkConfig::Redis {
+        redis_url: args.redis_url.clone(),
+        queue: args.redis_queue,
+        transport: args.transport,
+        max_len: args.max_len,
+        format,
+    }];
+    if let Some(archive_queue) = args.archive_queue {
+        sinks.push(SinkConfig::Redis {
+            redis_url: args.redis_url.clone(),
+            queue: archive_queue,
+            transport: args.transport,
+            max_len: args.max_len,
+            format: SinkFormat::Notification,
+        });
     }
-    Ok(())
-}
-
-async fn entrypoint() -> Result<()> {
-    let args = Args::parse();
-    let program_id = pubkey::Pubkey::from_str(&args.program_id).expect("Invalid program ID");
-    let api_key = args.api_key.clone();
-    let redis_url = args.redis_url.clone();
-    let redis_queue = args.redis_queue.clone();
-
-    dbg!(args.program_id);
-    dbg!(args.api_key);
-    dbg!(args.redis_url);
-
-    loop {
-        if let Err(e) = subscribe_and_process(&api_key, &program_id, &redis_url, &redis_queue).await
-        {
-            eprintln!("Error during stream processing: {:?}", e);
-        }
-        println!(
-            "Stream has been exhausted, attempting to reconnect..., {}",
-            Utc::now()
-        );
+    let config = PipelineConfig {
+        program_id: args.program_id,
+        capture_failed: args.capture_failed,
+        dead_letter: Some(DeadLetterConfig::Redis {
+            redis_url: args.redis_url,
+            key: args.dead_letter_key,
+        }),
+        ..PipelineConfig::new(source, sinks)
+    };
 
-        sleep(Duration::from_secs(1)).await;
-    }
+    Pipeline::new(config).await?.run().await
 }
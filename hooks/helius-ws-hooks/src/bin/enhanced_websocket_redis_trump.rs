@@ -1,16 +1,15 @@
 use clap::Parser;
-use helius::error::HeliusError;
-use helius::types::TransactionNotification;
-use helius_ws_hooks::pumpfun_instruction_parser::{parse_notification, WrapPayload};
-use lazy_static::lazy_static;
-use redis::AsyncCommands;
-use solana_program::pubkey;
-use std::str::FromStr;
-
-lazy_static! {
-    static ref EVENT_PROCESSED: &'static str = "event_counter_trump";
-}
+use helius_ws_hooks::dead_letter::DeadLetterConfig;
+use helius_ws_hooks::ingest::config::PipelineConfig;
+use helius_ws_hooks::ingest::filter::FilterConfig;
+use helius_ws_hooks::ingest::pipeline::Pipeline;
+use helius_ws_hooks::ingest::sink::{SinkConfig, SinkFormat};
+use helius_ws_hooks::ingest::source::SourceConfig;
+use helius_ws_hooks::redis_queue::RedisTransport;
+use log::Level;
 
+/// Pushes the `createbuy` payloads of the launches named like trump to another queue, as json.
+/// An `ingest` pipeline with a redis source, two filters and a redis sink.
 #[derive(Parser)]
 struct Args {
     #[clap(
@@ -24,52 +23,60 @@ struct Args {
     redis_queue: String,
     #[clap(short = 'o', default_value = "events_trump_out")]
     redis_queue_trump: String,
+    /// Queue transport: list (RPUSH/BLPOP) or stream (XADD/XREADGROUP)
+    #[clap(long, default_value = "list")]
+    transport: RedisTransport,
+    /// Consumer group reading the stream, each binary should have its own
+    #[clap(short = 'g', long, default_value = "trump")]
+    group: String,
+    /// Consumer name within the group, defaults to `<hostname>-<pid>`
+    #[clap(long)]
+    consumer: Option<String>,
+    /// Redis list that notifications which fail to deserialize or decode are moved to
+    #[clap(long, default_value = "dead_letters")]
+    dead_letter_key: String,
+    /// Approximate cap on the length of the output stream
+    #[clap(long, default_value = "100000")]
+    max_len: usize,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
     let args = Args::parse();
-    let redis_url = args.redis_url.clone();
-    let program_id = pubkey::Pubkey::from_str(&args.program_id).expect("Invalid program ID");
-    let redis_queue = args.redis_queue;
-    let redis_queue_trump = args.redis_queue_trump;
-
-    let redis_client = redis::Client::open(redis_url)?;
-    let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
-
-    loop {
-        let event: Option<(String, String)> = redis_conn.blpop(redis_queue.clone(), 0.0).await?;
 
-        if let Err(e) = redis_conn.incr::<&str, i32, ()>(&EVENT_PROCESSED, 1).await {
-            eprintln!("Failed to increment event counter in Redis: {:?}", e);
-        }
+    let source = SourceConfig::Redis {
+        redis_url: args.redis_url.clone(),
+        queue: args.redis_queue,
+        transport: args.transport,
+        group: args.group,
+        consumer: args.consumer,
+    };
+    let sink = SinkConfig::Redis {
+        redis_url: args.redis_url.clone(),
+        queue: args.redis_queue_trump,
+        transport: args.transport,
+        max_len: args.max_len,
+        format: SinkFormat::Payload,
+    };
+    let config = PipelineConfig {
+        program_id: args.program_id,
+        filters: vec![
+            FilterConfig::Action {
+                actions: vec!["createbuy".to_string()],
+            },
+            FilterConfig::NameContains {
+                pattern: "trump".to_string(),
+            },
+        ],
+        dead_letter: Some(DeadLetterConfig::Redis {
+            redis_url: args.redis_url,
+            key: args.dead_letter_key,
+        }),
+        ..PipelineConfig::new(source, vec![sink])
+    };
 
-        if let Some((_key, event_str)) = event {
-            let notification: TransactionNotification =
-                serde_json::from_str(event_str.as_str()).unwrap();
-            if let Some(wrap_payload) = parse_notification(&notification, &program_id.to_string()) {
-                match wrap_payload {
-                    WrapPayload::Create(_, _) => {
-                        // todo!()
-                    }
-                    WrapPayload::CreateBuy(event, _) => {
-                        let json_str = serde_json::to_string(&event).unwrap();
-                        if event.name.to_lowercase().contains("trump") {
-                            redis_conn
-                                .rpush::<&str, &str, ()>(&redis_queue_trump, &json_str)
-                                .await
-                                .map_err(|e| HeliusError::EnhancedWebsocket {
-                                    reason: format!(
-                                        "Failed to send notification to Redis: {:?}",
-                                        e
-                                    ),
-                                    message: e.to_string(),
-                                })?;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        }
-    }
+    Pipeline::new(config).await?.run().await
 }
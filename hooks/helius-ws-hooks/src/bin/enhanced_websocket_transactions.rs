@@ -1,19 +1,15 @@
 use clap::Parser;
-use helius::{
-    error::Result,
-    types::{
-        Cluster, RpcTransactionsConfig, TransactionNotification, TransactionSubscribeFilter,
-        TransactionSubscribeOptions,
-    },
-    Helius,
-};
-use helius_ws_hooks::batch_csv_writer::{BatchCsvRecord, BatchCsvWriter};
-use helius_ws_hooks::pumpfun_instruction_parser::parse_notification;
-use solana_program::pubkey;
-use std::str::FromStr;
-use tokio::sync::mpsc;
-use tokio_stream::StreamExt;
+use helius::types::TransactionCommitment;
+use helius_ws_hooks::batch_csv_writer::CsvCompression;
+use helius_ws_hooks::ingest::config::PipelineConfig;
+use helius_ws_hooks::ingest::pipeline::Pipeline;
+use helius_ws_hooks::ingest::sink::SinkConfig;
+use helius_ws_hooks::ingest::source::SourceConfig;
+use helius_ws_hooks::pumpfun_instruction_parser::parse_commitment;
+use log::Level;
 
+/// Records the payloads of the program's transactions as csv files. An `ingest` pipeline with
+/// a helius source and a csv sink.
 #[derive(Parser)]
 struct Args {
     /// The program ID to subscribe to
@@ -26,60 +22,56 @@ struct Args {
     api_key: String,
     #[clap(short = 'd', default_value = "/tmp/csv")]
     csv_dir: String,
+    /// Transaction encoding to subscribe with: jsonParsed, base58 or base64
+    #[clap(short = 'e', long, default_value = "jsonParsed")]
+    encoding: String,
+    /// Commitment to subscribe with, also recorded on every payload: processed, confirmed or
+    /// finalized
+    #[clap(long, default_value = "confirmed", value_parser = parse_commitment)]
+    commitment: TransactionCommitment,
+    /// Also record failed pump.fun transactions with their program error
+    #[clap(long)]
+    capture_failed: bool,
+    /// Compress the csv files: none, gzip or zstd
+    #[clap(long, default_value = "none")]
+    compression: CsvCompression,
+    /// Also start a new csv file once this many MB are written
+    #[clap(long)]
+    max_file_mb: Option<u64>,
+    /// Also write the records as parquet files into this directory
+    #[clap(long)]
+    parquet_dir: Option<String>,
 }
 
 #[tokio::main]
-async fn main() {
-    if let Err(e) = entrypoint().await {
-        eprintln!("Error: {:?}", e);
-    }
-}
-
-async fn entrypoint() -> Result<()> {
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
     let args = Args::parse();
-    let program_id = pubkey::Pubkey::from_str(&args.program_id).expect("Invalid program ID");
-    let api_key = args.api_key.clone();
-    let csv_dir = args.csv_dir.clone();
-
-    dbg!(args.program_id);
-    dbg!(args.api_key);
-    dbg!(args.csv_dir);
-
-    let (notification_tx, mut notification_rx) =
-        mpsc::unbounded_channel::<TransactionNotification>();
-
-    let mut my_writer = BatchCsvWriter::new(csv_dir, 1000, 3600).unwrap();
-
-    tokio::spawn(async move {
-        let cluster: Cluster = Cluster::MainnetBeta;
-        let helius: Helius =
-            Helius::new_with_ws_with_timeouts(&api_key, cluster, Some(15), Some(45))
-                .await
-                .unwrap();
-        dbg!(&helius.config.endpoints);
-        dbg!(&helius.config.cluster);
-        let config: RpcTransactionsConfig = RpcTransactionsConfig {
-            filter: TransactionSubscribeFilter::standard(&program_id),
-            options: TransactionSubscribeOptions::default(),
-        };
-
-        if let Some(ws) = helius.ws() {
-            let (mut stream, _unsub) = ws.transaction_subscribe(config).await.unwrap();
-            while let Some(notification) = stream.next().await {
-                // dbg!("{:#?}", &notification);
-                notification_tx.send(notification).unwrap();
-            }
-        }
-    });
-
-    while let Some(notification) = notification_rx.recv().await {
-        if let Some(wrap_payload) = parse_notification(&notification, &program_id.to_string()) {
-            let batch_csv_record: BatchCsvRecord = wrap_payload.into();
-            my_writer.write(batch_csv_record).unwrap();
-        }
 
-        // TODO: write parquet record
+    let source = SourceConfig::HeliusWs {
+        api_key: Some(args.api_key),
+        upstreams: vec![],
+        encoding: args.encoding,
+        commitment: Some(args.commitment),
+    };
+    let mut sinks = vec![SinkConfig::Csv {
+        dir: args.csv_dir,
+        compression: args.compression,
+        max_file_mb: args.max_file_mb,
+    }];
+    if let Some(dir) = args.parquet_dir {
+        sinks.push(SinkConfig::Parquet {
+            dir,
+            max_file_secs: 3600,
+        });
     }
+    let config = PipelineConfig {
+        program_id: args.program_id,
+        capture_failed: args.capture_failed,
+        ..PipelineConfig::new(source, sinks)
+    };
 
-    Ok(())
+    Pipeline::new(config).await?.run().await
 }
@@ -0,0 +1,24 @@
+use clap::Parser;
+use helius_ws_hooks::ingest::config::load_pipeline_config;
+use helius_ws_hooks::ingest::pipeline::Pipeline;
+use log::{info, Level};
+
+#[derive(Parser)]
+struct Args {
+    /// The pipeline: a source, filters and sinks
+    #[arg(short = 'c', long, default_value = "/tmp/ingest.toml")]
+    config: String,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
+
+    let args = Args::parse();
+    let config = load_pipeline_config(&args.config)?;
+    info!("Starting ingest pipeline {}", args.config);
+
+    Pipeline::new(config).await?.run().await
+}
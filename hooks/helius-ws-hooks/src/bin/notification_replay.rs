@@ -0,0 +1,169 @@
+use clap::Parser;
+use helius_ws_hooks::payload_codec::{encode_payloads, QueueFormat};
+use helius_ws_hooks::pumpfun_instruction_parser::{
+    parse_notification, PayloadEnvelope, PayloadSource, WrapPayload,
+};
+use helius_ws_hooks::pumpfun_trading::controller::Controller;
+use helius_ws_hooks::pumpfun_trading::utils::load_trading_config;
+use helius_ws_hooks::recording::{Recorded, RecordingReader, ReplayPacer, ReplaySpeed};
+use helius_ws_hooks::redis_queue::{RedisProducer, RedisTransport};
+use log::{error, info, Level};
+use std::str::FromStr;
+
+/// Where recorded notifications are replayed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReplayTarget {
+    /// A redis queue, as `enhanced_websocket_redis_producer` would push them
+    Redis,
+    /// A controller in this process, deciding as it would live, not paper trading. Its orders
+    /// are logged and never executed.
+    Controller,
+}
+
+impl FromStr for ReplayTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "redis" => Ok(ReplayTarget::Redis),
+            "controller" => Ok(ReplayTarget::Controller),
+            _ => Err(format!(
+                "unknown target {}, expected redis or controller",
+                s
+            )),
+        }
+    }
+}
+
+/// Replays notifications recorded by the `recording` ingest sink
+#[derive(Parser)]
+struct Args {
+    /// A recorded segment, or a directory of them
+    #[clap(short = 'i', long)]
+    input: String,
+    /// original, max, or a factor like 10x
+    #[clap(long, default_value = "max")]
+    speed: ReplaySpeed,
+    /// Replay to redis or to a controller
+    #[clap(long, default_value = "redis")]
+    to: ReplayTarget,
+    #[clap(
+        short = 'p',
+        default_value = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P"
+    )]
+    program_id: String,
+    #[clap(short = 'r', default_value = "redis://127.0.0.1/")]
+    redis_url: String,
+    #[clap(short = 'q', default_value = "events")]
+    redis_queue: String,
+    /// Queue transport: list (RPUSH/BLPOP) or stream (XADD/XREADGROUP)
+    #[clap(long, default_value = "list")]
+    transport: RedisTransport,
+    /// Approximate cap on the stream length
+    #[clap(long, default_value = "1000000")]
+    max_len: usize,
+    /// Queue format: json pushes the notifications, binary the payloads decoded here. Recorded
+    /// payloads are pushed as binary either way.
+    #[clap(long, default_value = "json")]
+    format: QueueFormat,
+    /// Trading config of the controller, its paper_trading is ignored
+    #[arg(short = 'b', long, default_value = "/tmp/config.toml")]
+    bot_config: String,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
+    let args = Args::parse();
+
+    let mut producer = match args.to {
+        ReplayTarget::Redis => {
+            let redis_client = redis::Client::open(args.redis_url.as_str())?;
+            let redis_conn = redis_client.get_multiplexed_async_connection().await?;
+            Some(RedisProducer::new(
+                redis_conn,
+                args.redis_queue.clone(),
+                args.transport,
+                args.max_len,
+            ))
+        }
+        ReplayTarget::Controller => None,
+    };
+    let mut controller = match args.to {
+        ReplayTarget::Controller => Some(Controller::new(load_trading_config(&args.bot_config)?)),
+        ReplayTarget::Redis => None,
+    };
+
+    let mut pacer = ReplayPacer::new(args.speed);
+    let mut replayed = 0;
+    let mut orders = 0;
+    for recorded in RecordingReader::open(&args.input)? {
+        let recorded = match recorded {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                error!("Skipping an unreadable recorded notification: {:?}", e);
+                continue;
+            }
+        };
+        pacer.wait(recorded.received_at()).await;
+
+        // payloads carry the time the notification was originally received. A controller skips
+        // paper trades, the payloads are not marked so that it decides.
+        let payloads = match &recorded {
+            Recorded::Notification(recorded) => {
+                let envelope = PayloadEnvelope {
+                    received_at: recorded.received_at,
+                    ..PayloadEnvelope::new(PayloadSource::Replay, None)
+                };
+                parse_notification(&recorded.notification, &args.program_id)
+                    .map(|transaction| transaction.into_payloads(&envelope))
+                    .unwrap_or_default()
+            }
+            Recorded::Payloads(recorded) => {
+                let mut payloads = recorded.payloads.clone();
+                for envelope in payloads.iter_mut().filter_map(WrapPayload::envelope_mut) {
+                    envelope.source = PayloadSource::Replay;
+                    envelope.is_paper_trade = false;
+                }
+                payloads
+            }
+        };
+        if let Some(producer) = producer.as_mut() {
+            match (&recorded, args.format) {
+                (Recorded::Notification(recorded), QueueFormat::Json) => {
+                    producer
+                        .push(&serde_json::to_string(&recorded.notification)?)
+                        .await?
+                }
+                _ => {
+                    if !payloads.is_empty() {
+                        producer.push_bytes(&encode_payloads(&payloads)?).await?;
+                    }
+                }
+            }
+        }
+        if let Some(controller) = controller.as_mut() {
+            for wrap_payload in payloads {
+                if let Some(order) = controller.handle_payload(wrap_payload) {
+                    info!("Order (not executed): {:?}", order);
+                    orders += 1;
+                }
+            }
+        }
+        replayed += 1;
+    }
+
+    match args.to {
+        ReplayTarget::Redis => info!(
+            "Replayed {} notifications to {}",
+            replayed, args.redis_queue
+        ),
+        ReplayTarget::Controller => info!(
+            "Replayed {} notifications to the controller, {} orders",
+            replayed, orders
+        ),
+    }
+    Ok(())
+}
@@ -1,12 +1,18 @@
 use clap::Parser;
-use helius::types::TransactionNotification;
-use helius_ws_hooks::pumpfun_instruction_parser::{parse_notification, WrapPayload};
+use helius_ws_hooks::dead_letter::{DeadLetter, DeadLetterReason, DeadLetterSink};
+use helius_ws_hooks::kafka_consumer::PayloadConsumer;
+use helius_ws_hooks::payload_codec::QueueMessage;
+use helius_ws_hooks::pumpfun_instruction_parser::{
+    parse_notification, PayloadEnvelope, PayloadSource, WrapPayload,
+};
+use helius_ws_hooks::pumpfun_trading::controller::Controller;
 use helius_ws_hooks::pumpfun_trading::orders::{
     execute_pumpfun_buy, execute_pumpfun_sell, BotOrder, TimerBotOrder,
 };
-use helius_ws_hooks::pumpfun_trading::{controller::Controller, utils::load_trading_config};
+use helius_ws_hooks::pumpfun_trading::utils::load_trading_config;
+use helius_ws_hooks::redis_queue::{default_consumer_name, RedisConsumer, RedisTransport};
 use lazy_static::lazy_static;
-use log::{error, info, Level};
+use log::{error, info, warn, Level};
 use redis::AsyncCommands;
 use solana_program::pubkey::Pubkey;
 use std::str::FromStr;
@@ -18,6 +24,38 @@ lazy_static! {
     static ref EVENT_PROCESSED: &'static str = "event_looped";
 }
 
+// the pause before reading again after a failed read, doubled while the reads keep failing
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Where the trading loop reads market events from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventSource {
+    /// Notifications pushed by `enhanced_websocket_redis_producer`
+    Redis,
+    /// Payloads produced by `enhanced_websocket_kafka`
+    Kafka,
+}
+
+impl FromStr for EventSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "redis" => Ok(EventSource::Redis),
+            "kafka" => Ok(EventSource::Kafka),
+            _ => Err(format!("unknown source {}, expected redis or kafka", s)),
+        }
+    }
+}
+
+// an RFC 3339 time, e.g. 2025-01-20T00:00:00Z, as unix milliseconds
+fn parse_start_time(s: &str) -> Result<i64, String> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .map(|time| time.timestamp_millis())
+        .map_err(|e| e.to_string())
+}
+
 #[derive(Parser)]
 struct Args {
     #[clap(
@@ -29,6 +67,31 @@ struct Args {
     redis_url: String,
     #[clap(short = 'q', default_value = "events")]
     redis_queue: String,
+    /// Read events from redis or kafka
+    #[clap(long, default_value = "redis")]
+    source: EventSource,
+    /// Comma separated list of kafka brokers
+    #[clap(long, default_value = "localhost")]
+    brokers: String,
+    /// Kafka topics to consume, comma separated, e.g. the `--routing action` topics
+    #[clap(long, default_value = "pumpfun", value_delimiter = ',')]
+    topics: Vec<String>,
+    /// Replay kafka from this RFC 3339 time instead of the committed offsets of the group, paper
+    /// trading whatever the config says. The offsets of the group are left as they are
+    #[clap(long, value_parser = parse_start_time)]
+    start_time: Option<i64>,
+    /// Queue transport: list (RPUSH/BLPOP) or stream (XADD/XREADGROUP)
+    #[clap(long, default_value = "list")]
+    transport: RedisTransport,
+    /// Consumer group reading the stream or the kafka topics, each binary should have its own
+    #[clap(short = 'g', long, default_value = "trading_loop")]
+    group: String,
+    /// Consumer name within the group, defaults to `<hostname>-<pid>`
+    #[clap(long)]
+    consumer: Option<String>,
+    /// Redis list that notifications which fail to deserialize or decode are moved to
+    #[clap(long, default_value = "dead_letters")]
+    dead_letter_key: String,
     #[arg(short = 'b', long, default_value = "/tmp/config.toml")]
     bot_config: String,
 }
@@ -51,6 +114,26 @@ async fn main() -> anyhow::Result<()> {
     if let Err(e) = redis_conn.set::<&str, i32, ()>(&EVENT_PROCESSED, 0).await {
         error!("Failed to increment event counter in Redis: {:?}", e);
     }
+    let mut dead_letters = DeadLetterSink::redis(redis_conn.clone(), args.dead_letter_key);
+    let origin = format!("trading_loop:{}", redis_queue);
+    let mut consumer = match args.source {
+        EventSource::Redis => EventConsumer::Redis(Box::new(
+            RedisConsumer::new(
+                redis_conn.clone(),
+                redis_queue,
+                args.transport,
+                args.group,
+                args.consumer.unwrap_or_else(default_consumer_name),
+            )
+            .await?,
+        )),
+        EventSource::Kafka => EventConsumer::Kafka(PayloadConsumer::new(
+            &args.brokers,
+            &args.group,
+            &args.topics,
+            args.start_time,
+        )?),
+    };
 
     let (thread2_tx, mut thread2_rx) = mpsc::channel::<BotOrder>(32);
 
@@ -71,58 +154,116 @@ async fn main() -> anyhow::Result<()> {
             error!("Failed to load trading config");
             return;
         }
-        let mut controller = Controller::new(trading_config.unwrap());
+        let mut trading_config = trading_config.unwrap();
+        // old launches are replayed into the bot, never traded
+        if args.start_time.is_some() && !trading_config.paper_trading {
+            warn!("Replaying from --start-time, paper trading instead of live");
+            trading_config.paper_trading = true;
+        }
+        let paper_trading = trading_config.paper_trading;
+        let mut controller = Controller::new(trading_config);
 
         // Start the trading loop
+        let mut retry_delay = MIN_RETRY_DELAY;
         loop {
-            let event: Result<Option<(String, String)>, _> =
-                redis_conn.blpop(redis_queue.clone(), 0.0).await;
-            if event.is_err() {
-                error!("Failed to pop event from Redis");
-                continue;
-            }
-            if let Err(e) = redis_conn.incr::<&str, i32, ()>(&EVENT_PROCESSED, 1).await {
-                error!("Failed to increment event counter in Redis: {:?}", e);
-            }
+            match &mut consumer {
+                EventConsumer::Redis(consumer) => {
+                    let message = match consumer.next().await {
+                        Ok(message) => message,
+                        Err(e) => {
+                            error!(
+                                "Failed to pop event from Redis, retrying in {:?}: {:?}",
+                                retry_delay, e
+                            );
+                            tokio::time::sleep(retry_delay).await;
+                            retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                            continue;
+                        }
+                    };
+                    retry_delay = MIN_RETRY_DELAY;
+                    if let Err(e) = redis_conn.incr::<&str, i32, ()>(&EVENT_PROCESSED, 1).await {
+                        error!("Failed to increment event counter in Redis: {:?}", e);
+                    }
 
-            if let Some((_key, event_str)) = event.unwrap() {
-                let notification: TransactionNotification =
-                    serde_json::from_str(event_str.as_str()).unwrap();
-                if let Some(wrap_payload) =
-                    parse_notification(&notification, &program_id.to_string())
-                {
-                    match wrap_payload {
-                        WrapPayload::Create(event, is_paper_trade) => {
-                            // to be finished
-                            // let is_paper_trade = false;
-                            controller.handle_create(&event, is_paper_trade);
+                    let payloads = match dead_letters.decode(&origin, &message.payload).await {
+                        Some(QueueMessage::Notification(notification)) => {
+                            dead_letters
+                                .check_decodable(&origin, &notification, &program_id.to_string())
+                                .await;
+                            let envelope = PayloadEnvelope {
+                                is_paper_trade: paper_trading,
+                                ..PayloadEnvelope::new(PayloadSource::Redis, None)
+                            };
+                            parse_notification(&notification, &program_id.to_string())
+                                .map(|transaction| transaction.into_payloads(&envelope))
+                                .unwrap_or_default()
                         }
-                        WrapPayload::CreateBuy(event, is_paper_trade) => {
-                            // let is_paper_trade = false;
-                            let order = controller.handle_create_buy(&event, is_paper_trade);
-                            if let Some(order) = order {
-                                thread2_tx_for_t1.send(order).await.unwrap();
+                        // decoded by the producer, with its envelope
+                        Some(QueueMessage::Payloads(mut payloads)) => {
+                            for envelope in
+                                payloads.iter_mut().filter_map(WrapPayload::envelope_mut)
+                            {
+                                envelope.is_paper_trade = paper_trading;
                             }
+                            payloads
                         }
-                        WrapPayload::Buy(event, is_paper_trade) => {
-                            // let is_paper_trade = false;
-                            controller.handle_buy(&event, is_paper_trade);
+                        None => {
+                            if let Err(e) = consumer.ack(&[message]).await {
+                                error!("Failed to ack event in Redis: {:?}", e);
+                            }
+                            continue;
                         }
-                        WrapPayload::Sell(event, is_paper_trade) => {
-                            // let is_paper_trade = false;
-                            controller.handle_sell(&event, is_paper_trade);
+                    };
+                    for wrap_payload in payloads {
+                        if let Some(order) = controller.handle_payload(wrap_payload) {
+                            thread2_tx_for_t1.send(order).await.unwrap();
                         }
-                        WrapPayload::BuySell(event, is_paper_trade) => {
-                            // let is_paper_trade = false;
-                            // to be finished
-                            controller.handle_buy_sell(&event, is_paper_trade);
+                    }
+                    if let Err(e) = consumer.ack(&[message]).await {
+                        error!("Failed to ack event in Redis: {:?}", e);
+                    }
+                }
+                EventConsumer::Kafka(consumer) => {
+                    let message = match consumer.next().await {
+                        Ok(message) => message,
+                        Err(e) => {
+                            error!(
+                                "Failed to consume event from Kafka, retrying in {:?}: {:?}",
+                                retry_delay, e
+                            );
+                            tokio::time::sleep(retry_delay).await;
+                            retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                            continue;
                         }
-                        WrapPayload::Withdraw(event, is_paper_trade) => {
-                            // let is_paper_trade = false;
-                            // to be finished
-                            controller.handle_withdraw(&event, is_paper_trade);
+                    };
+                    retry_delay = MIN_RETRY_DELAY;
+                    if let Err(e) = redis_conn.incr::<&str, i32, ()>(&EVENT_PROCESSED, 1).await {
+                        error!("Failed to increment event counter in Redis: {:?}", e);
+                    }
+
+                    match serde_json::from_str::<WrapPayload>(&message.payload) {
+                        Ok(mut wrap_payload) => {
+                            if let Some(envelope) = wrap_payload.envelope_mut() {
+                                envelope.is_paper_trade = paper_trading;
+                            }
+                            if let Some(order) = controller.handle_payload(wrap_payload) {
+                                thread2_tx_for_t1.send(order).await.unwrap();
+                            }
+                        }
+                        Err(e) => {
+                            let origin = format!("trading_loop:{}", message.topic);
+                            let letter = DeadLetter::new(
+                                DeadLetterReason::Deserialize,
+                                e.to_string(),
+                                &origin,
+                                message.payload.clone(),
+                            );
+                            dead_letters.send(letter).await;
                         }
-                        WrapPayload::Unknown => {}
+                    }
+                    // the offset is committed only once the controller has handled the payload
+                    if let Err(e) = consumer.ack(&message) {
+                        error!("Failed to store offset in Kafka: {:?}", e);
                     }
                 }
             }
@@ -208,3 +349,8 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+enum EventConsumer {
+    Redis(Box<RedisConsumer>),
+    Kafka(PayloadConsumer),
+}
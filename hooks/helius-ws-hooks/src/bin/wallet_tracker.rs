@@ -0,0 +1,142 @@
+use clap::Parser;
+use helius::types::{
+    TransactionNotification, TransactionSubscribeOptions, UiEnhancedTransactionEncoding,
+};
+use helius::websocket::{StreamEvent, ENHANCED_WEBSOCKET_URL};
+use helius_ws_hooks::pumpfun_instruction_parser::parse_encoding;
+use helius_ws_hooks::subscription_manager::{AccountFilter, ShardLimits, SubscriptionManager};
+use log::{error, info, Level};
+use std::str::FromStr;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// A change to the tracked wallets, read from stdin
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Add(String, AccountFilter),
+    Remove(String, AccountFilter),
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    // `<add|remove|add-required|remove-required> <tag> <account>[,<account>...]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let [verb, tag, accounts] = words[..] else {
+            return Err(format!("expected <command> <tag> <accounts>, got {}", s));
+        };
+        let accounts: Vec<String> = accounts.split(',').map(str::to_string).collect();
+        let tag = tag.to_string();
+        match verb {
+            "add" => Ok(Command::Add(tag, AccountFilter::Include(accounts))),
+            "remove" => Ok(Command::Remove(tag, AccountFilter::Include(accounts))),
+            "add-required" => Ok(Command::Add(tag, AccountFilter::Required(accounts))),
+            "remove-required" => Ok(Command::Remove(tag, AccountFilter::Required(accounts))),
+            _ => Err(format!(
+                "unknown command {}, expected add, remove, add-required or remove-required",
+                verb
+            )),
+        }
+    }
+}
+
+/// Logs the transactions of tagged wallets. The wallets can be changed while running by writing
+/// commands to stdin, e.g. `add whales <account>,<account>`.
+#[derive(Parser)]
+struct Args {
+    #[clap(short = 'k', default_value = "5d166540-f22e-4f66-bb70-8349844d4a0e")]
+    api_key: String,
+    /// Transaction encoding to subscribe with: jsonParsed, base58 or base64
+    #[clap(short = 'e', long, default_value = "jsonParsed", value_parser = parse_encoding)]
+    encoding: UiEnhancedTransactionEncoding,
+    /// Wallets tracked from the start, as `<tag>=<account>`
+    #[clap(long)]
+    track: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter(None, Level::Info.to_level_filter())
+        .init();
+    let args = Args::parse();
+
+    let options = TransactionSubscribeOptions {
+        encoding: Some(args.encoding.clone()),
+        ..Default::default()
+    };
+    let url = format!("{}{}", ENHANCED_WEBSOCKET_URL, args.api_key);
+    let (mut manager, mut receiver) =
+        SubscriptionManager::new(url, options, ShardLimits::default());
+
+    for track in &args.track {
+        let Some((tag, account)) = track.split_once('=') else {
+            anyhow::bail!("expected <tag>=<account>, got {}", track);
+        };
+        manager
+            .add(tag, AccountFilter::Include(vec![account.to_string()]))
+            .await?;
+    }
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    info!("stdin closed, no more changes");
+                    // keep streaming, the wallets are fixed from now on
+                    while let Some(tagged) = receiver.recv().await {
+                        log_event(&tagged.tag, &tagged.event);
+                    }
+                    return Ok(());
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let result = match line.parse::<Command>() {
+                    Ok(Command::Add(tag, filter)) => manager.add(&tag, filter).await,
+                    Ok(Command::Remove(tag, filter)) => manager.remove(&tag, filter).await,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    }
+                };
+                match result {
+                    Ok(()) => info!(
+                        "Applied {}, subscriptions per connection {:?}",
+                        line,
+                        manager.subscriptions()
+                    ),
+                    Err(e) => error!("Failed to apply {}: {:?}", line, e),
+                }
+            }
+            tagged = receiver.recv() => {
+                let Some(tagged) = tagged else {
+                    return Ok(());
+                };
+                log_event(&tagged.tag, &tagged.event);
+            }
+        }
+    }
+}
+
+fn log_event(tag: &str, event: &StreamEvent<TransactionNotification>) {
+    match event {
+        StreamEvent::Notification(notification) => info!(
+            "[{}] slot {} {}",
+            tag, notification.slot, notification.signature
+        ),
+        StreamEvent::Gap(gap) => error!(
+            "[{}] resubscribed at {}, transactions since {} may be missing: {}",
+            tag, gap.resubscribed_at, gap.disconnected_at, gap.reason
+        ),
+        StreamEvent::ParseError(parse_error) => error!(
+            "[{}] failed to parse a notification: {}",
+            tag, parse_error.error
+        ),
+        StreamEvent::Closed(closed) => error!(
+            "[{}] closed, disconnected at {}: {}",
+            tag, closed.disconnected_at, closed.reason
+        ),
+    }
+}
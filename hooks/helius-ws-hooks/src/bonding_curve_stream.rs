@@ -0,0 +1,219 @@
+use borsh::BorshDeserialize;
+use helius::websocket::{EnhancedWebsocket, StreamEvent, UnsubscribeFn};
+use log::error;
+use pumpfun::accounts::BondingCurveAccount;
+use pumpfun::PumpFun;
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_rpc_client_api::config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_rpc_client_api::filter::{Memcmp, RpcFilterType};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::pin::Pin;
+use std::str::FromStr;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::pumpfun_trading::utils::PriceUtil;
+
+/// Anchor discriminator of the pump.fun `BondingCurve` account
+pub const BONDING_CURVE_DISCRIMINATOR: [u8; 8] = [23, 183, 248, 55, 96, 216, 172, 96];
+
+/// A bonding curve as it changed on chain
+#[derive(Debug, Clone)]
+pub struct BondingCurveUpdate {
+    pub slot: u64,
+    pub mint: Option<Pubkey>, // unknown to `pump_bonding_curves_subscribe`, see the create payloads
+    pub bonding_curve: Pubkey,
+    pub account: BondingCurveAccount,
+}
+
+impl BondingCurveUpdate {
+    /// SOL per token, from the virtual reserves
+    pub fn price_sol(&self) -> f64 {
+        let sol = self.account.virtual_sol_reserves as f64 / PriceUtil::PRICE_SCALE;
+        let token = self.account.virtual_token_reserves as f64 / PriceUtil::TOKEN_SCALE;
+        if token == 0.0 {
+            return 0.0;
+        }
+        sol / token
+    }
+}
+
+pub type BondingCurveStream = Pin<Box<dyn Stream<Item = BondingCurveUpdate> + Send>>;
+
+/// Decodes a base64 encoded bonding curve account
+pub fn decode_bonding_curve(account: &UiAccount) -> anyhow::Result<BondingCurveAccount> {
+    let Some(data) = account.data.decode() else {
+        anyhow::bail!("account data is not binary encoded");
+    };
+    if !data.starts_with(&BONDING_CURVE_DISCRIMINATOR) {
+        anyhow::bail!("not a bonding curve account");
+    }
+    // newer accounts are longer than the layout, the trailing bytes are ignored
+    Ok(BondingCurveAccount::deserialize(&mut data.as_slice())?)
+}
+
+fn account_config(commitment: Option<CommitmentConfig>) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment,
+        ..Default::default()
+    }
+}
+
+/// Streams the bonding curve of `mint`, from its PDA
+pub async fn bonding_curve_subscribe(
+    ws: &EnhancedWebsocket,
+    mint: &Pubkey,
+    commitment: Option<CommitmentConfig>,
+) -> anyhow::Result<(BondingCurveStream, UnsubscribeFn)> {
+    let Some(bonding_curve) = PumpFun::get_bonding_curve_pda(mint) else {
+        anyhow::bail!("no bonding curve PDA for {}", mint);
+    };
+    let mint = *mint;
+    let (events, unsubscribe) = ws
+        .account_subscribe_with_gaps(&bonding_curve, Some(account_config(commitment)))
+        .await?;
+    let updates = events.filter_map(move |event| match event {
+        StreamEvent::Notification(response) => match decode_bonding_curve(&response.value) {
+            Ok(account) => Some(BondingCurveUpdate {
+                slot: response.context.slot,
+                mint: Some(mint),
+                bonding_curve,
+                account,
+            }),
+            Err(e) => {
+                error!("Failed to decode bonding curve {}: {:?}", bonding_curve, e);
+                None
+            }
+        },
+        StreamEvent::Gap(gap) => {
+            error!(
+                "Bonding curve {} resubscribed at {}, updates since {} may be missing: {}",
+                bonding_curve, gap.resubscribed_at, gap.disconnected_at, gap.reason
+            );
+            None
+        }
+        StreamEvent::ParseError(parse_error) => {
+            error!(
+                "Failed to parse bonding curve {} notification: {}",
+                bonding_curve, parse_error.error
+            );
+            None
+        }
+        StreamEvent::Closed(closed) => {
+            error!(
+                "Bonding curve {} stream closed, disconnected at {}: {}",
+                bonding_curve, closed.disconnected_at, closed.reason
+            );
+            None
+        }
+    });
+    Ok((Box::pin(updates), unsubscribe))
+}
+
+/// Streams every bonding curve of the pump.fun program, on a websocket serving `programSubscribe`
+pub async fn pump_bonding_curves_subscribe(
+    ws: &EnhancedWebsocket,
+    commitment: Option<CommitmentConfig>,
+) -> anyhow::Result<(BondingCurveStream, UnsubscribeFn)> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            0,
+            &BONDING_CURVE_DISCRIMINATOR,
+        ))]),
+        account_config: account_config(commitment),
+        with_context: Some(true),
+        ..Default::default()
+    };
+    let (events, unsubscribe) = ws
+        .program_subscribe_with_gaps(&pumpfun_cpi::ID, Some(config))
+        .await?;
+    let updates = events.filter_map(|event| match event {
+        StreamEvent::Notification(response) => {
+            let keyed = response.value;
+            let bonding_curve = match Pubkey::from_str(&keyed.pubkey) {
+                Ok(bonding_curve) => bonding_curve,
+                Err(e) => {
+                    error!("Invalid bonding curve address {}: {:?}", keyed.pubkey, e);
+                    return None;
+                }
+            };
+            match decode_bonding_curve(&keyed.account) {
+                Ok(account) => Some(BondingCurveUpdate {
+                    slot: response.context.slot,
+                    mint: None,
+                    bonding_curve,
+                    account,
+                }),
+                Err(e) => {
+                    error!("Failed to decode bonding curve {}: {:?}", bonding_curve, e);
+                    None
+                }
+            }
+        }
+        StreamEvent::Gap(gap) => {
+            error!(
+                "Bonding curves resubscribed at {}, updates since {} may be missing: {}",
+                gap.resubscribed_at, gap.disconnected_at, gap.reason
+            );
+            None
+        }
+        StreamEvent::ParseError(parse_error) => {
+            error!(
+                "Failed to parse a bonding curves notification: {}",
+                parse_error.error
+            );
+            None
+        }
+        StreamEvent::Closed(closed) => {
+            error!(
+                "Bonding curves stream closed, disconnected at {}: {}",
+                closed.disconnected_at, closed.reason
+            );
+            None
+        }
+    });
+    Ok((Box::pin(updates), unsubscribe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    fn ui_account(data: &[u8]) -> UiAccount {
+        serde_json::from_value(serde_json::json!({
+            "lamports": 1_000_000,
+            "data": [base64::engine::general_purpose::STANDARD.encode(data), "base64"],
+            "owner": pumpfun_cpi::ID.to_string(),
+            "executable": false,
+            "rentEpoch": 0,
+            "space": data.len()
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decode_bonding_curve() {
+        let account = BondingCurveAccount::new(
+            u64::from_le_bytes(BONDING_CURVE_DISCRIMINATOR),
+            1_073_000_000_000_000,
+            30_000_000_000,
+            793_100_000_000_000,
+            0,
+            1_000_000_000_000_000,
+            false,
+        );
+        let mut data = borsh::to_vec(&account).unwrap();
+        // the creator appended by newer accounts
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+
+        let decoded = decode_bonding_curve(&ui_account(&data)).unwrap();
+        assert_eq!(30_000_000_000, decoded.virtual_sol_reserves);
+        assert_eq!(793_100_000_000_000, decoded.real_token_reserves);
+        assert!(!decoded.complete);
+
+        data[0] = 0;
+        assert!(decode_bonding_curve(&ui_account(&data)).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use helius::types::TransactionNotification;
+use log::error;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::ingest::sink::{EventSink, SinkEvent};
+use crate::payload_codec::{decode_message, QueueMessage};
+use crate::pumpfun_instruction_parser::{undecodable_instructions, PayloadEnvelope, PayloadSource};
+use crate::redis_queue::RedisProducer;
+
+/// Why a message could not be processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterReason {
+    Deserialize, // not a notification
+    Decode,      // a known pump.fun instruction that does not decode
+    Sink,        // a sink failed to write one of its events
+}
+
+impl FromStr for DeadLetterReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deserialize" => Ok(DeadLetterReason::Deserialize),
+            "decode" => Ok(DeadLetterReason::Decode),
+            "sink" => Ok(DeadLetterReason::Sink),
+            _ => Err(format!(
+                "unknown reason {}, expected deserialize, decode or sink",
+                s
+            )),
+        }
+    }
+}
+
+/// A message set aside, with the raw payload to resubmit once the cause is fixed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub reason: DeadLetterReason,
+    pub error: String,
+    pub origin: String,  // the queue, source or sink the message failed in
+    pub payload: String, // the message as received, a notification json or binary payloads
+    pub failed_at: i64,  // local unix timestamp, milliseconds
+    #[serde(default)]
+    pub binary: bool, // payload is base64 encoded
+    #[serde(default)]
+    pub sink: Option<String>, // the sink that failed to write it, the only one it is replayed to
+}
+
+impl DeadLetter {
+    pub fn new(reason: DeadLetterReason, error: String, origin: &str, payload: String) -> Self {
+        Self {
+            reason,
+            error,
+            origin: origin.to_string(),
+            payload,
+            failed_at: chrono::Utc::now().timestamp_millis(),
+            binary: false,
+            sink: None,
+        }
+    }
+
+    /// A raw queue message, base64 encoded unless it is text
+    pub fn from_message(
+        reason: DeadLetterReason,
+        error: String,
+        origin: &str,
+        message: &[u8],
+    ) -> Self {
+        match std::str::from_utf8(message) {
+            Ok(payload) => Self::new(reason, error, origin, payload.to_string()),
+            Err(_) => Self {
+                binary: true,
+                ..Self::new(reason, error, origin, STANDARD.encode(message))
+            },
+        }
+    }
+
+    /// The message as received, to resubmit
+    pub fn message(&self) -> anyhow::Result<Vec<u8>> {
+        if self.binary {
+            Ok(STANDARD.decode(&self.payload)?)
+        } else {
+            Ok(self.payload.clone().into_bytes())
+        }
+    }
+}
+
+/// Where dead letters are written, `type` picks the sink
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeadLetterConfig {
+    /// RPUSH onto a redis list
+    Redis {
+        redis_url: String,
+        key: String,
+    },
+    /// One json line per dead letter, appended
+    File {
+        path: String,
+    },
+    Kafka {
+        brokers: String,
+        topic: String,
+    },
+}
+
+pub enum DeadLetterSink {
+    /// Only log, for when no sink is configured
+    Log,
+    Redis {
+        conn: MultiplexedConnection,
+        key: String,
+    },
+    File(File),
+    Kafka {
+        producer: FutureProducer,
+        topic: String,
+    },
+}
+
+impl DeadLetterSink {
+    pub async fn connect(config: &DeadLetterConfig) -> anyhow::Result<Self> {
+        Ok(match config {
+            DeadLetterConfig::Redis { redis_url, key } => {
+                let redis_client = redis::Client::open(redis_url.as_str())?;
+                DeadLetterSink::Redis {
+                    conn: redis_client.get_multiplexed_async_connection().await?,
+                    key: key.clone(),
+                }
+            }
+            DeadLetterConfig::File { path } => {
+                DeadLetterSink::File(OpenOptions::new().create(true).append(true).open(path)?)
+            }
+            DeadLetterConfig::Kafka { brokers, topic } => DeadLetterSink::Kafka {
+                producer: ClientConfig::new()
+                    .set("bootstrap.servers", brokers)
+                    .set("message.timeout.ms", "5000")
+                    .create()?,
+                topic: topic.clone(),
+            },
+        })
+    }
+
+    /// A redis list on an open connection
+    pub fn redis(conn: MultiplexedConnection, key: String) -> Self {
+        DeadLetterSink::Redis { conn, key }
+    }
+
+    /// Logs the dead letter, then writes it. A failed write is logged too, the message is lost.
+    pub async fn send(&mut self, letter: DeadLetter) {
+        error!(
+            "Dead letter from {}, {:?}: {}",
+            letter.origin, letter.reason, letter.error
+        );
+        if let Err(e) = self.write(&letter).await {
+            error!("Failed to write dead letter: {:?}", e);
+        }
+    }
+
+    async fn write(&mut self, letter: &DeadLetter) -> anyhow::Result<()> {
+        let json = serde_json::to_string(letter)?;
+        match self {
+            DeadLetterSink::Log => {}
+            DeadLetterSink::Redis { conn, key } => conn.rpush::<_, _, ()>(&*key, &json).await?,
+            DeadLetterSink::File(file) => {
+                writeln!(file, "{}", json)?;
+                file.flush()?;
+            }
+            DeadLetterSink::Kafka { producer, topic } => {
+                producer
+                    .send(
+                        FutureRecord::<(), _>::to(topic).payload(&json),
+                        Duration::from_secs(0),
+                    )
+                    .await
+                    .map_err(|(e, _message)| e)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The notification or payloads in `message`, or None once it is dead-lettered
+    pub async fn decode(&mut self, origin: &str, message: &[u8]) -> Option<QueueMessage> {
+        match decode_message(message) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                let letter = DeadLetter::from_message(
+                    DeadLetterReason::Deserialize,
+                    e.to_string(),
+                    origin,
+                    message,
+                );
+                self.send(letter).await;
+                None
+            }
+        }
+    }
+
+    /// Dead-letters the notification json if any of its `program_id` instructions does not
+    /// decode. The rest of the notification is still processed.
+    pub async fn check_decodable(
+        &mut self,
+        origin: &str,
+        notification: &TransactionNotification,
+        program_id: &str,
+    ) {
+        let undecodable = undecodable_instructions(notification, program_id);
+        if !undecodable.is_empty() {
+            let letter = DeadLetter::new(
+                DeadLetterReason::Decode,
+                undecodable.join("; "),
+                origin,
+                serde_json::to_string(notification).unwrap_or_default(),
+            );
+            self.send(letter).await;
+        }
+    }
+}
+
+/// Resubmits dead letters: a sink letter to the sink that failed it, any other to the queue
+pub struct Replay {
+    pub reason: Option<DeadLetterReason>, // only replay the letters of this reason
+    pub queue: Option<RedisProducer>,
+    pub sinks: Vec<Box<dyn EventSink>>, // of the pipeline that wrote the sink letters
+}
+
+impl Replay {
+    /// Whether the letter was replayed. A letter of another reason, or without its queue or sink,
+    /// is kept.
+    pub async fn replay(&mut self, letter: &DeadLetter) -> anyhow::Result<bool> {
+        if self.reason.is_some_and(|reason| reason != letter.reason) {
+            return Ok(false);
+        }
+        let message = letter.message()?;
+        let Some(name) = &letter.sink else {
+            let Some(queue) = self.queue.as_mut() else {
+                return Ok(false);
+            };
+            queue.push_bytes(&message).await?;
+            return Ok(true);
+        };
+        // the other sinks of the pipeline already wrote the event
+        let Some(sink) = self.sinks.iter_mut().find(|sink| sink.name() == name) else {
+            return Ok(false);
+        };
+        match decode_message(&message)? {
+            QueueMessage::Notification(notification) => {
+                // received about when it failed
+                let envelope = PayloadEnvelope {
+                    received_at: letter.failed_at,
+                    ..PayloadEnvelope::new(PayloadSource::Replay, None)
+                };
+                sink.write(SinkEvent::Notification(&notification, &envelope))
+                    .await?
+            }
+            // a sink takes either the payloads as a whole or each of them, never both
+            QueueMessage::Payloads(payloads) => {
+                sink.write(SinkEvent::Payloads(&payloads)).await?;
+                for payload in &payloads {
+                    sink.write(SinkEvent::Payload(payload)).await?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Replays the letters of a redis list, returning how many were replayed and kept. Each is
+    /// popped once, those kept are pushed back at the end of the list. A letter that fails to
+    /// replay is pushed back too, before the error is returned.
+    pub async fn replay_redis(
+        &mut self,
+        conn: &mut MultiplexedConnection,
+        key: &str,
+    ) -> anyhow::Result<(usize, usize)> {
+        let (mut replayed, mut kept) = (0, 0);
+        let len: usize = conn.llen(key).await?;
+        for _ in 0..len {
+            let Some(json): Option<String> = conn.lpop(key, None).await? else {
+                break;
+            };
+            let outcome = match serde_json::from_str::<DeadLetter>(&json) {
+                Ok(letter) => self.replay(&letter).await,
+                Err(e) => {
+                    error!("Keeping an entry that is not a dead letter: {:?}", e);
+                    Ok(false)
+                }
+            };
+            match outcome {
+                Ok(true) => replayed += 1,
+                Ok(false) => {
+                    conn.rpush::<_, _, ()>(key, &json).await?;
+                    kept += 1;
+                }
+                Err(e) => {
+                    conn.rpush::<_, _, ()>(key, &json).await?;
+                    return Err(e);
+                }
+            }
+        }
+        Ok((replayed, kept))
+    }
+
+    /// Flushes the sinks
+    pub async fn close(&mut self) -> anyhow::Result<()> {
+        for sink in &mut self.sinks {
+            sink.close().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch_csv_writer::CsvCompression;
+    use crate::ingest::sink::{build_sink, SinkConfig};
+    use crate::payload_codec::encode_payloads;
+    use crate::pumpfun_instruction_parser::WrapPayload;
+    use crate::redis_queue::RedisTransport;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+
+    #[tokio::test]
+    async fn test_dead_letter_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead_letters.jsonl");
+        let config = DeadLetterConfig::File {
+            path: path.to_str().unwrap().to_string(),
+        };
+        let mut dead_letters = DeadLetterSink::connect(&config).await.unwrap();
+
+        assert!(dead_letters
+            .decode("redis:events", b"{\"truncated")
+            .await
+            .is_none());
+        assert!(dead_letters
+            .decode("redis:events", &[0xff, 0x00])
+            .await
+            .is_none());
+
+        let lines: Vec<String> = BufReader::new(File::open(&path).unwrap())
+            .lines()
+            .map(|line| line.unwrap())
+            .collect();
+        assert_eq!(2, lines.len());
+        let letter: DeadLetter = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(DeadLetterReason::Deserialize, letter.reason);
+        assert_eq!("redis:events", letter.origin);
+        assert_eq!("{\"truncated", letter.payload);
+        assert!(!letter.binary);
+        let letter: DeadLetter = serde_json::from_str(&lines[1]).unwrap();
+        assert!(letter.binary);
+        assert_eq!(vec![0xff, 0x00], letter.message().unwrap());
+    }
+
+    fn sink_letter(sink: &str) -> DeadLetter {
+        let message = encode_payloads(&[WrapPayload::Unknown]).unwrap();
+        DeadLetter {
+            sink: Some(sink.to_string()),
+            ..DeadLetter::from_message(
+                DeadLetterReason::Sink,
+                "down".to_string(),
+                "helius_ws",
+                &message,
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_to_failed_sink() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SinkConfig::Csv {
+            dir: dir.path().to_str().unwrap().to_string(),
+            compression: CsvCompression::None,
+            max_file_mb: None,
+        };
+        let mut replay = Replay {
+            reason: None,
+            queue: None,
+            sinks: vec![build_sink(&config).await.unwrap()],
+        };
+
+        assert!(replay.replay(&sink_letter(&config.name())).await.unwrap());
+        // written by the other sinks already
+        assert!(!replay.replay(&sink_letter("redis:events")).await.unwrap());
+        // no queue to replay to
+        let letter = DeadLetter::new(
+            DeadLetterReason::Decode,
+            "bad".to_string(),
+            "helius_ws",
+            "{}".to_string(),
+        );
+        assert!(!replay.replay(&letter).await.unwrap());
+        replay.close().await.unwrap();
+
+        let entry = fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap();
+        let records = csv::Reader::from_path(entry.path())
+            .unwrap()
+            .records()
+            .count();
+        assert_eq!(1, records);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a redis server, at REDIS_URL or 127.0.0.1"]
+    async fn test_replay_redis() {
+        let redis_url =
+            std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
+        let mut conn = redis::Client::open(redis_url)
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        let key = format!("test_dead_letters:{}", std::process::id());
+        let queue = format!("test_replayed:{}", std::process::id());
+        let _: () = conn.del(&[&key, &queue]).await.unwrap();
+
+        let letter = DeadLetter::new(
+            DeadLetterReason::Decode,
+            "bad".to_string(),
+            "helius_ws",
+            "{}".to_string(),
+        );
+        for letter in [&letter, &sink_letter("csv:/tmp/csv")] {
+            let json = serde_json::to_string(letter).unwrap();
+            let _: () = conn.rpush(&key, json).await.unwrap();
+        }
+        let mut replay = Replay {
+            reason: None,
+            queue: Some(RedisProducer::new(
+                conn.clone(),
+                queue.clone(),
+                RedisTransport::List,
+                0,
+            )),
+            sinks: vec![],
+        };
+        assert_eq!((1, 1), replay.replay_redis(&mut conn, &key).await.unwrap());
+        let replayed: Vec<String> = conn.lrange(&queue, 0, -1).await.unwrap();
+        assert_eq!(vec!["{}".to_string()], replayed);
+        let kept: usize = conn.llen(&key).await.unwrap();
+        assert_eq!(1, kept);
+
+        // a failed push leaves the letter on the list
+        let _: () = conn.set(&queue, "not a list").await.unwrap();
+        let json = serde_json::to_string(&letter).unwrap();
+        let _: () = conn.rpush(&key, &json).await.unwrap();
+        assert!(replay.replay_redis(&mut conn, &key).await.is_err());
+        let left: Vec<String> = conn.lrange(&key, 0, -1).await.unwrap();
+        assert_eq!(2, left.len());
+        assert!(left.contains(&json));
+
+        let _: () = conn.del(&[&key, &queue]).await.unwrap();
+    }
+}
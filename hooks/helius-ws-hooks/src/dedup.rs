@@ -0,0 +1,379 @@
+use log::{error, info};
+use redis::aio::MultiplexedConnection;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::pumpfun_instruction_parser::InstructionIndex;
+
+/// Where the keys already seen are kept, `type` picks the store
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DedupConfig {
+    /// In process, for a single pipeline reading every upstream
+    Memory {
+        #[serde(default = "default_ttl_secs")]
+        ttl_secs: u64,
+        #[serde(default = "default_capacity")]
+        capacity: usize, // keys kept at most, the oldest are forgotten first
+    },
+    /// `SET NX PX` on redis, shared by every pipeline using the same `key_prefix`
+    Redis {
+        redis_url: String,
+        #[serde(default = "default_key_prefix")]
+        key_prefix: String,
+        #[serde(default = "default_ttl_secs")]
+        ttl_secs: u64,
+    },
+}
+
+fn default_ttl_secs() -> u64 {
+    120
+}
+
+fn default_capacity() -> usize {
+    1_000_000
+}
+
+fn default_key_prefix() -> String {
+    "dedup".to_string()
+}
+
+/// Who delivered a key first, and when
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirstCopy {
+    pub upstream: String,
+    pub received_at: i64, // local unix timestamp, milliseconds
+}
+
+impl FirstCopy {
+    // `<received_at>:<upstream>`, as stored on redis
+    fn to_value(&self) -> String {
+        format!("{}:{}", self.received_at, self.upstream)
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        let (received_at, upstream) = value.split_once(':')?;
+        Some(Self {
+            upstream: upstream.to_string(),
+            received_at: received_at.parse().ok()?,
+        })
+    }
+}
+
+/// Whether an event is the first copy of its key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DedupOutcome {
+    First,
+    Duplicate(FirstCopy),
+}
+
+/// Per upstream: how often it delivered first, and how far behind it was otherwise
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamStats {
+    pub first: u64,
+    pub duplicate: u64,
+    pub total_lag_ms: u64, // summed over the duplicates
+    pub max_lag_ms: u64,
+}
+
+impl UpstreamStats {
+    pub fn mean_lag_ms(&self) -> u64 {
+        self.total_lag_ms.checked_div(self.duplicate).unwrap_or(0)
+    }
+}
+
+/// Time-bounded set of the keys seen, oldest first
+pub struct MemoryStore {
+    ttl: Duration,
+    capacity: usize,
+    seen: HashMap<String, FirstCopy>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl MemoryStore {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// First unless `key` was seen within the ttl, then the copy seen first
+    pub fn insert(&mut self, key: &str, first: FirstCopy) -> DedupOutcome {
+        while self
+            .order
+            .front()
+            .is_some_and(|(inserted, _)| inserted.elapsed() >= self.ttl)
+        {
+            self.evict_oldest();
+        }
+        if let Some(first) = self.seen.get(key) {
+            return DedupOutcome::Duplicate(first.clone());
+        }
+        while self.order.len() >= self.capacity {
+            self.evict_oldest();
+        }
+        self.seen.insert(key.to_string(), first);
+        self.order.push_back((Instant::now(), key.to_string()));
+        DedupOutcome::First
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((_, key)) = self.order.pop_front() {
+            self.seen.remove(&key);
+        }
+    }
+}
+
+pub enum DedupStore {
+    Memory(MemoryStore),
+    Redis {
+        conn: MultiplexedConnection,
+        key_prefix: String,
+        ttl: Duration,
+    },
+}
+
+/// Passes the first copy of an event delivered by redundant upstreams, keyed on the signature and
+/// the instruction it was decoded from, `<signature>:<outer>[.<inner>]`. A transaction without
+/// events is keyed on its signature alone.
+pub struct Deduplicator {
+    store: DedupStore,
+    stats: BTreeMap<String, UpstreamStats>,
+}
+
+impl Deduplicator {
+    pub async fn connect(config: &DedupConfig) -> anyhow::Result<Self> {
+        let store = match config {
+            DedupConfig::Memory { ttl_secs, capacity } => {
+                DedupStore::Memory(MemoryStore::new(Duration::from_secs(*ttl_secs), *capacity))
+            }
+            DedupConfig::Redis {
+                redis_url,
+                key_prefix,
+                ttl_secs,
+            } => {
+                let redis_client = redis::Client::open(redis_url.as_str())?;
+                DedupStore::Redis {
+                    conn: redis_client.get_multiplexed_async_connection().await?,
+                    key_prefix: key_prefix.clone(),
+                    ttl: Duration::from_secs(*ttl_secs),
+                }
+            }
+        };
+        Ok(Self::new(store))
+    }
+
+    pub fn new(store: DedupStore) -> Self {
+        Self {
+            store,
+            stats: BTreeMap::new(),
+        }
+    }
+
+    /// Records the copy `upstream` received at `received_at`. If the store fails the copy is
+    /// passed, a duplicate is better than a gap.
+    pub async fn check(
+        &mut self,
+        signature: &str,
+        instruction: Option<InstructionIndex>,
+        upstream: &str,
+        received_at: i64,
+    ) -> DedupOutcome {
+        self.check_all(signature, &[instruction], upstream, received_at)
+            .await
+            .remove(0)
+    }
+
+    /// `check` for every event of a transaction, one outcome per instruction, in a single round
+    /// trip to redis
+    pub async fn check_all(
+        &mut self,
+        signature: &str,
+        instructions: &[Option<InstructionIndex>],
+        upstream: &str,
+        received_at: i64,
+    ) -> Vec<DedupOutcome> {
+        let keys: Vec<String> = instructions
+            .iter()
+            .map(|instruction| match instruction {
+                Some(instruction) => format!("{}:{}", signature, instruction),
+                None => signature.to_string(),
+            })
+            .collect();
+        let copy = FirstCopy {
+            upstream: upstream.to_string(),
+            received_at,
+        };
+        let outcomes = match &mut self.store {
+            DedupStore::Memory(store) => keys
+                .iter()
+                .map(|key| store.insert(key, copy.clone()))
+                .collect(),
+            DedupStore::Redis {
+                conn,
+                key_prefix,
+                ttl,
+            } => {
+                let keys: Vec<String> = keys
+                    .iter()
+                    .map(|key| format!("{}:{}", key_prefix, key))
+                    .collect();
+                match redis_insert(conn, &keys, &copy, *ttl).await {
+                    Ok(outcomes) => outcomes,
+                    Err(e) => {
+                        error!("Failed to check {} for duplicates: {:?}", signature, e);
+                        vec![DedupOutcome::First; keys.len()]
+                    }
+                }
+            }
+        };
+
+        let stats = self.stats.entry(upstream.to_string()).or_default();
+        for outcome in &outcomes {
+            match outcome {
+                DedupOutcome::First => stats.first += 1,
+                DedupOutcome::Duplicate(first) => {
+                    let lag_ms = (received_at - first.received_at).max(0) as u64;
+                    stats.duplicate += 1;
+                    stats.total_lag_ms += lag_ms;
+                    stats.max_lag_ms = stats.max_lag_ms.max(lag_ms);
+                }
+            }
+        }
+        outcomes
+    }
+
+    pub fn stats(&self) -> &BTreeMap<String, UpstreamStats> {
+        &self.stats
+    }
+
+    pub fn log_stats(&self) {
+        for (upstream, stats) in &self.stats {
+            info!(
+                "Upstream {}: first {}, duplicate {}, behind by {}ms on average, {}ms at most",
+                upstream,
+                stats.first,
+                stats.duplicate,
+                stats.mean_lag_ms(),
+                stats.max_lag_ms
+            );
+        }
+    }
+}
+
+// SET NX and GET of every key in one transaction: the value read back is the first copy, ours if
+// SET won
+async fn redis_insert(
+    conn: &mut MultiplexedConnection,
+    keys: &[String],
+    copy: &FirstCopy,
+    ttl: Duration,
+) -> anyhow::Result<Vec<DedupOutcome>> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    for key in keys {
+        pipe.cmd("SET")
+            .arg(key)
+            .arg(copy.to_value())
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .cmd("GET")
+            .arg(key);
+    }
+    let replies: Vec<Option<String>> = pipe.query_async(conn).await?;
+    if replies.len() != keys.len() * 2 {
+        anyhow::bail!("expected {} replies, got {}", keys.len() * 2, replies.len());
+    }
+    replies
+        .chunks_exact(2)
+        .map(|reply| {
+            if reply[0].is_some() {
+                return Ok(DedupOutcome::First);
+            }
+            match reply[1].as_deref().and_then(FirstCopy::from_value) {
+                Some(first) => Ok(DedupOutcome::Duplicate(first)),
+                None => anyhow::bail!("unexpected value {:?}", reply[1]),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outer(outer: usize) -> InstructionIndex {
+        InstructionIndex { outer, inner: None }
+    }
+
+    #[tokio::test]
+    async fn test_dedup() {
+        let store = DedupStore::Memory(MemoryStore::new(Duration::from_secs(60), 2));
+        let mut dedup = Deduplicator::new(store);
+
+        assert_eq!(
+            DedupOutcome::First,
+            dedup.check("sig1", Some(outer(0)), "ewr", 1000).await
+        );
+        assert_eq!(
+            DedupOutcome::First,
+            dedup.check("sig1", Some(outer(1)), "ewr", 1000).await
+        );
+        assert_eq!(
+            DedupOutcome::Duplicate(FirstCopy {
+                upstream: "ewr".to_string(),
+                received_at: 1000
+            }),
+            dedup.check("sig1", Some(outer(1)), "fra", 1030).await
+        );
+        let fra = dedup.stats()["fra"];
+        assert_eq!(
+            (0, 1, 30, 30),
+            (fra.first, fra.duplicate, fra.max_lag_ms, fra.mean_lag_ms())
+        );
+        assert_eq!(2, dedup.stats()["ewr"].first);
+
+        // over capacity the oldest key is forgotten
+        assert_eq!(
+            DedupOutcome::First,
+            dedup.check("sig2", Some(outer(0)), "fra", 2000).await
+        );
+        assert_eq!(
+            DedupOutcome::First,
+            dedup.check("sig1", Some(outer(0)), "fra", 2000).await
+        );
+
+        // the events of a transaction at once
+        let inner = InstructionIndex {
+            outer: 0,
+            inner: Some(1),
+        };
+        assert_eq!(
+            vec![DedupOutcome::First, DedupOutcome::First],
+            dedup
+                .check_all("sig3", &[Some(outer(0)), Some(inner)], "ewr", 3000)
+                .await
+        );
+        assert!(matches!(
+            dedup.check("sig3", Some(inner), "fra", 3010).await,
+            DedupOutcome::Duplicate(_)
+        ));
+    }
+
+    #[test]
+    fn test_first_copy_value() {
+        let first = FirstCopy {
+            upstream: "helius_ws:fra".to_string(),
+            received_at: 1000,
+        };
+        assert_eq!(
+            Some(first.clone()),
+            FirstCopy::from_value(&first.to_value())
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use serde::Deserialize;
+use std::fs;
+
+use crate::dead_letter::DeadLetterConfig;
+use crate::dedup::DedupConfig;
+use crate::ingest::filter::FilterConfig;
+use crate::ingest::sink::SinkConfig;
+use crate::ingest::source::SourceConfig;
+use crate::pumpfun_instruction_parser::PUMPFUN_PROGRAM_ID;
+
+/// A source, the filters its payloads must all match, and the sinks every kept event is written to
+#[derive(Debug, Clone, Deserialize)]
+pub struct PipelineConfig {
+    #[serde(default = "default_program_id")]
+    pub program_id: String, // subscribed to, and decoded
+    #[serde(default)]
+    pub capture_failed: bool,
+    #[serde(default)]
+    pub decoders: Vec<String>, // other programs decoded too: pump_amm, raydium_amm or spl_token
+    pub source: SourceConfig,
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    pub sinks: Vec<SinkConfig>,
+    pub dead_letter: Option<DeadLetterConfig>, // only logged if unset
+    pub dedup: Option<DedupConfig>,            // every copy is processed if unset
+}
+
+impl PipelineConfig {
+    /// A pump.fun pipeline without filters, dead letters or dedup, for the binaries that preset
+    /// their pipeline from their arguments
+    pub fn new(source: SourceConfig, sinks: Vec<SinkConfig>) -> Self {
+        Self {
+            program_id: default_program_id(),
+            capture_failed: false,
+            decoders: vec![],
+            source,
+            filters: vec![],
+            sinks,
+            dead_letter: None,
+            dedup: None,
+        }
+    }
+}
+
+fn default_program_id() -> String {
+    PUMPFUN_PROGRAM_ID.to_string()
+}
+
+pub fn load_pipeline_config(config_path: &str) -> anyhow::Result<PipelineConfig> {
+    let file_content = fs::read_to_string(config_path)?;
+    let config: PipelineConfig = toml::from_str(&file_content)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch_csv_writer::CsvCompression;
+    use crate::ingest::sink::SinkFormat;
+    use crate::kafka_producer::TopicRouting;
+    use crate::redis_queue::RedisTransport;
+    use helius::types::enhanced_websocket::TransactionCommitment;
+    use helius::websocket::ENHANCED_WEBSOCKET_URL;
+    use std::io::Write;
+
+    #[test]
+    fn test_load_pipeline_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("ingest.toml");
+        let mut file = fs::File::create(&file_path).unwrap();
+        write!(
+            file,
+            r#"
+capture_failed = true
+decoders = ["pump_amm", "spl_token"]
+
+[source]
+type = "helius_ws"
+api_key = "api_key"
+commitment = "confirmed"
+
+[[source.upstreams]]
+name = "fra"
+api_key = "other_api_key"
+
+[[filters]]
+type = "action"
+actions = ["create", "createbuy"]
+
+[[sinks]]
+type = "csv"
+dir = "/tmp/csv"
+compression = "zstd"
+
+[[sinks]]
+type = "redis"
+redis_url = "redis://127.0.0.1/"
+queue = "events"
+transport = "stream"
+format = "notification"
+
+[[sinks]]
+type = "kafka"
+brokers = "localhost:9092"
+topic = "pumpfun"
+format = "binary"
+routing = "action"
+
+[[sinks]]
+type = "stdout"
+
+[dead_letter]
+type = "file"
+path = "/tmp/dead_letters.jsonl"
+
+[dedup]
+type = "memory"
+"#
+        )
+        .unwrap();
+
+        let config = load_pipeline_config(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(PUMPFUN_PROGRAM_ID, config.program_id);
+        assert!(config.capture_failed);
+        assert_eq!(vec!["pump_amm", "spl_token"], config.decoders);
+        match config.source {
+            SourceConfig::HeliusWs {
+                upstreams,
+                encoding,
+                commitment,
+                ..
+            } => {
+                assert_eq!(1, upstreams.len());
+                assert_eq!(ENHANCED_WEBSOCKET_URL, upstreams[0].url);
+                assert_eq!("jsonParsed", encoding);
+                assert_eq!(Some(TransactionCommitment::Confirmed), commitment);
+            }
+            other => panic!("expected a helius source, got {:?}", other),
+        }
+        assert_eq!(1, config.filters.len());
+        assert_eq!(4, config.sinks.len());
+        assert!(matches!(
+            config.sinks[0],
+            SinkConfig::Csv {
+                compression: CsvCompression::Zstd,
+                max_file_mb: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            config.sinks[1],
+            SinkConfig::Redis {
+                transport: RedisTransport::Stream,
+                max_len: 1_000_000,
+                format: SinkFormat::Notification,
+                ..
+            }
+        ));
+        assert!(matches!(
+            config.sinks[2],
+            SinkConfig::Kafka {
+                format: SinkFormat::Binary,
+                routing: TopicRouting::Action,
+                max_in_flight: 1000,
+                ..
+            }
+        ));
+        assert!(matches!(
+            config.sinks[3],
+            SinkConfig::Stdout {
+                format: SinkFormat::Payload
+            }
+        ));
+        assert!(matches!(
+            config.dead_letter,
+            Some(DeadLetterConfig::File { .. })
+        ));
+        assert!(matches!(
+            config.dedup,
+            Some(DedupConfig::Memory {
+                ttl_secs: 120,
+                capacity: 1_000_000
+            })
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+use serde::Deserialize;
+
+use crate::pumpfun_instruction_parser::WrapPayload;
+
+/// Keeps a payload only if it matches. A pipeline keeps the payloads matching all its filters.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterConfig {
+    /// The csv action names, e.g. "createbuy" or "failed"
+    Action {
+        actions: Vec<String>,
+    },
+    Mint {
+        mints: Vec<String>,
+    },
+    /// A launch whose token name contains `pattern`, ignoring case
+    NameContains {
+        pattern: String,
+    },
+}
+
+impl FilterConfig {
+    pub fn matches(&self, payload: &WrapPayload) -> bool {
+        match self {
+            FilterConfig::Action { actions } => {
+                actions.iter().any(|action| action == payload.action())
+            }
+            FilterConfig::Mint { mints } => payload
+                .mint_pk()
+                .is_some_and(|mint_pk| mints.iter().any(|mint| mint == mint_pk)),
+            FilterConfig::NameContains { pattern } => payload
+                .name()
+                .is_some_and(|name| name.to_lowercase().contains(&pattern.to_lowercase())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pumpfun_fee_parser::PayloadFees;
+    use crate::pumpfun_instruction_parser::{
+        InstructionIndex, PayloadCreate, PayloadEnvelope, PayloadSource,
+    };
+
+    #[test]
+    fn test_filter_matches() {
+        let create = WrapPayload::Create(
+            PayloadCreate {
+                slot: 320000000,
+                signature: "sig".to_string(),
+                instruction: InstructionIndex::default(),
+                mint_pk: "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump".to_string(),
+                user_pk: "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47".to_string(),
+                name: "Official Trump".to_string(),
+                symbol: "TRUMP".to_string(),
+                uri: "https://ipfs.io/ipfs/trump".to_string(),
+                bonding_curve: "bonding_curve".to_string(),
+                associated_bonding_curve: "associated_bonding_curve".to_string(),
+                router: None,
+                fees: PayloadFees::default(),
+            },
+            PayloadEnvelope::new(PayloadSource::HeliusWs, None),
+        );
+
+        let filter: FilterConfig =
+            toml::from_str("type = \"action\"\nactions = [\"create\", \"createbuy\"]").unwrap();
+        assert!(filter.matches(&create));
+        assert!(!filter.matches(&WrapPayload::Unknown));
+
+        let filter = FilterConfig::Mint {
+            mints: vec!["8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump".to_string()],
+        };
+        assert!(filter.matches(&create));
+
+        let filter = FilterConfig::NameContains {
+            pattern: "trump".to_string(),
+        };
+        assert!(filter.matches(&create));
+        let filter = FilterConfig::NameContains {
+            pattern: "melania".to_string(),
+        };
+        assert!(!filter.matches(&create));
+    }
+}
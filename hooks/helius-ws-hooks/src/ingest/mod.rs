@@ -0,0 +1,5 @@
+pub mod config;
+pub mod filter;
+pub mod pipeline;
+pub mod sink;
+pub mod source;
@@ -0,0 +1,340 @@
+use helius::types::TransactionNotification;
+use log::{error, info, warn};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::dead_letter::{DeadLetter, DeadLetterReason, DeadLetterSink};
+use crate::dedup::{DedupOutcome, Deduplicator};
+use crate::ingest::config::PipelineConfig;
+use crate::ingest::filter::FilterConfig;
+use crate::ingest::sink::{build_sink, EventSink, SinkEvent};
+use crate::ingest::source::{EventSource, SourceEvent};
+use crate::payload_codec::encode_payloads;
+use crate::program_decoder::{parse_transaction, DecoderRegistry};
+use crate::pumpfun_instruction_parser::{
+    undecodable_instructions, ParseOptions, PayloadEnvelope, WrapPayload,
+};
+use crate::spl_token_parser::SPL_TOKEN_PROGRAM_ID;
+
+/// Reads notifications from one source, decodes them, and fans every event kept by the filters
+/// out to all sinks
+pub struct Pipeline {
+    program_id: String,
+    parse_options: ParseOptions,
+    decoders: DecoderRegistry,
+    source: EventSource,
+    filters: Vec<FilterConfig>,
+    sinks: Vec<Box<dyn EventSink>>,
+    dead_letters: DeadLetterSink,
+    dedup: Option<Deduplicator>,
+    decoded_upstream: bool, // a producer decoded the payloads read so far
+}
+
+impl Pipeline {
+    pub async fn new(config: PipelineConfig) -> anyhow::Result<Self> {
+        // fail early on a malformed program id
+        Pubkey::from_str(&config.program_id)?;
+        let decoders = DecoderRegistry::from_names(&config.decoders)?;
+        let mut sinks = vec![];
+        for sink in &config.sinks {
+            sinks.push(build_sink(sink).await?);
+        }
+        // token transfers are only decoded in the transactions of the other programs, subscribing
+        // to every transfer would flood the source
+        let mut program_ids = vec![config.program_id.clone()];
+        program_ids.extend(
+            decoders
+                .program_ids()
+                .filter(|program_id| *program_id != SPL_TOKEN_PROGRAM_ID)
+                .map(str::to_string),
+        );
+        let source = EventSource::connect(&config.source, &program_ids).await?;
+        let dead_letters = match &config.dead_letter {
+            Some(dead_letter) => DeadLetterSink::connect(dead_letter).await?,
+            None => DeadLetterSink::Log,
+        };
+        let dedup = match &config.dedup {
+            Some(dedup) => Some(Deduplicator::connect(dedup).await?),
+            None => None,
+        };
+
+        Ok(Self {
+            program_id: config.program_id,
+            parse_options: ParseOptions {
+                capture_failed: config.capture_failed,
+            },
+            decoders,
+            source,
+            filters: config.filters,
+            sinks,
+            dead_letters,
+            dedup,
+            decoded_upstream: false,
+        })
+    }
+
+    /// Runs until the source is exhausted or Ctrl-C, then closes every sink.
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        let mut last_report = Instant::now();
+        loop {
+            let next = tokio::select! {
+                next = self.source.next() => next?,
+                _ = tokio::signal::ctrl_c() => None,
+            };
+            match next {
+                Some(SourceEvent::Notification(notification, envelope)) => {
+                    self.process(&notification, &envelope).await
+                }
+                Some(SourceEvent::Payloads(payloads)) => self.process_payloads(payloads).await,
+                Some(SourceEvent::Idle) => self.tick().await,
+                Some(SourceEvent::Malformed { payload, error }) => {
+                    self.dead_letter(
+                        DeadLetterReason::Deserialize,
+                        error,
+                        self.source.name(),
+                        &payload,
+                    )
+                    .await;
+                }
+                None => break,
+            }
+            self.dead_letter_failed_writes().await;
+            // a crash then loses nothing the sinks still buffer
+            if self.sinks.iter().all(|sink| sink.is_flushed()) {
+                self.source.ack().await?;
+            }
+
+            if let Some(dedup) = &self.dedup {
+                if last_report.elapsed() >= Duration::from_secs(60) {
+                    dedup.log_stats();
+                    last_report = Instant::now();
+                }
+            }
+        }
+
+        if let Some(dedup) = &self.dedup {
+            dedup.log_stats();
+        }
+
+        info!("Closing sinks");
+        let mut closed = true;
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.close().await {
+                error!("Failed to close sink {}: {:?}", sink.name(), e);
+                closed = false;
+            }
+        }
+        self.dead_letter_failed_writes().await;
+        // otherwise redelivered on the next run
+        if closed {
+            self.source.ack().await?;
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        notification: &TransactionNotification,
+        envelope: &PayloadEnvelope,
+    ) {
+        let payloads = parse_transaction(
+            notification,
+            &self.program_id,
+            &self.decoders,
+            &self.parse_options,
+        )
+        .map(|transaction| transaction.into_payloads(envelope))
+        .unwrap_or_default();
+        let upstream = envelope
+            .upstream
+            .clone()
+            .unwrap_or_else(|| self.source.name());
+        let Some(payloads) = self
+            .drop_duplicates(
+                &notification.signature,
+                payloads,
+                &upstream,
+                envelope.received_at,
+            )
+            .await
+        else {
+            return;
+        };
+
+        // once per transaction, not per upstream delivering it
+        let undecodable = undecodable_instructions(notification, &self.program_id);
+        if !undecodable.is_empty() {
+            let message = serde_json::to_vec(notification).unwrap_or_default();
+            self.dead_letter(
+                DeadLetterReason::Decode,
+                undecodable.join("; "),
+                self.source.name(),
+                &message,
+            )
+            .await;
+        }
+
+        let payloads: Vec<WrapPayload> = payloads
+            .into_iter()
+            .filter(|payload| self.filters.iter().all(|filter| filter.matches(payload)))
+            .collect();
+
+        // without filters every notification is kept, with filters those with a kept payload
+        if self.filters.is_empty() || !payloads.is_empty() {
+            self.write(SinkEvent::Notification(notification, envelope))
+                .await;
+        }
+        for payload in &payloads {
+            self.write(SinkEvent::Payload(payload)).await;
+        }
+    }
+
+    // payloads decoded by a producer have no notification to write
+    async fn process_payloads(&mut self, payloads: Vec<WrapPayload>) {
+        if !self.decoded_upstream {
+            self.decoded_upstream = true;
+            if self.parse_options.capture_failed {
+                warn!(
+                    "{} delivers payloads decoded by the producer, --capture-failed only applies \
+                     there",
+                    self.source.name()
+                );
+            }
+        }
+        // the producer received them, and stamped the envelope
+        let first = payloads.first().and_then(|payload| {
+            Some((
+                payload.signature()?.to_string(),
+                payload.envelope()?.clone(),
+            ))
+        });
+        let payloads = match first {
+            Some((signature, envelope)) => {
+                let upstream = envelope.upstream.unwrap_or_else(|| self.source.name());
+                match self
+                    .drop_duplicates(&signature, payloads, &upstream, envelope.received_at)
+                    .await
+                {
+                    Some(payloads) => payloads,
+                    None => return,
+                }
+            }
+            None => payloads,
+        };
+        let kept: Vec<&WrapPayload> = payloads
+            .iter()
+            .filter(|payload| self.filters.iter().all(|filter| filter.matches(payload)))
+            .collect();
+
+        // the message as a whole, like a notification
+        if self.filters.is_empty() || !kept.is_empty() {
+            self.write(SinkEvent::Payloads(&payloads)).await;
+        }
+        for payload in kept {
+            self.write(SinkEvent::Payload(payload)).await;
+        }
+    }
+
+    // Drops the payloads another upstream delivered first, keyed on the instruction they were
+    // decoded from. None if the whole transaction was a copy. Without payloads the transaction is
+    // keyed on its signature.
+    async fn drop_duplicates(
+        &mut self,
+        signature: &str,
+        payloads: Vec<WrapPayload>,
+        upstream: &str,
+        received_at: i64,
+    ) -> Option<Vec<WrapPayload>> {
+        let Some(dedup) = self.dedup.as_mut() else {
+            return Some(payloads);
+        };
+        if payloads.is_empty() {
+            return match dedup.check(signature, None, upstream, received_at).await {
+                DedupOutcome::First => Some(payloads),
+                DedupOutcome::Duplicate(_) => None,
+            };
+        }
+        let instructions: Vec<_> = payloads.iter().map(WrapPayload::instruction).collect();
+        let outcomes = dedup
+            .check_all(signature, &instructions, upstream, received_at)
+            .await;
+        let first_copies: Vec<WrapPayload> = payloads
+            .into_iter()
+            .zip(outcomes)
+            .filter(|(_, outcome)| *outcome == DedupOutcome::First)
+            .map(|(payload, _)| payload)
+            .collect();
+        (!first_copies.is_empty()).then_some(first_copies)
+    }
+
+    // an idle stream is still flushed
+    async fn tick(&mut self) {
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.tick().await {
+                error!("Failed to flush sink {}: {:?}", sink.name(), e);
+            }
+        }
+    }
+
+    // a failing sink is dead-lettered, it does not hold back the others
+    async fn write(&mut self, event: SinkEvent<'_>) {
+        let mut failed = vec![];
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.write(event).await {
+                failed.push((sink.name().to_string(), e));
+            }
+        }
+        if failed.is_empty() {
+            return;
+        }
+        // the event as a queue message, to be resubmitted to a redis source
+        let message = match event {
+            SinkEvent::Notification(notification, _) => {
+                serde_json::to_vec(notification).map_err(anyhow::Error::from)
+            }
+            SinkEvent::Payloads(payloads) => encode_payloads(payloads),
+            SinkEvent::Payload(payload) => encode_payloads(std::slice::from_ref(payload)),
+        }
+        .unwrap_or_default();
+        for (name, e) in failed {
+            self.dead_letter_sink(name, format!("{:?}", e), &message)
+                .await;
+        }
+    }
+
+    // events a sink accepted, then failed to write, e.g. undelivered to kafka
+    async fn dead_letter_failed_writes(&mut self) {
+        let mut failed = vec![];
+        for sink in &mut self.sinks {
+            for failed_write in sink.take_failed() {
+                failed.push((sink.name().to_string(), failed_write));
+            }
+        }
+        for (name, failed_write) in failed {
+            self.dead_letter_sink(name, failed_write.error, &failed_write.message)
+                .await;
+        }
+    }
+
+    // replayed only to the sink that failed, the others wrote the event
+    async fn dead_letter_sink(&mut self, sink: String, error: String, message: &[u8]) {
+        let origin = self.source.name();
+        let letter = DeadLetter {
+            sink: Some(sink),
+            ..DeadLetter::from_message(DeadLetterReason::Sink, error, &origin, message)
+        };
+        self.dead_letters.send(letter).await;
+    }
+
+    async fn dead_letter(
+        &mut self,
+        reason: DeadLetterReason,
+        error: String,
+        origin: String,
+        message: &[u8],
+    ) {
+        let letter = DeadLetter::from_message(reason, error, &origin, message);
+        self.dead_letters.send(letter).await;
+    }
+}
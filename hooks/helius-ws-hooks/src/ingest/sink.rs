@@ -0,0 +1,483 @@
+use async_trait::async_trait;
+use helius::types::TransactionNotification;
+use log::info;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use crate::batch_csv_writer::{
+    BatchCsvRecord, BatchCsvWriter, BatchCsvWriterOptions, CsvCompression,
+};
+use crate::batch_parquet_writer::BatchParquetWriter;
+use crate::kafka_producer::{PayloadProducer, TopicRouting};
+use crate::payload_codec::encode_payloads;
+use crate::pumpfun_instruction_parser::{PayloadEnvelope, WrapPayload};
+use crate::recording::NotificationRecorder;
+use crate::redis_queue::{RedisProducer, RedisTransport};
+
+/// What a pipeline hands to its sinks: every message that passed the filters, a notification or
+/// the payloads a producer decoded from one, then each of its payloads that passed them
+#[derive(Debug, Clone, Copy)]
+pub enum SinkEvent<'a> {
+    Notification(&'a TransactionNotification, &'a PayloadEnvelope),
+    Payloads(&'a [WrapPayload]),
+    Payload(&'a WrapPayload),
+}
+
+/// Which events a message sink writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkFormat {
+    /// The decoded payloads, as json
+    #[default]
+    Payload,
+    /// The raw notifications, as json, for a consumer that parses them itself
+    Notification,
+    /// The decoded payloads, one per message in the binary format of `payload_codec`
+    Binary,
+}
+
+impl SinkFormat {
+    // the message of an event, None for events of another format
+    fn serialize(&self, event: SinkEvent) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(match (self, event) {
+            (SinkFormat::Payload, SinkEvent::Payload(payload)) => {
+                Some(serde_json::to_vec(payload)?)
+            }
+            (SinkFormat::Notification, SinkEvent::Notification(notification, _)) => {
+                Some(serde_json::to_vec(notification)?)
+            }
+            (SinkFormat::Binary, SinkEvent::Payload(payload)) => {
+                Some(encode_payloads(std::slice::from_ref(payload))?)
+            }
+            _ => None,
+        })
+    }
+
+    // a message this format serialized, as the queue message a redis source reads back
+    fn queue_message(&self, message: Vec<u8>) -> Vec<u8> {
+        match self {
+            SinkFormat::Payload => serde_json::from_slice::<WrapPayload>(&message)
+                .ok()
+                .and_then(|payload| encode_payloads(&[payload]).ok())
+                .unwrap_or(message),
+            SinkFormat::Notification | SinkFormat::Binary => message,
+        }
+    }
+}
+
+/// An event a sink accepted and then failed to write, e.g. an undelivered kafka send
+#[derive(Debug)]
+pub struct FailedWrite {
+    pub message: Vec<u8>, // the event as a queue message, for the dead letters
+    pub error: String,
+}
+
+#[async_trait]
+pub trait EventSink: Send {
+    /// Names the sink in logs and dead letters, unique within a pipeline, see `SinkConfig::name`
+    fn name(&self) -> &str;
+
+    async fn write(&mut self, event: SinkEvent<'_>) -> anyhow::Result<()>;
+
+    /// Flush or rotate what is due by time. Called every second, also while no event arrives.
+    async fn tick(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Whether every event written so far is durable. The source acks its messages only then.
+    fn is_flushed(&self) -> bool {
+        true
+    }
+
+    /// The events that failed since the last call, after `write` accepted them. Taken after
+    /// every event and tick, and dead-lettered before the source acks.
+    fn take_failed(&mut self) -> Vec<FailedWrite> {
+        vec![]
+    }
+
+    /// Write out anything buffered. Called once, when the pipeline stops.
+    async fn close(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// A sink of a pipeline, `type` picks the sink
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Csv {
+        dir: String,
+        #[serde(default)]
+        compression: CsvCompression,
+        max_file_mb: Option<u64>,
+    },
+    Parquet {
+        dir: String,
+        #[serde(default = "default_max_file_secs")]
+        max_file_secs: u64, // what a file holds is acked once it is closed
+    },
+    Kafka {
+        brokers: String,
+        topic: String, // or the topic prefix with `routing = "action"`
+        #[serde(default)]
+        format: SinkFormat,
+        #[serde(default)]
+        routing: TopicRouting,
+        #[serde(default = "default_max_in_flight")]
+        max_in_flight: usize,
+    },
+    Redis {
+        redis_url: String,
+        queue: String,
+        #[serde(default)]
+        transport: RedisTransport,
+        #[serde(default = "default_max_len")]
+        max_len: usize,
+        #[serde(default)]
+        format: SinkFormat,
+    },
+    /// The raw notifications, as zstd compressed json lines segments, for `notification_replay`
+    Recording {
+        dir: String,
+        #[serde(default = "default_max_segment_minutes")]
+        max_segment_minutes: u64,
+    },
+    /// One json line per event
+    Stdout {
+        #[serde(default)]
+        format: SinkFormat,
+    },
+}
+
+fn default_max_len() -> usize {
+    1_000_000
+}
+
+fn default_max_segment_minutes() -> u64 {
+    60
+}
+
+fn default_max_file_secs() -> u64 {
+    60
+}
+
+fn default_max_in_flight() -> usize {
+    1000
+}
+
+impl SinkConfig {
+    /// The type and where it writes, e.g. `redis:events`
+    pub fn name(&self) -> String {
+        match self {
+            SinkConfig::Csv { dir, .. } => format!("csv:{}", dir),
+            SinkConfig::Parquet { dir, .. } => format!("parquet:{}", dir),
+            SinkConfig::Kafka { topic, .. } => format!("kafka:{}", topic),
+            SinkConfig::Redis { queue, .. } => format!("redis:{}", queue),
+            SinkConfig::Recording { dir, .. } => format!("recording:{}", dir),
+            SinkConfig::Stdout { .. } => "stdout".to_string(),
+        }
+    }
+}
+
+pub async fn build_sink(config: &SinkConfig) -> anyhow::Result<Box<dyn EventSink>> {
+    let name = config.name();
+    Ok(match config {
+        SinkConfig::Csv {
+            dir,
+            compression,
+            max_file_mb,
+        } => {
+            let options = BatchCsvWriterOptions {
+                max_file_bytes: max_file_mb.map(|mb| mb * 1024 * 1024),
+                compression: *compression,
+                ..Default::default()
+            };
+            Box::new(CsvSink {
+                name,
+                writer: Some(BatchCsvWriter::with_options(dir.clone(), options)?),
+            })
+        }
+        SinkConfig::Parquet { dir, max_file_secs } => Box::new(ParquetSink {
+            name,
+            writer: Some(BatchParquetWriter::new(dir.clone(), 1000, *max_file_secs)?),
+        }),
+        SinkConfig::Kafka {
+            brokers,
+            topic,
+            format,
+            routing,
+            max_in_flight,
+        } => Box::new(KafkaSink {
+            name,
+            producer: PayloadProducer::new(brokers, topic.clone(), *routing, *max_in_flight)?,
+            format: *format,
+            last_report: Instant::now(),
+            last_flush: Instant::now(),
+        }),
+        SinkConfig::Redis {
+            redis_url,
+            queue,
+            transport,
+            max_len,
+            format,
+        } => {
+            let redis_client = redis::Client::open(redis_url.as_str())?;
+            let redis_conn = redis_client.get_multiplexed_async_connection().await?;
+            Box::new(RedisSink {
+                name,
+                producer: RedisProducer::new(redis_conn, queue.clone(), *transport, *max_len),
+                format: *format,
+            })
+        }
+        SinkConfig::Recording {
+            dir,
+            max_segment_minutes,
+        } => Box::new(RecordingSink {
+            name,
+            recorder: Some(NotificationRecorder::new(
+                dir.clone(),
+                Duration::from_secs(max_segment_minutes * 60),
+            )?),
+        }),
+        SinkConfig::Stdout {
+            format: SinkFormat::Binary,
+        } => anyhow::bail!("the stdout sink writes json only"),
+        SinkConfig::Stdout { format } => Box::new(StdoutSink {
+            name,
+            format: *format,
+        }),
+    })
+}
+
+/// Payloads as `BatchCsvRecord`s
+pub struct CsvSink {
+    name: String,
+    writer: Option<BatchCsvWriter>,
+}
+
+#[async_trait]
+impl EventSink for CsvSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn write(&mut self, event: SinkEvent<'_>) -> anyhow::Result<()> {
+        if let (SinkEvent::Payload(payload), Some(writer)) = (event, self.writer.as_mut()) {
+            let record: BatchCsvRecord = payload.clone().into();
+            writer.write(record)?;
+        }
+        Ok(())
+    }
+
+    async fn tick(&mut self) -> anyhow::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.tick(),
+            None => Ok(()),
+        }
+    }
+
+    fn is_flushed(&self) -> bool {
+        self.writer
+            .as_ref()
+            .is_none_or(|writer| writer.current_count() == 0)
+    }
+
+    async fn close(&mut self) -> anyhow::Result<()> {
+        match self.writer.take() {
+            Some(writer) => writer.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Payloads as `BatchCsvRecord` rows
+pub struct ParquetSink {
+    name: String,
+    writer: Option<BatchParquetWriter>,
+}
+
+#[async_trait]
+impl EventSink for ParquetSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn write(&mut self, event: SinkEvent<'_>) -> anyhow::Result<()> {
+        if let (SinkEvent::Payload(payload), Some(writer)) = (event, self.writer.as_mut()) {
+            writer.write(payload.clone().into())?;
+        }
+        Ok(())
+    }
+
+    async fn tick(&mut self) -> anyhow::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.tick(),
+            None => Ok(()),
+        }
+    }
+
+    // a file without its footer is unreadable, its rows are durable once it is closed
+    fn is_flushed(&self) -> bool {
+        self.writer
+            .as_ref()
+            .is_none_or(|writer| writer.unclosed_count() == 0)
+    }
+
+    async fn close(&mut self) -> anyhow::Result<()> {
+        match self.writer.take() {
+            Some(writer) => writer.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Events produced without waiting for each delivery, payloads routed and keyed by
+/// `PayloadProducer`. The pending deliveries are awaited every second, so the source can ack.
+pub struct KafkaSink {
+    name: String,
+    producer: PayloadProducer,
+    format: SinkFormat,
+    last_report: Instant,
+    last_flush: Instant,
+}
+
+#[async_trait]
+impl EventSink for KafkaSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn write(&mut self, event: SinkEvent<'_>) -> anyhow::Result<()> {
+        let Some(message) = self.format.serialize(event)? else {
+            return Ok(());
+        };
+        match event {
+            SinkEvent::Notification(notification, _) => {
+                self.producer
+                    .send_notification(notification, &message)
+                    .await?
+            }
+            SinkEvent::Payload(payload) => self.producer.send_message(payload, &message).await?,
+            SinkEvent::Payloads(_) => {}
+        }
+        if self.last_flush.elapsed() >= Duration::from_secs(1) {
+            self.flush().await;
+        }
+        Ok(())
+    }
+
+    async fn tick(&mut self) -> anyhow::Result<()> {
+        self.flush().await;
+        if self.last_report.elapsed() >= Duration::from_secs(60) {
+            info!("Kafka deliveries: {:?}", self.producer.stats());
+            self.last_report = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn is_flushed(&self) -> bool {
+        self.producer.in_flight() == 0
+    }
+
+    fn take_failed(&mut self) -> Vec<FailedWrite> {
+        self.producer
+            .take_failed()
+            .into_iter()
+            .map(|failed| FailedWrite {
+                message: self.format.queue_message(failed.message),
+                error: format!("{:?}", failed.error),
+            })
+            .collect()
+    }
+
+    async fn close(&mut self) -> anyhow::Result<()> {
+        info!("Kafka deliveries: {:?}", self.producer.flush().await);
+        Ok(())
+    }
+}
+
+impl KafkaSink {
+    // waits for the pending deliveries, each is bounded by the message timeout
+    async fn flush(&mut self) {
+        self.producer.flush().await;
+        self.last_flush = Instant::now();
+    }
+}
+
+pub struct RedisSink {
+    name: String,
+    producer: RedisProducer,
+    format: SinkFormat,
+}
+
+#[async_trait]
+impl EventSink for RedisSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn write(&mut self, event: SinkEvent<'_>) -> anyhow::Result<()> {
+        if let Some(message) = self.format.serialize(event)? {
+            self.producer.push_bytes(&message).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Notifications as `RecordedNotification`s, or the payloads a producer decoded as
+/// `RecordedPayloads`, with when they were received
+pub struct RecordingSink {
+    name: String,
+    recorder: Option<NotificationRecorder>,
+}
+
+#[async_trait]
+impl EventSink for RecordingSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn write(&mut self, event: SinkEvent<'_>) -> anyhow::Result<()> {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return Ok(());
+        };
+        match event {
+            SinkEvent::Notification(notification, envelope) => {
+                recorder.record(notification, envelope.received_at)
+            }
+            SinkEvent::Payloads(payloads) => {
+                match payloads.iter().find_map(WrapPayload::envelope) {
+                    Some(envelope) => recorder.record_payloads(payloads, envelope.received_at),
+                    None => Ok(()),
+                }
+            }
+            SinkEvent::Payload(_) => Ok(()),
+        }
+    }
+
+    async fn close(&mut self) -> anyhow::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct StdoutSink {
+    name: String,
+    format: SinkFormat,
+}
+
+#[async_trait]
+impl EventSink for StdoutSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn write(&mut self, event: SinkEvent<'_>) -> anyhow::Result<()> {
+        if let Some(message) = self.format.serialize(event)? {
+            println!("{}", String::from_utf8_lossy(&message));
+        }
+        Ok(())
+    }
+}
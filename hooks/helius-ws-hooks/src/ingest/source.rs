@@ -0,0 +1,289 @@
+use helius::types::enhanced_websocket::TransactionCommitment;
+use helius::types::{
+    RpcTransactionsConfig, TransactionNotification, TransactionSubscribeFilter,
+    TransactionSubscribeOptions, UiEnhancedTransactionEncoding,
+};
+use helius::websocket::{EnhancedWebsocket, StreamEvent, ENHANCED_WEBSOCKET_URL};
+use log::{error, info};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration};
+use tokio_stream::StreamExt;
+
+use crate::payload_codec::{decode_message, QueueMessage};
+use crate::pumpfun_instruction_parser::{
+    parse_encoding, PayloadEnvelope, PayloadSource, WrapPayload,
+};
+use crate::redis_queue::{default_consumer_name, RedisConsumer, RedisMessage, RedisTransport};
+use crate::ws_health::monitor_health;
+
+/// Where a pipeline reads notifications from
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceConfig {
+    /// Subscribe to the transactions of the pipeline programs, on one upstream or several
+    /// redundant ones, whose copies `[dedup]` drops
+    HeliusWs {
+        api_key: Option<String>,
+        #[serde(default)]
+        upstreams: Vec<HeliusUpstream>,
+        #[serde(default = "default_encoding")]
+        encoding: String, // jsonParsed, base58 or base64
+        commitment: Option<TransactionCommitment>,
+    },
+    /// Read notifications pushed by `enhanced_websocket_redis_producer` or a redis sink
+    Redis {
+        redis_url: String,
+        queue: String,
+        #[serde(default)]
+        transport: RedisTransport,
+        #[serde(default = "default_group")]
+        group: String,
+        consumer: Option<String>,
+    },
+}
+
+/// One enhanced websocket connection, e.g. of another region or key
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeliusUpstream {
+    pub name: String, // names the upstream in the dedup stats
+    pub api_key: String,
+    #[serde(default = "default_url")]
+    pub url: String, // the api key is appended
+}
+
+fn default_url() -> String {
+    ENHANCED_WEBSOCKET_URL.to_string()
+}
+
+fn default_encoding() -> String {
+    "jsonParsed".to_string()
+}
+
+fn default_group() -> String {
+    "ingest".to_string()
+}
+
+/// What a source reads
+pub enum SourceEvent {
+    /// A notification, its envelope names the upstream that delivered it
+    Notification(TransactionNotification, PayloadEnvelope),
+    /// Payloads a producer already decoded
+    Payloads(Vec<WrapPayload>),
+    /// A message that is neither, acked with the next `ack`
+    Malformed { payload: Vec<u8>, error: String },
+    /// Nothing arrived for `IDLE_AFTER`, time for the sinks to flush
+    Idle,
+}
+
+/// How long a source waits for an event before it reports `SourceEvent::Idle`
+pub const IDLE_AFTER: Duration = Duration::from_secs(1);
+
+pub enum EventSource {
+    HeliusWs {
+        event_rx: mpsc::Receiver<SourceEvent>,
+    },
+    Redis {
+        consumer: Box<RedisConsumer>,
+        unacked: Vec<RedisMessage>,
+    },
+}
+
+impl EventSource {
+    /// Connects to the source, a helius source subscribing to the transactions of any of
+    /// `program_ids`
+    pub async fn connect(config: &SourceConfig, program_ids: &[String]) -> anyhow::Result<Self> {
+        match config {
+            SourceConfig::HeliusWs {
+                api_key,
+                upstreams,
+                encoding,
+                commitment,
+            } => {
+                let encoding = parse_encoding(encoding).map_err(anyhow::Error::msg)?;
+                let mut upstreams = upstreams.clone();
+                if let Some(api_key) = api_key {
+                    upstreams.push(HeliusUpstream {
+                        name: "helius_ws".to_string(),
+                        api_key: api_key.clone(),
+                        url: default_url(),
+                    });
+                }
+                if upstreams.is_empty() {
+                    anyhow::bail!("a helius_ws source needs an api_key or upstreams");
+                }
+
+                let (event_tx, event_rx) = mpsc::channel(1024);
+                for upstream in upstreams {
+                    tokio::spawn(subscribe_forever(
+                        upstream,
+                        program_ids.to_vec(),
+                        encoding.clone(),
+                        commitment.clone(),
+                        event_tx.clone(),
+                    ));
+                }
+                Ok(EventSource::HeliusWs { event_rx })
+            }
+            SourceConfig::Redis {
+                redis_url,
+                queue,
+                transport,
+                group,
+                consumer,
+            } => {
+                let redis_client = redis::Client::open(redis_url.as_str())?;
+                let redis_conn = redis_client.get_multiplexed_async_connection().await?;
+                let consumer = RedisConsumer::new(
+                    redis_conn,
+                    queue.clone(),
+                    *transport,
+                    group.clone(),
+                    consumer.clone().unwrap_or_else(default_consumer_name),
+                )
+                .await?;
+                Ok(EventSource::Redis {
+                    consumer: Box::new(consumer),
+                    unacked: vec![],
+                })
+            }
+        }
+    }
+
+    /// Names the source in dead letters
+    pub fn name(&self) -> String {
+        match self {
+            EventSource::HeliusWs { .. } => "helius_ws".to_string(),
+            EventSource::Redis { consumer, .. } => format!("redis:{}", consumer.queue()),
+        }
+    }
+
+    /// The next event, `SourceEvent::Idle` while none arrives, None once the source is exhausted
+    pub async fn next(&mut self) -> anyhow::Result<Option<SourceEvent>> {
+        match self {
+            EventSource::HeliusWs { event_rx } => {
+                match tokio::time::timeout(IDLE_AFTER, event_rx.recv()).await {
+                    Ok(event) => Ok(event),
+                    Err(_elapsed) => Ok(Some(SourceEvent::Idle)),
+                }
+            }
+            EventSource::Redis { consumer, unacked } => {
+                let Some(message) = consumer.next_timeout(IDLE_AFTER).await? else {
+                    return Ok(Some(SourceEvent::Idle));
+                };
+                let event = match decode_message(&message.payload) {
+                    Ok(QueueMessage::Notification(notification)) => {
+                        let envelope = PayloadEnvelope {
+                            upstream: Some(format!("redis:{}", consumer.queue())),
+                            ..PayloadEnvelope::new(PayloadSource::Redis, None)
+                        };
+                        SourceEvent::Notification(notification, envelope)
+                    }
+                    Ok(QueueMessage::Payloads(payloads)) => SourceEvent::Payloads(payloads),
+                    // acked all the same, a message that never parses would be redelivered forever
+                    Err(e) => SourceEvent::Malformed {
+                        payload: message.payload.clone(),
+                        error: e.to_string(),
+                    },
+                };
+                unacked.push(message);
+                Ok(Some(event))
+            }
+        }
+    }
+
+    /// Ack the events read so far, once every sink holds them durably or they are dead-lettered.
+    pub async fn ack(&mut self) -> anyhow::Result<()> {
+        if let EventSource::Redis { consumer, unacked } = self {
+            consumer.ack(unacked).await?;
+            unacked.clear();
+        }
+        Ok(())
+    }
+}
+
+// Retries until subscribed, then the websocket reconnects and resubscribes by itself; until the
+// receiver is dropped. Notifications are stamped as they arrive, so the upstreams sharing the
+// channel are compared fairly.
+async fn subscribe_forever(
+    upstream: HeliusUpstream,
+    program_ids: Vec<String>,
+    encoding: UiEnhancedTransactionEncoding,
+    commitment: Option<TransactionCommitment>,
+    event_tx: mpsc::Sender<SourceEvent>,
+) {
+    let url = format!("{}{}", upstream.url, upstream.api_key);
+    let name = format!("helius_ws:{}", upstream.name);
+    loop {
+        let config = RpcTransactionsConfig {
+            filter: TransactionSubscribeFilter {
+                account_include: Some(program_ids.clone()),
+                ..Default::default()
+            },
+            options: TransactionSubscribeOptions {
+                encoding: Some(encoding.clone()),
+                commitment: commitment.clone(),
+                ..Default::default()
+            },
+        };
+        match EnhancedWebsocket::new(&url, Some(15), Some(45)).await {
+            Ok(ws) => match ws.transaction_subscribe_with_gaps(config).await {
+                Ok((mut stream, _unsub)) => {
+                    info!("Stream subscribe success on {}", name);
+                    tokio::spawn(monitor_health(
+                        name.clone(),
+                        ws.health(),
+                        Duration::from_secs(60),
+                        Duration::from_secs(30),
+                    ));
+                    while let Some(event) = stream.next().await {
+                        let notification = match event {
+                            StreamEvent::Notification(notification) => notification,
+                            StreamEvent::Gap(gap) => {
+                                error!(
+                                    "Stream on {} resubscribed, notifications since {} may be missing: {}",
+                                    name, gap.disconnected_at, gap.reason
+                                );
+                                continue;
+                            }
+                            // dead lettered by the pipeline
+                            StreamEvent::ParseError(parse_error) => {
+                                let event = SourceEvent::Malformed {
+                                    payload: parse_error.value.to_string().into_bytes(),
+                                    error: parse_error.error,
+                                };
+                                if event_tx.send(event).await.is_err() {
+                                    return;
+                                }
+                                continue;
+                            }
+                            // the stream ends next, then we connect and subscribe again
+                            StreamEvent::Closed(closed) => {
+                                error!(
+                                    "Stream on {} closed, disconnected at {}: {}",
+                                    name, closed.disconnected_at, closed.reason
+                                );
+                                continue;
+                            }
+                        };
+                        let envelope = PayloadEnvelope {
+                            upstream: Some(name.clone()),
+                            ..PayloadEnvelope::new(PayloadSource::HeliusWs, commitment.clone())
+                        };
+                        let event = SourceEvent::Notification(notification, envelope);
+                        if event_tx.send(event).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to subscribe on {}: {:?}", name, e),
+            },
+            Err(e) => error!("Failed to connect to {}: {:?}", name, e),
+        }
+        info!(
+            "Stream on {} has been exhausted, attempting to reconnect...",
+            name
+        );
+        sleep(Duration::from_secs(1)).await;
+    }
+}
@@ -0,0 +1,103 @@
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::Message;
+use rdkafka::{Offset, TopicPartitionList};
+use std::time::Duration;
+
+/// A message read from kafka, to be acked once handled
+#[derive(Debug, Clone)]
+pub struct KafkaMessage {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    pub timestamp: Option<i64>, // create time, milliseconds
+    pub payload: String,
+}
+
+/// Reads the payloads of `enhanced_websocket_kafka` within a consumer group. Offsets are stored
+/// only by `ack`, and committed by the next auto commit, so a message that is never acked is read
+/// again after a restart. A replay from `start_time` commits nothing, leaving the offsets of the
+/// group where the live consumer left them.
+pub struct PayloadConsumer {
+    consumer: StreamConsumer,
+    replaying: bool,
+}
+
+impl PayloadConsumer {
+    /// Subscribes to `topics`, from the committed offsets of the group, or from `start_time`
+    /// (unix milliseconds) on every partition if set. A group without offsets starts from the
+    /// latest message, never from the history of the topic.
+    pub fn new(
+        brokers: &str,
+        group: &str,
+        topics: &[String],
+        start_time: Option<i64>,
+    ) -> anyhow::Result<Self> {
+        let replaying = start_time.is_some();
+        let offset_reset = if replaying { "earliest" } else { "latest" };
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("group.id", group)
+            .set("enable.auto.commit", (!replaying).to_string())
+            .set("enable.auto.offset.store", "false")
+            .set("auto.offset.reset", offset_reset)
+            .create()?;
+
+        match start_time {
+            None => {
+                let topics: Vec<&str> = topics.iter().map(|topic| topic.as_str()).collect();
+                consumer.subscribe(&topics)?;
+            }
+            Some(start_time) => {
+                // partitions are assigned, not balanced across the group, to seek them all
+                let mut timestamps = TopicPartitionList::new();
+                for topic in topics {
+                    let metadata = consumer.fetch_metadata(Some(topic), Duration::from_secs(10))?;
+                    for metadata_topic in metadata.topics() {
+                        for partition in metadata_topic.partitions() {
+                            timestamps.add_partition_offset(
+                                topic,
+                                partition.id(),
+                                Offset::Offset(start_time),
+                            )?;
+                        }
+                    }
+                }
+                let offsets = consumer.offsets_for_times(timestamps, Duration::from_secs(10))?;
+                consumer.assign(&offsets)?;
+            }
+        }
+        Ok(Self {
+            consumer,
+            replaying,
+        })
+    }
+
+    /// Waits for the next message with a payload, skipping tombstones
+    pub async fn next(&mut self) -> anyhow::Result<KafkaMessage> {
+        loop {
+            let message = self.consumer.recv().await?;
+            let Some(payload) = message.payload_view::<str>() else {
+                continue;
+            };
+            return Ok(KafkaMessage {
+                topic: message.topic().to_string(),
+                partition: message.partition(),
+                offset: message.offset(),
+                timestamp: message.timestamp().to_millis(),
+                payload: payload?.to_string(),
+            });
+        }
+    }
+
+    /// Marks the message handled, its offset is committed with the next auto commit. Nothing is
+    /// stored while replaying.
+    pub fn ack(&self, message: &KafkaMessage) -> anyhow::Result<()> {
+        if self.replaying {
+            return Ok(());
+        }
+        self.consumer
+            .store_offset(&message.topic, message.partition, message.offset)?;
+        Ok(())
+    }
+}
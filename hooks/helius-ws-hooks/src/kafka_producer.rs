@@ -0,0 +1,310 @@
+use helius::types::TransactionNotification;
+use log::error;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::KafkaError;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::types::RDKafkaErrorCode;
+use serde::Deserialize;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::task::{JoinError, JoinSet};
+use tokio::time::sleep;
+
+use crate::pumpfun_instruction_parser::WrapPayload;
+
+/// Which topic a payload is produced to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TopicRouting {
+    /// Every payload to the topic
+    #[default]
+    Single,
+    /// `<topic>-<action>`, e.g. `pumpfun-create`, `pumpfun-buy` and `pumpfun-sell`
+    Action,
+}
+
+impl FromStr for TopicRouting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single" => Ok(TopicRouting::Single),
+            "action" => Ok(TopicRouting::Action),
+            _ => Err(format!("unknown routing {}, expected single or action", s)),
+        }
+    }
+}
+
+/// Delivery outcomes so far, a send counts once kafka acks or rejects it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveryStats {
+    pub delivered: u64,
+    pub failed: u64,
+    pub in_flight: usize,
+}
+
+/// A send that kafka rejected or timed out after it was queued
+#[derive(Debug)]
+pub struct FailedDelivery {
+    pub error: KafkaError,
+    pub message: Vec<u8>, // as sent
+}
+
+/// Produces payloads as json, keyed by mint, without waiting for each delivery. At most
+/// `max_in_flight` sends are pending at once. A send that fails once queued is kept, with its
+/// message, until `take_failed`.
+pub struct PayloadProducer {
+    producer: FutureProducer,
+    topic: String,
+    routing: TopicRouting,
+    max_in_flight: usize,
+    in_flight: JoinSet<Result<(), FailedDelivery>>,
+    delivered: u64,
+    failed: u64,
+    failed_deliveries: Vec<FailedDelivery>,
+}
+
+impl PayloadProducer {
+    pub fn new(
+        brokers: &str,
+        topic: String,
+        routing: TopicRouting,
+        max_in_flight: usize,
+    ) -> anyhow::Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("message.timeout.ms", "5000")
+            // retries must not reorder the messages of a partition
+            .set("enable.idempotence", "true")
+            .create()?;
+        Ok(Self {
+            producer,
+            topic,
+            routing,
+            max_in_flight: max_in_flight.max(1),
+            in_flight: JoinSet::new(),
+            delivered: 0,
+            failed: 0,
+            failed_deliveries: vec![],
+        })
+    }
+
+    pub fn topic_for(&self, payload: &WrapPayload) -> String {
+        match self.routing {
+            TopicRouting::Single => self.topic.clone(),
+            TopicRouting::Action => format!("{}-{}", self.topic, payload.action()),
+        }
+    }
+
+    /// Queues the payload as json, waiting first for a pending send if `max_in_flight` are
+    /// pending. Sends are queued in order, so the payloads of a mint keep their order on its
+    /// partition.
+    pub async fn send(&mut self, payload: &WrapPayload) -> anyhow::Result<()> {
+        let message = serde_json::to_vec(payload)?;
+        self.send_message(payload, &message).await
+    }
+
+    /// Queues `message`, an encoding of `payload`, routed and keyed like `send`
+    pub async fn send_message(
+        &mut self,
+        payload: &WrapPayload,
+        message: &[u8],
+    ) -> anyhow::Result<()> {
+        let topic = self.topic_for(payload);
+        let record = FutureRecord::to(&topic)
+            .payload(message)
+            .key(message_key(payload))
+            .headers(payload_headers(payload));
+        self.produce(record).await
+    }
+
+    /// Queues `message`, an encoding of `notification`, to the topic, keyed by signature
+    pub async fn send_notification(
+        &mut self,
+        notification: &TransactionNotification,
+        message: &[u8],
+    ) -> anyhow::Result<()> {
+        let topic = self.topic.clone();
+        let record = FutureRecord::to(&topic)
+            .payload(message)
+            .key(notification.signature.as_str());
+        self.produce(record).await
+    }
+
+    async fn produce(&mut self, mut record: FutureRecord<'_, str, [u8]>) -> anyhow::Result<()> {
+        self.collect_delivered();
+        let message = record.payload.map(<[u8]>::to_vec).unwrap_or_default();
+        while self.in_flight.len() >= self.max_in_flight {
+            self.join_next().await;
+        }
+
+        let delivery = loop {
+            match self.producer.send_result(record) {
+                Ok(delivery) => break delivery,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                    record = returned;
+                    if self.in_flight.is_empty() {
+                        sleep(Duration::from_millis(100)).await;
+                    } else {
+                        self.join_next().await;
+                    }
+                }
+                Err((e, _record)) => {
+                    self.failed += 1;
+                    return Err(e.into());
+                }
+            }
+        };
+        self.in_flight.spawn(async move {
+            let error = match delivery.await {
+                Ok(Ok(_partition_offset)) => return Ok(()),
+                Ok(Err((e, _message))) => e,
+                Err(_canceled) => KafkaError::Canceled,
+            };
+            Err(FailedDelivery { error, message })
+        });
+        Ok(())
+    }
+
+    /// Waits until every queued payload is delivered or has failed
+    pub async fn flush(&mut self) -> DeliveryStats {
+        while !self.in_flight.is_empty() {
+            self.join_next().await;
+        }
+        self.stats()
+    }
+
+    /// Sends queued and not yet delivered or failed, some may be done since the last send
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// The sends that failed once queued, since the last call
+    pub fn take_failed(&mut self) -> Vec<FailedDelivery> {
+        self.collect_delivered();
+        std::mem::take(&mut self.failed_deliveries)
+    }
+
+    /// The outcomes up to now, counting the deliveries done since the last send
+    pub fn stats(&mut self) -> DeliveryStats {
+        self.collect_delivered();
+        DeliveryStats {
+            delivered: self.delivered,
+            failed: self.failed,
+            in_flight: self.in_flight.len(),
+        }
+    }
+
+    async fn join_next(&mut self) {
+        if let Some(result) = self.in_flight.join_next().await {
+            self.count(result);
+        }
+    }
+
+    // the deliveries already done, without waiting for the others
+    fn collect_delivered(&mut self) {
+        while let Some(result) = self.in_flight.try_join_next() {
+            self.count(result);
+        }
+    }
+
+    fn count(&mut self, result: Result<Result<(), FailedDelivery>, JoinError>) {
+        match result {
+            Ok(Ok(())) => self.delivered += 1,
+            Ok(Err(failed)) => {
+                self.failed += 1;
+                error!("Failed to deliver payload: {:?}", failed.error);
+                self.failed_deliveries.push(failed);
+            }
+            Err(e) => {
+                self.failed += 1;
+                error!("Delivery task failed: {:?}", e);
+            }
+        }
+    }
+}
+
+/// The mint, so the events of a token stay in order on one partition. The signature for payloads
+/// without a mint.
+pub fn message_key(payload: &WrapPayload) -> &str {
+    payload
+        .mint_pk()
+        .or_else(|| payload.signature())
+        .unwrap_or_default()
+}
+
+pub fn payload_headers(payload: &WrapPayload) -> OwnedHeaders {
+    let slot = payload.slot().map(|slot| slot.to_string());
+    OwnedHeaders::new()
+        .insert(Header {
+            key: "slot",
+            value: slot.as_deref(),
+        })
+        .insert(Header {
+            key: "signature",
+            value: payload.signature(),
+        })
+        .insert(Header {
+            key: "action",
+            value: Some(payload.action()),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pumpfun_fee_parser::PayloadFees;
+    use crate::pumpfun_instruction_parser::{
+        InstructionIndex, PayloadEnvelope, PayloadSell, PayloadSource,
+    };
+    use rdkafka::message::Headers;
+
+    fn sell() -> WrapPayload {
+        WrapPayload::Sell(
+            PayloadSell {
+                slot: 320000000,
+                signature: "sig".to_string(),
+                instruction: InstructionIndex::default(),
+                mint_pk: "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump".to_string(),
+                user_pk: "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47".to_string(),
+                amount: 1000,
+                min_sol_output: 10,
+                router: None,
+                trade: None,
+                balance: None,
+                fees: PayloadFees::default(),
+            },
+            PayloadEnvelope::new(PayloadSource::HeliusWs, None),
+        )
+    }
+
+    #[test]
+    fn test_message_key_and_headers() {
+        let payload = sell();
+        assert_eq!(
+            "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump",
+            message_key(&payload)
+        );
+
+        let headers = payload_headers(&payload);
+        assert_eq!(3, headers.count());
+        let header = headers.get(0);
+        assert_eq!("slot", header.key);
+        assert_eq!(Some("320000000".as_bytes()), header.value);
+        assert_eq!(Some("sell".as_bytes()), headers.get(2).value);
+    }
+
+    #[tokio::test]
+    async fn test_topic_routing() {
+        let producer = PayloadProducer::new(
+            "localhost:9092",
+            "pumpfun".to_string(),
+            TopicRouting::Action,
+            8,
+        )
+        .unwrap();
+        assert_eq!("pumpfun-sell", producer.topic_for(&sell()));
+        assert_eq!(Ok(TopicRouting::Single), "single".parse::<TopicRouting>());
+    }
+}
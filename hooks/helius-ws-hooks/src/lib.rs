@@ -1,4 +1,23 @@
 pub mod batch_csv_writer;
+pub mod batch_parquet_writer;
+pub mod bonding_curve_stream;
+pub mod dead_letter;
+pub mod dedup;
+pub mod payload_codec;
+pub mod program_decoder;
+pub mod pumpfun_amm_parser;
+pub mod pumpfun_balance_parser;
+pub mod pumpfun_event_parser;
+pub mod pumpfun_fee_parser;
 pub mod pumpfun_instruction_parser;
+pub mod raydium_amm_parser;
+pub mod recording;
+pub mod redis_queue;
+pub mod spl_token_parser;
+pub mod subscription_manager;
+pub mod ws_health;
 
+pub mod ingest;
+pub mod kafka_consumer;
+pub mod kafka_producer;
 pub mod pumpfun_trading;
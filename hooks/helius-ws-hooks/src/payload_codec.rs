@@ -0,0 +1,118 @@
+use helius::types::TransactionNotification;
+use serde::Deserialize;
+use std::str::FromStr;
+
+use crate::pumpfun_instruction_parser::WrapPayload;
+
+/// First byte of a binary queue message, bumped whenever `WrapPayload` changes shape. A json
+/// notification starts with `{` instead.
+pub const PAYLOAD_FORMAT_VERSION: u8 = 3;
+
+/// What a producer pushes to a redis queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueFormat {
+    /// The notification as json, decoded again by every consumer
+    Json,
+    /// The payloads decoded by the producer, bincode after a version byte
+    #[default]
+    Binary,
+}
+
+impl FromStr for QueueFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(QueueFormat::Json),
+            "binary" => Ok(QueueFormat::Binary),
+            _ => Err(format!(
+                "unknown queue format {}, expected json or binary",
+                s
+            )),
+        }
+    }
+}
+
+/// A queue message, in either format
+#[derive(Debug)]
+pub enum QueueMessage {
+    Notification(TransactionNotification),
+    Payloads(Vec<WrapPayload>),
+}
+
+/// The payloads of one notification as a binary queue message
+pub fn encode_payloads(payloads: &[WrapPayload]) -> anyhow::Result<Vec<u8>> {
+    let mut message = vec![PAYLOAD_FORMAT_VERSION];
+    bincode::serialize_into(&mut message, payloads)?;
+    Ok(message)
+}
+
+pub fn decode_payloads(message: &[u8]) -> anyhow::Result<Vec<WrapPayload>> {
+    match message.split_first() {
+        Some((&PAYLOAD_FORMAT_VERSION, payloads)) => Ok(bincode::deserialize(payloads)?),
+        Some((version, _)) => anyhow::bail!("unsupported payload format version {}", version),
+        None => anyhow::bail!("empty message"),
+    }
+}
+
+/// A json notification or binary payloads, told apart by the first byte that is not whitespace,
+/// which a format version never is
+pub fn decode_message(message: &[u8]) -> anyhow::Result<QueueMessage> {
+    match message.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'{') => Ok(QueueMessage::Notification(serde_json::from_slice(message)?)),
+        _ => Ok(QueueMessage::Payloads(decode_payloads(message)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pumpfun_fee_parser::PayloadFees;
+    use crate::pumpfun_instruction_parser::{
+        InstructionIndex, PayloadEnvelope, PayloadSell, PayloadSource,
+    };
+
+    #[test]
+    fn test_payload_codec() {
+        let payloads = vec![
+            WrapPayload::Sell(
+                PayloadSell {
+                    slot: 320000000,
+                    signature: "sig".to_string(),
+                    instruction: InstructionIndex::default(),
+                    mint_pk: "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump".to_string(),
+                    user_pk: "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47".to_string(),
+                    amount: 1000,
+                    min_sol_output: 10,
+                    router: None,
+                    trade: None,
+                    balance: None,
+                    fees: PayloadFees::default(),
+                },
+                PayloadEnvelope::new(PayloadSource::HeliusWs, None),
+            ),
+            WrapPayload::Unknown,
+        ];
+
+        let message = encode_payloads(&payloads).unwrap();
+        assert_eq!(PAYLOAD_FORMAT_VERSION, message[0]);
+        let json = serde_json::to_string(&payloads).unwrap();
+        assert!(message.len() < json.len());
+        match decode_message(&message).unwrap() {
+            QueueMessage::Payloads(decoded) => {
+                assert_eq!(json, serde_json::to_string(&decoded).unwrap())
+            }
+            other => panic!("expected payloads, got {:?}", other),
+        }
+
+        let mut future = message.clone();
+        future[0] = PAYLOAD_FORMAT_VERSION + 1;
+        assert!(decode_message(&future).is_err());
+        assert!(decode_message(b"{\"truncated").is_err());
+        assert!(matches!(
+            decode_message(b"\n  {\"truncated"),
+            Err(e) if e.is::<serde_json::Error>()
+        ));
+    }
+}
@@ -0,0 +1,315 @@
+use helius::types::enhanced_websocket::TransactionNotification;
+use solana_transaction_status::{UiInnerInstructions, UiInstruction};
+use std::collections::HashMap;
+
+use crate::pumpfun_amm_parser::PumpAmmDecoder;
+use crate::pumpfun_instruction_parser::{
+    instruction_program_id, outer_instructions, parse_notification_with_options, InstructionIndex,
+    ParseOptions, PayloadAction, PayloadTransaction,
+};
+use crate::raydium_amm_parser::RaydiumAmmDecoder;
+use crate::spl_token_parser::SplTokenDecoder;
+
+/// Where an instruction sits in its transaction
+#[derive(Clone, Copy)]
+pub struct DecodeContext<'a> {
+    pub slot: u64,
+    pub signature: &'a str,
+    pub instruction: InstructionIndex,
+    pub account_keys: &'a [String],
+    pub router: Option<&'a str>, // the outer program, for an instruction invoked via CPI
+}
+
+/// Decodes the instructions of one program into actions
+pub trait ProgramDecoder: Send + Sync {
+    /// The program id whose instructions this decoder understands
+    fn program_id(&self) -> &str;
+
+    /// None for instructions the decoder does not care about
+    fn decode(&self, instruction: &UiInstruction, context: &DecodeContext)
+        -> Option<PayloadAction>;
+}
+
+/// Decoders of the programs other than pump.fun, keyed by program id. pump.fun itself is decoded
+/// by `parse_notification`, with its trade events, balances and fees.
+#[derive(Default)]
+pub struct DecoderRegistry {
+    decoders: HashMap<String, Box<dyn ProgramDecoder>>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pump.fun AMM, Raydium AMM v4 and SPL Token
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(PumpAmmDecoder));
+        registry.register(Box::new(RaydiumAmmDecoder));
+        registry.register(Box::new(SplTokenDecoder));
+        registry
+    }
+
+    /// The decoders named `pump_amm`, `raydium_amm` or `spl_token`
+    pub fn from_names(names: &[String]) -> anyhow::Result<Self> {
+        let mut registry = Self::new();
+        for name in names {
+            let decoder: Box<dyn ProgramDecoder> = match name.as_str() {
+                "pump_amm" => Box::new(PumpAmmDecoder),
+                "raydium_amm" => Box::new(RaydiumAmmDecoder),
+                "spl_token" => Box::new(SplTokenDecoder),
+                _ => anyhow::bail!(
+                    "unknown decoder {}, expected pump_amm, raydium_amm or spl_token",
+                    name
+                ),
+            };
+            registry.register(decoder);
+        }
+        Ok(registry)
+    }
+
+    /// Replaces any decoder already registered for the same program id.
+    pub fn register(&mut self, decoder: Box<dyn ProgramDecoder>) {
+        self.decoders
+            .insert(decoder.program_id().to_string(), decoder);
+    }
+
+    pub fn get(&self, program_id: &str) -> Option<&dyn ProgramDecoder> {
+        self.decoders
+            .get(program_id)
+            .map(|decoder| decoder.as_ref())
+    }
+
+    pub fn program_ids(&self) -> impl Iterator<Item = &str> {
+        self.decoders.keys().map(String::as_str)
+    }
+
+    fn decode(
+        &self,
+        instruction: &UiInstruction,
+        context: &DecodeContext,
+    ) -> Option<PayloadAction> {
+        let program_id = instruction_program_id(instruction, context.account_keys)?;
+        self.get(program_id)?.decode(instruction, context)
+    }
+}
+
+/// Decode every instruction of a successful transaction whose program is registered. Instructions
+/// invoked via CPI are decoded too, with the outer program as router.
+pub fn parse_program_events(
+    notification: &TransactionNotification,
+    registry: &DecoderRegistry,
+) -> Option<PayloadTransaction> {
+    let meta = match &notification.transaction.meta {
+        Some(meta) if meta.status.is_ok() => meta,
+        _ => return None,
+    };
+    let (instructions, account_keys) =
+        outer_instructions(&notification.transaction.transaction, meta)?;
+    let inner_instructions: Option<&Vec<UiInnerInstructions>> =
+        meta.inner_instructions.as_ref().into();
+
+    let mut actions = vec![];
+    for (index, ui_instruction) in instructions.iter().enumerate() {
+        let Some(outer_program_id) = instruction_program_id(ui_instruction, &account_keys) else {
+            continue;
+        };
+        let context = DecodeContext {
+            slot: notification.slot,
+            signature: &notification.signature,
+            instruction: InstructionIndex {
+                outer: index,
+                inner: None,
+            },
+            account_keys: &account_keys,
+            router: None,
+        };
+        actions.extend(registry.decode(ui_instruction, &context));
+
+        let inner_context = DecodeContext {
+            router: Some(outer_program_id),
+            ..context
+        };
+        for inner in inner_instructions
+            .into_iter()
+            .flatten()
+            .filter(|inner| inner.index as usize == index)
+        {
+            for (inner_index, inner_instruction) in inner.instructions.iter().enumerate() {
+                // a program calling itself (e.g. anchor events) is not routed
+                let router = instruction_program_id(inner_instruction, &account_keys)
+                    .filter(|program_id| *program_id != outer_program_id)
+                    .map(|_| outer_program_id);
+                let context = DecodeContext {
+                    instruction: InstructionIndex {
+                        outer: index,
+                        inner: Some(inner_index),
+                    },
+                    router,
+                    ..inner_context
+                };
+                actions.extend(registry.decode(inner_instruction, &context));
+            }
+        }
+    }
+
+    if actions.is_empty() {
+        return None;
+    }
+
+    Some(PayloadTransaction {
+        slot: notification.slot,
+        signature: notification.signature.clone(),
+        actions,
+        error: None,
+    })
+}
+
+/// The pump.fun actions of `parse_notification_with_options`, followed by the actions of the
+/// registered programs. A failed transaction only has its pump.fun actions.
+pub fn parse_transaction(
+    notification: &TransactionNotification,
+    program_id: &str,
+    registry: &DecoderRegistry,
+    options: &ParseOptions,
+) -> Option<PayloadTransaction> {
+    let pumpfun = parse_notification_with_options(notification, program_id, options);
+    let others = parse_program_events(notification, registry);
+    match (pumpfun, others) {
+        (Some(mut transaction), Some(others)) if transaction.error.is_none() => {
+            transaction.actions.extend(others.actions);
+            Some(transaction)
+        }
+        (Some(transaction), _) => Some(transaction),
+        (None, others) => others,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pumpfun_amm_parser::PUMP_AMM_PROGRAM_ID;
+    use crate::pumpfun_instruction_parser::{PayloadEnvelope, PayloadSource, PUMPFUN_PROGRAM_ID};
+    use crate::spl_token_parser::SPL_TOKEN_PROGRAM_ID;
+
+    const ROUTER_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tQVkTqUwjnvsTEn";
+    const USER_PK: &str = "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47";
+
+    // A router swapping on the pump.fun AMM, which moves the tokens with an SPL transfer
+    fn routed_amm_swap_notification() -> TransactionNotification {
+        let mut swap_data = vec![51, 230, 133, 164, 1, 127, 131, 173]; // sell
+        swap_data.extend(1_000_000u64.to_le_bytes());
+        swap_data.extend(2_000u64.to_le_bytes());
+        let mut transfer_data = vec![3];
+        transfer_data.extend(1_000_000u64.to_le_bytes());
+
+        serde_json::from_value(serde_json::json!({
+            "transaction": {
+                "transaction": {
+                    "signatures": ["sig"],
+                    "message": {
+                        "accountKeys": [
+                            {"pubkey": USER_PK, "writable": true, "signer": true, "source": "transaction"},
+                            {"pubkey": ROUTER_PROGRAM_ID, "writable": false, "signer": false, "source": "transaction"}
+                        ],
+                        "recentBlockhash": "5vuDPHcRjQWAkf3ebsNkrw9RbHCrbNZjuw1jL47FA3mR",
+                        "instructions": [
+                            {
+                                "programId": ROUTER_PROGRAM_ID,
+                                "accounts": [USER_PK],
+                                "data": "",
+                                "stackHeight": null
+                            }
+                        ]
+                    }
+                },
+                "meta": {
+                    "err": null,
+                    "status": {"Ok": null},
+                    "fee": 5000,
+                    "preBalances": [],
+                    "postBalances": [],
+                    "innerInstructions": [
+                        {
+                            "index": 0,
+                            "instructions": [
+                                {
+                                    "programId": PUMP_AMM_PROGRAM_ID,
+                                    "accounts": [
+                                        "pool", USER_PK, "global_config", "base_mint", "quote_mint",
+                                        "user_base_ata", "user_quote_ata", "pool_base_ata", "pool_quote_ata"
+                                    ],
+                                    "data": bs58::encode(swap_data).into_string(),
+                                    "stackHeight": 2
+                                },
+                                {
+                                    "programId": SPL_TOKEN_PROGRAM_ID,
+                                    "accounts": ["user_base_ata", "pool_base_ata", USER_PK],
+                                    "data": bs58::encode(transfer_data).into_string(),
+                                    "stackHeight": 3
+                                }
+                            ]
+                        }
+                    ]
+                },
+                "version": 0
+            },
+            "signature": "sig",
+            "slot": 320000000
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_program_events() {
+        let notification = routed_amm_swap_notification();
+
+        let transaction =
+            parse_program_events(&notification, &DecoderRegistry::with_defaults()).unwrap();
+        assert_eq!(2, transaction.actions.len());
+        match &transaction.actions[0] {
+            PayloadAction::PumpAmmSwap(swap) => {
+                assert!(!swap.is_buy);
+                assert_eq!("base_mint", swap.base_mint);
+                assert_eq!(1_000_000, swap.base_amount);
+                assert_eq!(2_000, swap.quote_amount_limit);
+                assert_eq!(Some(ROUTER_PROGRAM_ID.to_string()), swap.router);
+                assert_eq!("0.0", swap.instruction.to_string());
+            }
+            other => panic!("expected a pump amm swap, got {:?}", other),
+        }
+        match &transaction.actions[1] {
+            PayloadAction::TokenTransfer(transfer) => {
+                assert_eq!(1_000_000, transfer.amount);
+                assert_eq!(USER_PK, transfer.authority);
+                assert_eq!("0.1", transfer.instruction.to_string());
+            }
+            other => panic!("expected a token transfer, got {:?}", other),
+        }
+
+        // nothing registered, nothing decoded
+        assert!(parse_program_events(&notification, &DecoderRegistry::new()).is_none());
+    }
+
+    #[test]
+    fn test_parse_transaction() {
+        let notification = routed_amm_swap_notification();
+        let options = ParseOptions::default();
+
+        // no pump.fun instruction, the amm swap only
+        let registry = DecoderRegistry::from_names(&["pump_amm".to_string()]).unwrap();
+        let transaction =
+            parse_transaction(&notification, PUMPFUN_PROGRAM_ID, &registry, &options).unwrap();
+        match &transaction.actions[..] {
+            [PayloadAction::PumpAmmSwap(swap)] => assert_eq!("base_mint", swap.base_mint),
+            other => panic!("expected a single pump amm swap, got {:?}", other),
+        }
+        let payloads =
+            transaction.into_payloads(&PayloadEnvelope::new(PayloadSource::HeliusWs, None));
+        assert_eq!("pumpammswap", payloads[0].action());
+        assert_eq!(Some("base_mint"), payloads[0].mint_pk());
+
+        assert!(DecoderRegistry::from_names(&["orca".to_string()]).is_err());
+    }
+}
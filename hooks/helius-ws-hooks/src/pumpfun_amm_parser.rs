@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::UiInstruction;
+
+use crate::program_decoder::{DecodeContext, ProgramDecoder};
+use crate::pumpfun_instruction_parser::{resolve_instruction, InstructionIndex, PayloadAction};
+
+/// The pump.fun AMM, where a token trades after its bonding curve completes
+pub const PUMP_AMM_PROGRAM_ID: &str = "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA";
+
+// anchor discriminators, sha256("global:<name>")[..8]
+const BUY_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+const SELL_DISCRIMINATOR: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
+
+/// A buy or sell of the base token of a pump.fun AMM pool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayloadPumpAmmSwap {
+    pub slot: u64,
+    pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
+    pub pool: String,
+    pub user_pk: String,
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub is_buy: bool,
+    pub base_amount: u64, // base_amount_out of a buy, base_amount_in of a sell
+    pub quote_amount_limit: u64, // max quote in of a buy, min quote out of a sell
+    pub router: Option<String>,
+}
+
+pub struct PumpAmmDecoder;
+
+impl ProgramDecoder for PumpAmmDecoder {
+    fn program_id(&self) -> &str {
+        PUMP_AMM_PROGRAM_ID
+    }
+
+    fn decode(
+        &self,
+        instruction: &UiInstruction,
+        context: &DecodeContext,
+    ) -> Option<PayloadAction> {
+        let i = resolve_instruction(instruction, context.account_keys)?;
+        let data = bs58::decode(i.data.as_str()).into_vec().ok()?;
+        if data.len() < 24 {
+            return None;
+        }
+        let is_buy = match data[..8] {
+            BUY_DISCRIMINATOR => true,
+            SELL_DISCRIMINATOR => false,
+            _ => return None,
+        };
+        // pool, user, global_config, base_mint, quote_mint, ...
+        if i.accounts.len() < 5 {
+            return None;
+        }
+
+        Some(PayloadAction::PumpAmmSwap(PayloadPumpAmmSwap {
+            slot: context.slot,
+            signature: context.signature.to_string(),
+            instruction: context.instruction,
+            pool: i.accounts[0].clone(),
+            user_pk: i.accounts[1].clone(),
+            base_mint: i.accounts[3].clone(),
+            quote_mint: i.accounts[4].clone(),
+            is_buy,
+            base_amount: u64::from_le_bytes(data[8..16].try_into().unwrap()),
+            quote_amount_limit: u64::from_le_bytes(data[16..24].try_into().unwrap()),
+            router: context.router.map(str::to_string),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_pump_amm_buy() {
+        let mut data = BUY_DISCRIMINATOR.to_vec();
+        data.extend(35_000_000_000u64.to_le_bytes());
+        data.extend(1_100_000_000u64.to_le_bytes());
+        let instruction: UiInstruction = serde_json::from_value(serde_json::json!({
+            "programId": PUMP_AMM_PROGRAM_ID,
+            "accounts": [
+                "pool", "user", "global_config", "base_mint", "So11111111111111111111111111111111111111112"
+            ],
+            "data": bs58::encode(data).into_string(),
+            "stackHeight": null
+        }))
+        .unwrap();
+        let context = DecodeContext {
+            slot: 320000000,
+            signature: "sig",
+            instruction: InstructionIndex::default(),
+            account_keys: &[],
+            router: None,
+        };
+
+        match PumpAmmDecoder.decode(&instruction, &context) {
+            Some(PayloadAction::PumpAmmSwap(swap)) => {
+                assert!(swap.is_buy);
+                assert_eq!("pool", swap.pool);
+                assert_eq!("user", swap.user_pk);
+                assert_eq!("base_mint", swap.base_mint);
+                assert_eq!(35_000_000_000, swap.base_amount);
+                assert_eq!(1_100_000_000, swap.quote_amount_limit);
+                assert_eq!(None, swap.router);
+            }
+            other => panic!("expected a pump amm swap, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use helius::jito::JITO_TIP_ACCOUNTS;
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{UiTransactionStatusMeta, UiTransactionTokenBalance};
+
+/// What a user actually received (positive) or paid (negative) in a transaction, taken from the
+/// pre/post balances of the notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayloadBalance {
+    pub token_delta: i64, // raw token amount of the mint held by the user
+    pub sol_delta: i64,   // lamports, net of the transaction fee and jito tips
+}
+
+fn token_amount(
+    token_balances: Option<&Vec<UiTransactionTokenBalance>>,
+    mint: &str,
+    owner: &str,
+) -> i128 {
+    token_balances
+        .into_iter()
+        .flatten()
+        .filter(|balance| balance.mint == mint)
+        .filter(|balance| matches!(&balance.owner, OptionSerializer::Some(o) if o == owner))
+        .filter_map(|balance| balance.ui_token_amount.amount.parse::<u64>().ok())
+        .map(i128::from)
+        .sum()
+}
+
+/// Lamports received by the jito tip accounts in a transaction.
+pub fn jito_tips(meta: &UiTransactionStatusMeta, account_keys: &[String]) -> u64 {
+    account_keys
+        .iter()
+        .enumerate()
+        .filter(|(_, key)| JITO_TIP_ACCOUNTS.contains(&key.as_str()))
+        .filter_map(|(index, _)| {
+            let pre = meta.pre_balances.get(index)?;
+            let post = meta.post_balances.get(index)?;
+            Some(post.saturating_sub(*pre))
+        })
+        .sum()
+}
+
+/// The token and SOL balance change of `user_pk` over the whole transaction. The fee and the jito
+/// tips are paid by the fee payer and added back; account rent (e.g. a new ATA) is still included.
+pub fn balance_change(
+    meta: &UiTransactionStatusMeta,
+    account_keys: &[String],
+    mint_pk: &str,
+    user_pk: &str,
+) -> Option<PayloadBalance> {
+    let index = account_keys.iter().position(|key| key == user_pk)?;
+    let pre_sol = i128::from(*meta.pre_balances.get(index)?);
+    let post_sol = i128::from(*meta.post_balances.get(index)?);
+
+    let mut sol_delta = post_sol - pre_sol;
+    // the fee payer is always the first account
+    if index == 0 {
+        sol_delta += i128::from(meta.fee) + i128::from(jito_tips(meta, account_keys));
+    }
+
+    let token_delta = token_amount(meta.post_token_balances.as_ref().into(), mint_pk, user_pk)
+        - token_amount(meta.pre_token_balances.as_ref().into(), mint_pk, user_pk);
+
+    Some(PayloadBalance {
+        token_delta: i64::try_from(token_delta).ok()?,
+        sol_delta: i64::try_from(sol_delta).ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINT_PK: &str = "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump";
+    const USER_PK: &str = "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47";
+    const JITO_TIP_ACCOUNT: &str = "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5";
+
+    fn account_keys() -> Vec<String> {
+        vec![
+            USER_PK.to_string(),
+            "2SX6nwmusAzWTJkzawCiWM4cxZxBBQjLYeaVUhj8E5hx".to_string(), // associated_user
+            "8PkzhXamH8CkaxgGwjbi9HkkSjjA85bSbxmKhSKNbP98".to_string(), // bonding_curve
+            JITO_TIP_ACCOUNT.to_string(),
+        ]
+    }
+
+    // a buy of 35758322578 tokens for 1 SOL, paying a 5000 lamports fee and a 100000 lamports tip
+    fn buy_meta() -> UiTransactionStatusMeta {
+        serde_json::from_value(serde_json::json!({
+            "err": null,
+            "status": {"Ok": null},
+            "fee": 5000,
+            "preBalances": [5_000_000_000u64, 0, 30_000_000_000u64, 1_000_000],
+            "postBalances": [3_997_855_072u64, 2_039_928, 31_000_000_000u64, 1_100_000],
+            "preTokenBalances": [],
+            "postTokenBalances": [
+                {
+                    "accountIndex": 1,
+                    "mint": MINT_PK,
+                    "owner": USER_PK,
+                    "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                    "uiTokenAmount": {
+                        "uiAmount": 35758.322578,
+                        "decimals": 6,
+                        "amount": "35758322578",
+                        "uiAmountString": "35758.322578"
+                    }
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_jito_tips() {
+        assert_eq!(100_000, jito_tips(&buy_meta(), &account_keys()));
+    }
+
+    #[test]
+    fn test_balance_change() {
+        let balance = balance_change(&buy_meta(), &account_keys(), MINT_PK, USER_PK).unwrap();
+        assert_eq!(35758322578, balance.token_delta);
+        // 1 SOL into the curve and the rent of the new associated token account
+        assert_eq!(-1_002_039_928, balance.sol_delta);
+
+        // not a signer of this transaction
+        assert!(balance_change(&buy_meta(), &account_keys(), MINT_PK, MINT_PK).is_none());
+    }
+}
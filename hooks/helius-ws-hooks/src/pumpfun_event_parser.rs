@@ -0,0 +1,308 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_transaction_status::{UiInnerInstructions, UiTransactionStatusMeta};
+
+use crate::pumpfun_instruction_parser::resolve_instruction;
+
+/// Anchor prefixes events emitted through the event authority self-CPI with this tag
+pub const EVENT_IX_TAG_LE: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+// sha256("event:<EventName>")[..8]
+pub const CREATE_EVENT_DISCRIMINATOR: [u8; 8] = [27, 114, 169, 77, 222, 235, 99, 118];
+pub const TRADE_EVENT_DISCRIMINATOR: [u8; 8] = [189, 219, 127, 211, 78, 230, 97, 238];
+pub const COMPLETE_EVENT_DISCRIMINATOR: [u8; 8] = [95, 114, 97, 156, 212, 46, 152, 8];
+pub const SET_PARAMS_EVENT_DISCRIMINATOR: [u8; 8] = [223, 195, 159, 246, 62, 48, 143, 131];
+
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct CreateEvent {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub user: Pubkey,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct TradeEvent {
+    pub mint: Pubkey,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub is_buy: bool,
+    pub user: Pubkey,
+    pub timestamp: i64,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub real_token_reserves: u64,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct CompleteEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct SetParamsEvent {
+    pub fee_recipient: Pubkey,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    pub fee_basis_points: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum PumpfunEvent {
+    Create(CreateEvent),
+    Trade(TradeEvent),
+    Complete(CompleteEvent),
+    SetParams(SetParamsEvent),
+}
+
+/// What a pump.fun trade actually filled at, taken from its `TradeEvent`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayloadTrade {
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub is_buy: bool,
+    pub timestamp: i64,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub real_token_reserves: u64,
+}
+
+impl From<&TradeEvent> for PayloadTrade {
+    fn from(event: &TradeEvent) -> Self {
+        PayloadTrade {
+            sol_amount: event.sol_amount,
+            token_amount: event.token_amount,
+            is_buy: event.is_buy,
+            timestamp: event.timestamp,
+            virtual_sol_reserves: event.virtual_sol_reserves,
+            virtual_token_reserves: event.virtual_token_reserves,
+            real_sol_reserves: event.real_sol_reserves,
+            real_token_reserves: event.real_token_reserves,
+        }
+    }
+}
+
+/// Decode an event from `discriminator + borsh data`.
+pub fn decode_event(data: &[u8]) -> Option<PumpfunEvent> {
+    // invalid discriminator length
+    if data.len() < 8 {
+        return None;
+    }
+
+    let discriminator: [u8; 8] = data[..8].try_into().ok()?;
+    // newer program versions append fields to the events, so trailing bytes are ignored
+    let mut event_data = &data[8..];
+
+    match discriminator {
+        CREATE_EVENT_DISCRIMINATOR => {
+            let event = CreateEvent::deserialize(&mut event_data).ok()?;
+            Some(PumpfunEvent::Create(event))
+        }
+        TRADE_EVENT_DISCRIMINATOR => {
+            let event = TradeEvent::deserialize(&mut event_data).ok()?;
+            Some(PumpfunEvent::Trade(event))
+        }
+        COMPLETE_EVENT_DISCRIMINATOR => {
+            let event = CompleteEvent::deserialize(&mut event_data).ok()?;
+            Some(PumpfunEvent::Complete(event))
+        }
+        SET_PARAMS_EVENT_DISCRIMINATOR => {
+            let event = SetParamsEvent::deserialize(&mut event_data).ok()?;
+            Some(PumpfunEvent::SetParams(event))
+        }
+        _ => None,
+    }
+}
+
+/// Decode the instruction data of an event authority self-CPI.
+pub fn decode_cpi_event(data: &[u8]) -> Option<PumpfunEvent> {
+    if data.len() < 8 || data[..8] != EVENT_IX_TAG_LE {
+        return None;
+    }
+    decode_event(&data[8..])
+}
+
+/// Decode the `Program data:` lines logged while `program_id` is the executing program.
+pub fn parse_log_events(log_messages: &[String], program_id: &str) -> Vec<PumpfunEvent> {
+    let mut events = vec![];
+    // invocation stack, the last element is the currently executing program
+    let mut invocations: Vec<&str> = vec![];
+
+    for log in log_messages {
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if invocations.last() != Some(&program_id) {
+                continue;
+            }
+            if let Some(event) = STANDARD.decode(data).ok().and_then(|d| decode_event(&d)) {
+                events.push(event);
+            }
+            continue;
+        }
+
+        // `Program <id> invoke [n]`, `Program <id> success`, `Program <id> failed: ...`
+        let mut words = log.split_whitespace();
+        if words.next() != Some("Program") {
+            continue;
+        }
+        let (Some(id), Some(status)) = (words.next(), words.next()) else {
+            continue;
+        };
+        match status {
+            "invoke" => invocations.push(id),
+            "success" | "failed:" => {
+                invocations.pop();
+            }
+            _ => {}
+        }
+    }
+
+    events
+}
+
+/// Decode the events emitted as self-CPI inner instructions of `program_id`.
+pub fn parse_cpi_events(
+    inner_instructions: &[UiInnerInstructions],
+    account_keys: &[String],
+    program_id: &str,
+) -> Vec<PumpfunEvent> {
+    let mut events = vec![];
+
+    for inner in inner_instructions {
+        for ui_instruction in &inner.instructions {
+            let Some(i) = resolve_instruction(ui_instruction, account_keys) else {
+                continue;
+            };
+            if i.program_id != program_id {
+                continue;
+            }
+            let Ok(data) = bs58::decode(i.data.as_str()).into_vec() else {
+                continue;
+            };
+            if let Some(event) = decode_cpi_event(&data) {
+                events.push(event);
+            }
+        }
+    }
+
+    events
+}
+
+/// Decode the events of a transaction, preferring the self-CPI events over the logs,
+/// which can be truncated by the validator.
+pub fn parse_events(
+    meta: &UiTransactionStatusMeta,
+    account_keys: &[String],
+    program_id: &str,
+) -> Vec<PumpfunEvent> {
+    let inner_instructions: Option<&Vec<UiInnerInstructions>> =
+        meta.inner_instructions.as_ref().into();
+    if let Some(inner_instructions) = inner_instructions {
+        let events = parse_cpi_events(inner_instructions, account_keys, program_id);
+        if !events.is_empty() {
+            return events;
+        }
+    }
+
+    let log_messages: Option<&Vec<String>> = meta.log_messages.as_ref().into();
+    log_messages
+        .map(|log_messages| parse_log_events(log_messages, program_id))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn trade_event() -> TradeEvent {
+        TradeEvent {
+            mint: Pubkey::from_str("8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump").unwrap(),
+            sol_amount: 1_717_000_000,
+            token_amount: 57542586750788,
+            is_buy: true,
+            user: Pubkey::from_str("DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47").unwrap(),
+            timestamp: 1735000000,
+            virtual_sol_reserves: 31_717_000_000,
+            virtual_token_reserves: 1015457604249212,
+            real_sol_reserves: 1_717_000_000,
+            real_token_reserves: 735557604249212,
+        }
+    }
+
+    fn event_data(event: &TradeEvent) -> Vec<u8> {
+        let mut data = TRADE_EVENT_DISCRIMINATOR.to_vec();
+        data.extend(borsh::to_vec(event).unwrap());
+        data
+    }
+
+    #[test]
+    fn test_decode_trade_event() {
+        let data = event_data(&trade_event());
+
+        match decode_event(&data) {
+            Some(PumpfunEvent::Trade(event)) => {
+                assert_eq!(1_717_000_000, event.sol_amount);
+                assert_eq!(57542586750788, event.token_amount);
+                assert!(event.is_buy);
+            }
+            other => panic!("expected a trade event, got {:?}", other),
+        }
+
+        // extra trailing fields from newer program versions are ignored
+        let mut data = data;
+        data.extend([0u8; 32]);
+        assert!(matches!(decode_event(&data), Some(PumpfunEvent::Trade(_))));
+    }
+
+    #[test]
+    fn test_decode_cpi_event() {
+        let mut data = EVENT_IX_TAG_LE.to_vec();
+        data.extend(event_data(&trade_event()));
+        assert!(matches!(
+            decode_cpi_event(&data),
+            Some(PumpfunEvent::Trade(_))
+        ));
+
+        // without the event tag it is not an event
+        assert!(decode_cpi_event(&event_data(&trade_event())).is_none());
+    }
+
+    #[test]
+    fn test_parse_log_events() {
+        let program_id = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+        let program_data = format!(
+            "{}{}",
+            PROGRAM_DATA_PREFIX,
+            STANDARD.encode(event_data(&trade_event()))
+        );
+        let logs = vec![
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]".to_string(),
+            "Program ComputeBudget111111111111111111111111111111 success".to_string(),
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Instruction: Buy".to_string(),
+            program_data.clone(),
+            format!("Program {} success", program_id),
+            // logged by another program, must not be decoded as a pump.fun event
+            "Program routeUGWgWzqBWFcrCfv8tritsqukccJPu3q5GPP3xS invoke [1]".to_string(),
+            program_data,
+            "Program routeUGWgWzqBWFcrCfv8tritsqukccJPu3q5GPP3xS success".to_string(),
+        ];
+
+        let events = parse_log_events(&logs, program_id);
+        assert_eq!(1, events.len());
+        assert!(matches!(events[0], PumpfunEvent::Trade(_)));
+    }
+}
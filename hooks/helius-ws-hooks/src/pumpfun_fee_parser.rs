@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::{UiInstruction, UiTransactionStatusMeta};
+
+use crate::pumpfun_balance_parser::jito_tips;
+use crate::pumpfun_instruction_parser::resolve_instruction;
+
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+
+// ComputeBudgetInstruction tags
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+/// What a transaction paid to land: compute budget and jito tip
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PayloadFees {
+    pub cu_limit: Option<u32>,
+    pub priority_fee_micro_lamports: Option<u64>,
+    pub jito_tip_lamports: u64,
+}
+
+/// Decode the compute budget of a transaction, and the jito tips it paid from the balances of the
+/// tip accounts, tips paid through a CPI (e.g. by a trading bot program) included.
+pub fn parse_fees(
+    instructions: &[UiInstruction],
+    meta: &UiTransactionStatusMeta,
+    account_keys: &[String],
+) -> PayloadFees {
+    let mut fees = PayloadFees {
+        jito_tip_lamports: jito_tips(meta, account_keys),
+        ..Default::default()
+    };
+
+    for ui_instruction in instructions {
+        let Some(i) = resolve_instruction(ui_instruction, account_keys) else {
+            continue;
+        };
+        if i.program_id != COMPUTE_BUDGET_PROGRAM_ID {
+            continue;
+        }
+        let Ok(data) = bs58::decode(i.data.as_str()).into_vec() else {
+            continue;
+        };
+        match data.split_first() {
+            Some((&SET_COMPUTE_UNIT_LIMIT, rest)) => {
+                fees.cu_limit = rest.try_into().ok().map(u32::from_le_bytes);
+            }
+            Some((&SET_COMPUTE_UNIT_PRICE, rest)) => {
+                fees.priority_fee_micro_lamports = rest.try_into().ok().map(u64::from_le_bytes);
+            }
+            _ => {}
+        }
+    }
+
+    fees
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYER: &str = "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47";
+    const JITO_TIP_ACCOUNT: &str = "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5";
+
+    fn compute_budget(data: Vec<u8>) -> UiInstruction {
+        serde_json::from_value(serde_json::json!({
+            "programId": COMPUTE_BUDGET_PROGRAM_ID,
+            "accounts": [],
+            "data": bs58::encode(data).into_string(),
+            "stackHeight": null
+        }))
+        .unwrap()
+    }
+
+    // the payer tipping 10000 lamports
+    fn tip_meta() -> UiTransactionStatusMeta {
+        serde_json::from_value(serde_json::json!({
+            "err": null,
+            "status": {"Ok": null},
+            "fee": 5000,
+            "preBalances": [1_000_000_000u64, 1_000_000],
+            "postBalances": [999_985_000u64, 1_010_000]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_fees() {
+        let mut set_limit = vec![SET_COMPUTE_UNIT_LIMIT];
+        set_limit.extend(63909u32.to_le_bytes());
+        let mut set_price = vec![SET_COMPUTE_UNIT_PRICE];
+        set_price.extend(140999u64.to_le_bytes());
+
+        let instructions = vec![compute_budget(set_limit), compute_budget(set_price)];
+        let account_keys = vec![PAYER.to_string(), JITO_TIP_ACCOUNT.to_string()];
+        let fees = parse_fees(&instructions, &tip_meta(), &account_keys);
+        assert_eq!(Some(63909), fees.cu_limit);
+        assert_eq!(Some(140999), fees.priority_fee_micro_lamports);
+        assert_eq!(10000, fees.jito_tip_lamports);
+    }
+
+    #[test]
+    fn test_parse_fees_without_compute_budget() {
+        let account_keys = vec![PAYER.to_string()];
+        let fees = parse_fees(&[], &tip_meta(), &account_keys);
+        assert_eq!(PayloadFees::default(), fees);
+    }
+}
@@ -1,39 +1,492 @@
 use anchor_lang::{AnchorDeserialize, Discriminator};
-use helius::types::enhanced_websocket::TransactionNotification;
-use pumpfun_cpi::instruction::{Buy, Create, Sell, Withdraw};
+use helius::types::enhanced_websocket::{
+    TransactionCommitment, TransactionNotification, UiEnhancedTransactionEncoding,
+};
+use pumpfun_cpi::instruction::{Buy, Create, Initialize, Sell, SetParams, Withdraw};
 use serde::{Deserialize, Serialize};
+use solana_program::instruction::InstructionError;
+use solana_sdk::transaction::TransactionError;
 use solana_transaction_status::{
-    EncodedTransaction, UiInstruction, UiMessage, UiParsedInstruction,
-    UiPartiallyDecodedInstruction,
+    EncodedTransaction, UiCompiledInstruction, UiInnerInstructions, UiInstruction,
+    UiLoadedAddresses, UiMessage, UiParsedInstruction, UiPartiallyDecodedInstruction,
+    UiTransactionStatusMeta,
 };
-use std::ops::Index;
+use std::borrow::Cow;
+use std::fmt;
 use std::string::ToString;
 
 use crate::batch_csv_writer::BatchCsvRecord;
+use crate::pumpfun_amm_parser::PayloadPumpAmmSwap;
+use crate::pumpfun_balance_parser::{balance_change, PayloadBalance};
+use crate::pumpfun_event_parser::{parse_events, PayloadTrade, PumpfunEvent, TradeEvent};
+use crate::pumpfun_fee_parser::{parse_fees, PayloadFees};
+use crate::raydium_amm_parser::PayloadRaydiumSwap;
+use crate::spl_token_parser::PayloadTokenTransfer;
+
+pub const PUMPFUN_PROGRAM_ID: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
 
 #[allow(dead_code)]
 enum WrapInstruction {
-    Create(Create, u64, String, String, String, String, String), // slot, sig, mint_pk, user_pk, bonding_curve, associated_bonding_curve
-    Buy(Buy, u64, String, String, String),                       // slot, sig, mint_pk, user_pk
-    Sell(Sell, u64, String, String, String),                     // slot, sig, mint_pk, user_pk
-    Withdraw(Withdraw, u64, String, String),                     // slot, sig, mint_pk
+    Create(Create, u64, String, String, String, String, String, Option<String>), // slot, sig, mint_pk, user_pk, bonding_curve, associated_bonding_curve, router
+    Buy(Buy, u64, String, String, String, Option<String>),                       // slot, sig, mint_pk, user_pk, router
+    Sell(Sell, u64, String, String, String, Option<String>),                     // slot, sig, mint_pk, user_pk, router
+    Withdraw(Withdraw, u64, String, String, Option<String>),                     // slot, sig, mint_pk, router
+    SetParams(SetParams, u64, String, String, Option<String>),                   // slot, sig, user_pk, router
+    Initialize(Initialize, u64, String, String, Option<String>),                 // slot, sig, user_pk, router
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WrapPayload {
-    Create(PayloadCreate, bool),
-    CreateBuy(PayloadCreateBuy, bool),
-    Buy(PayloadBuy, bool),
-    Sell(PayloadSell, bool),
-    BuySell(PayloadBuySell, bool),
-    Withdraw(PayloadWithdraw, bool),
+    Create(PayloadCreate, PayloadEnvelope),
+    CreateBuy(PayloadCreateBuy, PayloadEnvelope),
+    Buy(PayloadBuy, PayloadEnvelope),
+    Sell(PayloadSell, PayloadEnvelope),
+    BuySell(PayloadBuySell, PayloadEnvelope),
+    Withdraw(PayloadWithdraw, PayloadEnvelope),
+    SetParams(PayloadSetParams, PayloadEnvelope),
+    Initialize(PayloadInitialize, PayloadEnvelope),
+    Failed(PayloadFailed, PayloadEnvelope),
+    PumpAmmSwap(PayloadPumpAmmSwap, PayloadEnvelope),
+    RaydiumSwap(PayloadRaydiumSwap, PayloadEnvelope),
+    TokenTransfer(PayloadTokenTransfer, PayloadEnvelope),
     Unknown,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Where a notification was received from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PayloadSource {
+    HeliusWs, // straight from the helius websocket
+    Redis,    // relayed through a redis queue
+    Replay,   // re-read from a recording
+    Backfill, // fetched from rpc after the fact
+}
+
+/// Metadata shared by every payload of a notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayloadEnvelope {
+    pub source: PayloadSource,
+    pub commitment: Option<TransactionCommitment>,
+    pub received_at: i64, // local unix timestamp, milliseconds
+    #[serde(default)]
+    pub block_time: Option<i64>, // unix timestamp, seconds, when known
+    pub is_paper_trade: bool,
+    #[serde(default)]
+    pub upstream: Option<String>, // the connection that received it, e.g. helius_ws:fra
+}
+
+impl PayloadEnvelope {
+    /// An envelope received now
+    pub fn new(source: PayloadSource, commitment: Option<TransactionCommitment>) -> Self {
+        Self {
+            source,
+            commitment,
+            received_at: chrono::Utc::now().timestamp_millis(),
+            block_time: None,
+            is_paper_trade: false,
+            upstream: None,
+        }
+    }
+}
+
+/// Where an instruction sits in its transaction: the outer instruction and, for one invoked via
+/// CPI, its position among the inner instructions of that outer one
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct InstructionIndex {
+    pub outer: usize,
+    pub inner: Option<usize>,
+}
+
+impl fmt::Display for InstructionIndex {
+    /// `2` for an outer instruction, `2.1` for an inner one
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.inner {
+            Some(inner) => write!(f, "{}.{}", self.outer, inner),
+            None => write!(f, "{}", self.outer),
+        }
+    }
+}
+
+/// One decoded pump.fun instruction, or one of a program of the `DecoderRegistry`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PayloadAction {
+    Create(PayloadCreate),
+    Buy(PayloadBuy),
+    Sell(PayloadSell),
+    Withdraw(PayloadWithdraw),
+    SetParams(PayloadSetParams),
+    Initialize(PayloadInitialize),
+    PumpAmmSwap(PayloadPumpAmmSwap),
+    RaydiumSwap(PayloadRaydiumSwap),
+    TokenTransfer(PayloadTokenTransfer),
+}
+
+/// All decoded actions of a transaction, in instruction order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadTransaction {
+    pub slot: u64,
+    pub signature: String,
+    pub actions: Vec<PayloadAction>,
+    pub error: Option<PayloadError>,
+}
+
+/// Why a transaction failed on chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadError {
+    pub instruction_index: Option<u8>,
+    pub error_code: Option<u32>,
+    pub error_name: Option<String>, // a custom code is named only for pump.fun instructions
+    pub error: String,
+    pub compute_units_consumed: Option<u64>,
+}
+
+/// A failed pump.fun transaction, with the actions of the instruction that failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadFailed {
+    pub slot: u64,
+    pub signature: String,
+    pub actions: Vec<PayloadAction>,
+    pub error: PayloadError,
+}
+
+/// Options of `parse_notification_with_options`
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// Also decode failed transactions, as a single `Failed` payload
+    pub capture_failed: bool,
+}
+
+// pump.fun IDL errors
+const PUMPFUN_ERRORS: [(u32, &str); 9] = [
+    (6000, "NotAuthorized"),
+    (6001, "AlreadyInitialized"),
+    (6002, "TooMuchSolRequired"),
+    (6003, "TooLittleSolReceived"),
+    (6004, "MintDoesNotMatchBondingCurve"),
+    (6005, "BondingCurveComplete"),
+    (6006, "BondingCurveNotComplete"),
+    (6007, "NotInitialized"),
+    (6008, "WithdrawTooFrequent"),
+];
+
+pub fn pumpfun_error_name(code: u32) -> Option<&'static str> {
+    PUMPFUN_ERRORS
+        .iter()
+        .find(|(error_code, _)| *error_code == code)
+        .map(|(_, name)| *name)
+}
+
+impl WrapPayload {
+    /// The `action` column of the csv record, "failed" for any failed transaction
+    pub fn action(&self) -> &'static str {
+        match self {
+            WrapPayload::Create(_, _) => "create",
+            WrapPayload::CreateBuy(_, _) => "createbuy",
+            WrapPayload::Buy(_, _) => "buy",
+            WrapPayload::Sell(_, _) => "sell",
+            WrapPayload::BuySell(_, _) => "buysell",
+            WrapPayload::Withdraw(_, _) => "withdraw",
+            WrapPayload::SetParams(_, _) => "setparams",
+            WrapPayload::Initialize(_, _) => "initialize",
+            WrapPayload::Failed(_, _) => "failed",
+            WrapPayload::PumpAmmSwap(_, _) => "pumpammswap",
+            WrapPayload::RaydiumSwap(_, _) => "raydiumswap",
+            WrapPayload::TokenTransfer(_, _) => "tokentransfer",
+            WrapPayload::Unknown => "unknown",
+        }
+    }
+
+    pub fn signature(&self) -> Option<&str> {
+        match self {
+            WrapPayload::Create(payload, _) => Some(&payload.signature),
+            WrapPayload::CreateBuy(payload, _) => Some(&payload.signature),
+            WrapPayload::Buy(payload, _) => Some(&payload.signature),
+            WrapPayload::Sell(payload, _) => Some(&payload.signature),
+            WrapPayload::BuySell(payload, _) => Some(&payload.signature),
+            WrapPayload::Withdraw(payload, _) => Some(&payload.signature),
+            WrapPayload::SetParams(payload, _) => Some(&payload.signature),
+            WrapPayload::Initialize(payload, _) => Some(&payload.signature),
+            WrapPayload::Failed(payload, _) => Some(&payload.signature),
+            WrapPayload::PumpAmmSwap(payload, _) => Some(&payload.signature),
+            WrapPayload::RaydiumSwap(payload, _) => Some(&payload.signature),
+            WrapPayload::TokenTransfer(payload, _) => Some(&payload.signature),
+            WrapPayload::Unknown => None,
+        }
+    }
+
+    pub fn slot(&self) -> Option<u64> {
+        match self {
+            WrapPayload::Create(payload, _) => Some(payload.slot),
+            WrapPayload::CreateBuy(payload, _) => Some(payload.slot),
+            WrapPayload::Buy(payload, _) => Some(payload.slot),
+            WrapPayload::Sell(payload, _) => Some(payload.slot),
+            WrapPayload::BuySell(payload, _) => Some(payload.slot),
+            WrapPayload::Withdraw(payload, _) => Some(payload.slot),
+            WrapPayload::SetParams(payload, _) => Some(payload.slot),
+            WrapPayload::Initialize(payload, _) => Some(payload.slot),
+            WrapPayload::Failed(payload, _) => Some(payload.slot),
+            WrapPayload::PumpAmmSwap(payload, _) => Some(payload.slot),
+            WrapPayload::RaydiumSwap(payload, _) => Some(payload.slot),
+            WrapPayload::TokenTransfer(payload, _) => Some(payload.slot),
+            WrapPayload::Unknown => None,
+        }
+    }
+
+    /// The token the payload is about, the one of the failing action for a failed transaction.
+    /// A raydium swap only names token accounts.
+    pub fn mint_pk(&self) -> Option<&str> {
+        match self {
+            WrapPayload::Create(payload, _) => Some(&payload.mint_pk),
+            WrapPayload::CreateBuy(payload, _) => Some(&payload.mint_pk),
+            WrapPayload::Buy(payload, _) => Some(&payload.mint_pk),
+            WrapPayload::Sell(payload, _) => Some(&payload.mint_pk),
+            WrapPayload::BuySell(payload, _) => Some(&payload.mint_pk),
+            WrapPayload::Withdraw(payload, _) => Some(&payload.mint_pk),
+            WrapPayload::Failed(payload, _) => payload.actions.first()?.mint_pk(),
+            WrapPayload::PumpAmmSwap(payload, _) => Some(&payload.base_mint),
+            WrapPayload::TokenTransfer(payload, _) => payload.mint.as_deref(),
+            WrapPayload::SetParams(_, _)
+            | WrapPayload::Initialize(_, _)
+            | WrapPayload::RaydiumSwap(_, _)
+            | WrapPayload::Unknown => None,
+        }
+    }
+
+    /// The instruction the payload was decoded from, the first one of a CreateBuy or BuySell and
+    /// the failing one of a failed transaction
+    pub fn instruction(&self) -> Option<InstructionIndex> {
+        match self {
+            WrapPayload::Create(payload, _) => Some(payload.instruction),
+            WrapPayload::CreateBuy(payload, _) => Some(payload.instruction),
+            WrapPayload::Buy(payload, _) => Some(payload.instruction),
+            WrapPayload::Sell(payload, _) => Some(payload.instruction),
+            WrapPayload::BuySell(payload, _) => Some(payload.instruction),
+            WrapPayload::Withdraw(payload, _) => Some(payload.instruction),
+            WrapPayload::SetParams(payload, _) => Some(payload.instruction),
+            WrapPayload::Initialize(payload, _) => Some(payload.instruction),
+            WrapPayload::Failed(payload, _) => Some(payload.actions.first()?.instruction()),
+            WrapPayload::PumpAmmSwap(payload, _) => Some(payload.instruction),
+            WrapPayload::RaydiumSwap(payload, _) => Some(payload.instruction),
+            WrapPayload::TokenTransfer(payload, _) => Some(payload.instruction),
+            WrapPayload::Unknown => None,
+        }
+    }
+
+    /// The token name of a launch
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            WrapPayload::Create(payload, _) => Some(&payload.name),
+            WrapPayload::CreateBuy(payload, _) => Some(&payload.name),
+            _ => None,
+        }
+    }
+
+    /// Where and when the notification of the payload was received
+    pub fn envelope(&self) -> Option<&PayloadEnvelope> {
+        match self {
+            WrapPayload::Create(_, envelope) => Some(envelope),
+            WrapPayload::CreateBuy(_, envelope) => Some(envelope),
+            WrapPayload::Buy(_, envelope) => Some(envelope),
+            WrapPayload::Sell(_, envelope) => Some(envelope),
+            WrapPayload::BuySell(_, envelope) => Some(envelope),
+            WrapPayload::Withdraw(_, envelope) => Some(envelope),
+            WrapPayload::SetParams(_, envelope) => Some(envelope),
+            WrapPayload::Initialize(_, envelope) => Some(envelope),
+            WrapPayload::Failed(_, envelope) => Some(envelope),
+            WrapPayload::PumpAmmSwap(_, envelope) => Some(envelope),
+            WrapPayload::RaydiumSwap(_, envelope) => Some(envelope),
+            WrapPayload::TokenTransfer(_, envelope) => Some(envelope),
+            WrapPayload::Unknown => None,
+        }
+    }
+
+    pub fn envelope_mut(&mut self) -> Option<&mut PayloadEnvelope> {
+        match self {
+            WrapPayload::Create(_, envelope) => Some(envelope),
+            WrapPayload::CreateBuy(_, envelope) => Some(envelope),
+            WrapPayload::Buy(_, envelope) => Some(envelope),
+            WrapPayload::Sell(_, envelope) => Some(envelope),
+            WrapPayload::BuySell(_, envelope) => Some(envelope),
+            WrapPayload::Withdraw(_, envelope) => Some(envelope),
+            WrapPayload::SetParams(_, envelope) => Some(envelope),
+            WrapPayload::Initialize(_, envelope) => Some(envelope),
+            WrapPayload::Failed(_, envelope) => Some(envelope),
+            WrapPayload::PumpAmmSwap(_, envelope) => Some(envelope),
+            WrapPayload::RaydiumSwap(_, envelope) => Some(envelope),
+            WrapPayload::TokenTransfer(_, envelope) => Some(envelope),
+            WrapPayload::Unknown => None,
+        }
+    }
+}
+
+impl PayloadAction {
+    pub fn into_payload(self, envelope: PayloadEnvelope) -> WrapPayload {
+        match self {
+            PayloadAction::Create(create) => WrapPayload::Create(create, envelope),
+            PayloadAction::Buy(buy) => WrapPayload::Buy(buy, envelope),
+            PayloadAction::Sell(sell) => WrapPayload::Sell(sell, envelope),
+            PayloadAction::Withdraw(withdraw) => WrapPayload::Withdraw(withdraw, envelope),
+            PayloadAction::SetParams(set_params) => WrapPayload::SetParams(set_params, envelope),
+            PayloadAction::Initialize(initialize) => WrapPayload::Initialize(initialize, envelope),
+            PayloadAction::PumpAmmSwap(swap) => WrapPayload::PumpAmmSwap(swap, envelope),
+            PayloadAction::RaydiumSwap(swap) => WrapPayload::RaydiumSwap(swap, envelope),
+            PayloadAction::TokenTransfer(transfer) => {
+                WrapPayload::TokenTransfer(transfer, envelope)
+            }
+        }
+    }
+
+    fn mint_pk(&self) -> Option<&str> {
+        match self {
+            PayloadAction::Create(create) => Some(&create.mint_pk),
+            PayloadAction::Buy(buy) => Some(&buy.mint_pk),
+            PayloadAction::Sell(sell) => Some(&sell.mint_pk),
+            PayloadAction::Withdraw(withdraw) => Some(&withdraw.mint_pk),
+            PayloadAction::PumpAmmSwap(swap) => Some(&swap.base_mint),
+            PayloadAction::TokenTransfer(transfer) => transfer.mint.as_deref(),
+            PayloadAction::SetParams(_)
+            | PayloadAction::Initialize(_)
+            | PayloadAction::RaydiumSwap(_) => None,
+        }
+    }
+
+    fn instruction(&self) -> InstructionIndex {
+        match self {
+            PayloadAction::Create(create) => create.instruction,
+            PayloadAction::Buy(buy) => buy.instruction,
+            PayloadAction::Sell(sell) => sell.instruction,
+            PayloadAction::Withdraw(withdraw) => withdraw.instruction,
+            PayloadAction::SetParams(set_params) => set_params.instruction,
+            PayloadAction::Initialize(initialize) => initialize.instruction,
+            PayloadAction::PumpAmmSwap(swap) => swap.instruction,
+            PayloadAction::RaydiumSwap(swap) => swap.instruction,
+            PayloadAction::TokenTransfer(transfer) => transfer.instruction,
+        }
+    }
+}
+
+impl PayloadTransaction {
+    /// A token launch where the creator buys in the same transaction
+    pub fn is_create_buy(&self) -> bool {
+        self.actions.windows(2).any(|pair| match pair {
+            [PayloadAction::Create(create), PayloadAction::Buy(buy)] => {
+                create.mint_pk == buy.mint_pk
+            }
+            _ => false,
+        })
+    }
+
+    /// A buy followed by a sell of the same mint by the same user
+    pub fn is_round_trip(&self) -> bool {
+        self.actions.windows(2).any(|pair| match pair {
+            [PayloadAction::Buy(buy), PayloadAction::Sell(sell)] => {
+                buy.mint_pk == sell.mint_pk && buy.user_pk == sell.user_pk
+            }
+            _ => false,
+        })
+    }
+
+    /// Classify the actions into payloads, a Create followed by a Buy of the same mint becomes a
+    /// CreateBuy and a Buy followed by a Sell of the same mint and user becomes a BuySell.
+    /// A failed transaction is a single Failed payload. Every payload carries a copy of `envelope`.
+    pub fn into_payloads(self, envelope: &PayloadEnvelope) -> Vec<WrapPayload> {
+        // every trade event of a transaction carries the clock of its block
+        let trade_time = self
+            .actions
+            .iter()
+            .find_map(|action| match action {
+                PayloadAction::Create(create) => create.trade.as_ref(),
+                PayloadAction::Buy(buy) => buy.trade.as_ref(),
+                PayloadAction::Sell(sell) => sell.trade.as_ref(),
+                _ => None,
+            })
+            .map(|trade| trade.timestamp);
+        let envelope = &PayloadEnvelope {
+            block_time: envelope.block_time.or(trade_time),
+            ..envelope.clone()
+        };
+
+        if let Some(error) = self.error {
+            let failed = PayloadFailed {
+                slot: self.slot,
+                signature: self.signature,
+                actions: self.actions,
+                error,
+            };
+            return vec![WrapPayload::Failed(failed, envelope.clone())];
+        }
+
+        let mut payloads = vec![];
+        let mut actions = self.actions.into_iter().peekable();
+
+        while let Some(action) = actions.next() {
+            let payload = match action {
+                PayloadAction::Create(create) => match actions.next_if(
+                    |next| matches!(next, PayloadAction::Buy(buy) if buy.mint_pk == create.mint_pk),
+                ) {
+                    Some(PayloadAction::Buy(buy)) => WrapPayload::CreateBuy(
+                        PayloadCreateBuy {
+                            slot: create.slot,
+                            signature: create.signature,
+                            instruction: create.instruction,
+                            mint_pk: create.mint_pk,
+                            user_pk: create.user_pk,
+                            name: create.name,
+                            symbol: create.symbol,
+                            uri: create.uri,
+                            bonding_curve: create.bonding_curve,
+                            associated_bonding_curve: create.associated_bonding_curve,
+                            amount: buy.amount,
+                            max_sol_cost: buy.max_sol_cost,
+                            router: create.router,
+                            trade: buy.trade,
+                            balance: buy.balance,
+                            fees: create.fees,
+                        },
+                        envelope.clone(),
+                    ),
+                    _ => WrapPayload::Create(create, envelope.clone()),
+                },
+                PayloadAction::Buy(buy) => match actions.next_if(|next| {
+                    matches!(next, PayloadAction::Sell(sell)
+                        if sell.mint_pk == buy.mint_pk && sell.user_pk == buy.user_pk)
+                }) {
+                    Some(PayloadAction::Sell(sell)) => WrapPayload::BuySell(
+                        PayloadBuySell {
+                            slot: buy.slot,
+                            signature: buy.signature,
+                            instruction: buy.instruction,
+                            mint_pk: buy.mint_pk,
+                            user_pk: buy.user_pk,
+                            amount_buy: buy.amount,
+                            max_sol_cost: buy.max_sol_cost,
+                            amount_sell: sell.amount,
+                            min_sol_output: sell.min_sol_output,
+                            router: buy.router,
+                            trade_buy: buy.trade,
+                            trade_sell: sell.trade,
+                            fees: buy.fees,
+                        },
+                        envelope.clone(),
+                    ),
+                    _ => WrapPayload::Buy(buy, envelope.clone()),
+                },
+                action => action.into_payload(envelope.clone()),
+            };
+            payloads.push(payload);
+        }
+
+        payloads
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayloadCreate {
     pub slot: u64,
     pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
     pub mint_pk: String,
     pub user_pk: String,
     pub name: String,
@@ -41,12 +494,16 @@ pub struct PayloadCreate {
     pub uri: String,
     pub bonding_curve: String,
     pub associated_bonding_curve: String,
+    pub router: Option<String>,
+    pub fees: PayloadFees,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayloadCreateBuy {
     pub slot: u64,
     pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
     pub mint_pk: String,
     pub user_pk: String,
     pub name: String,
@@ -56,51 +513,143 @@ pub struct PayloadCreateBuy {
     pub associated_bonding_curve: String,
     pub amount: u64,
     pub max_sol_cost: u64,
+    pub router: Option<String>,
+    pub trade: Option<PayloadTrade>,
+    pub balance: Option<PayloadBalance>,
+    pub fees: PayloadFees,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayloadBuy {
     pub slot: u64,
     pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
     pub mint_pk: String,
     pub user_pk: String,
     pub amount: u64,
     pub max_sol_cost: u64,
+    pub router: Option<String>,
+    pub trade: Option<PayloadTrade>,
+    pub balance: Option<PayloadBalance>,
+    pub fees: PayloadFees,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayloadSell {
     pub slot: u64,
     pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
     pub mint_pk: String,
     pub user_pk: String,
     pub amount: u64,
     pub min_sol_output: u64,
+    pub router: Option<String>,
+    pub trade: Option<PayloadTrade>,
+    pub balance: Option<PayloadBalance>,
+    pub fees: PayloadFees,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayloadBuySell {
     pub slot: u64,
     pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
     pub mint_pk: String,
     pub user_pk: String,
     pub amount_buy: u64,
     pub max_sol_cost: u64,
     pub amount_sell: u64,
     pub min_sol_output: u64,
+    pub router: Option<String>,
+    pub trade_buy: Option<PayloadTrade>,
+    pub trade_sell: Option<PayloadTrade>,
+    pub fees: PayloadFees,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayloadWithdraw {
     slot: u64,
     signature: String,
+    #[serde(default)]
+    instruction: InstructionIndex,
     mint_pk: String,
+    router: Option<String>,
+    fees: PayloadFees,
+}
+
+impl PayloadCreateBuy {
+    /// Tokens the creator actually received: the trade event, then the balance change, then the
+    /// instruction amount
+    pub fn filled_token_amount(&self) -> u64 {
+        filled_amount(self.balance.as_ref(), self.trade.as_ref(), self.amount)
+    }
+}
+
+impl PayloadBuy {
+    /// Tokens actually received: the trade event, then the balance change, then the instruction
+    /// amount
+    pub fn filled_token_amount(&self) -> u64 {
+        filled_amount(self.balance.as_ref(), self.trade.as_ref(), self.amount)
+    }
+}
+
+impl PayloadSell {
+    /// Tokens actually sold: the trade event, then the balance change, then the instruction amount
+    pub fn filled_token_amount(&self) -> u64 {
+        filled_amount(self.balance.as_ref(), self.trade.as_ref(), self.amount)
+    }
+}
+
+// The trade event belongs to the instruction, the balance change to the whole transaction, which
+// can trade the same mint several times
+fn filled_amount(
+    balance: Option<&PayloadBalance>,
+    trade: Option<&PayloadTrade>,
+    amount: u64,
+) -> u64 {
+    match (trade, balance) {
+        (Some(trade), _) => trade.token_amount,
+        (_, Some(balance)) if balance.token_delta != 0 => balance.token_delta.unsigned_abs(),
+        _ => amount,
+    }
+}
+
+/// New global parameters set by the pump.fun authority
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadSetParams {
+    pub slot: u64,
+    pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
+    pub user_pk: String,
+    pub fee_recipient: String,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    pub fee_basis_points: u64,
+    pub router: Option<String>,
+    pub fees: PayloadFees,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadInitialize {
+    pub slot: u64,
+    pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
+    pub user_pk: String,
+    pub router: Option<String>,
+    pub fees: PayloadFees,
 }
 
 impl From<WrapPayload> for BatchCsvRecord {
     fn from(payload: WrapPayload) -> Self {
         match payload {
-            WrapPayload::Create(payload, _is_paper_trade) => BatchCsvRecord {
+            WrapPayload::Create(payload, envelope) => BatchCsvRecord {
                 action: "create".to_string(),
                 slot: payload.slot,
                 signature: payload.signature,
@@ -109,14 +658,20 @@ impl From<WrapPayload> for BatchCsvRecord {
                 name: Some(payload.name),
                 symbol: Some(payload.symbol),
                 uri: Some(payload.uri),
-                amount_buy: None,
-                max_sol_cost: None,
-                amount_sell: None,
-                min_sol_output: None,
                 bonding_curve: Some(payload.bonding_curve),
                 associated_bonding_curve: Some(payload.associated_bonding_curve),
+                router: payload.router,
+                cu_limit: payload.fees.cu_limit,
+                priority_fee_micro_lamports: payload.fees.priority_fee_micro_lamports,
+                jito_tip_lamports: Some(payload.fees.jito_tip_lamports),
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
             },
-            WrapPayload::CreateBuy(payload, _is_paper_trade) => BatchCsvRecord {
+            WrapPayload::CreateBuy(payload, envelope) => BatchCsvRecord {
                 action: "createbuy".to_string(),
                 slot: payload.slot,
                 signature: payload.signature,
@@ -127,146 +682,644 @@ impl From<WrapPayload> for BatchCsvRecord {
                 uri: Some(payload.uri),
                 amount_buy: Some(payload.amount),
                 max_sol_cost: Some(payload.max_sol_cost),
-                amount_sell: None,
-                min_sol_output: None,
                 bonding_curve: Some(payload.bonding_curve),
                 associated_bonding_curve: Some(payload.associated_bonding_curve),
+                router: payload.router,
+                sol_amount: payload.trade.as_ref().map(|t| t.sol_amount),
+                token_amount: payload.trade.as_ref().map(|t| t.token_amount),
+                virtual_sol_reserves: payload.trade.as_ref().map(|t| t.virtual_sol_reserves),
+                virtual_token_reserves: payload.trade.as_ref().map(|t| t.virtual_token_reserves),
+                trade_timestamp: payload.trade.as_ref().map(|t| t.timestamp),
+                token_delta: payload.balance.as_ref().map(|b| b.token_delta),
+                sol_delta: payload.balance.as_ref().map(|b| b.sol_delta),
+                cu_limit: payload.fees.cu_limit,
+                priority_fee_micro_lamports: payload.fees.priority_fee_micro_lamports,
+                jito_tip_lamports: Some(payload.fees.jito_tip_lamports),
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
             },
-            WrapPayload::Buy(payload, _is_paper_trade) => BatchCsvRecord {
+            WrapPayload::Buy(payload, envelope) => BatchCsvRecord {
                 action: "buy".to_string(),
                 slot: payload.slot,
                 signature: payload.signature,
                 mint_pk: Some(payload.mint_pk),
                 user_pk: Some(payload.user_pk),
-                name: None,
-                symbol: None,
-                uri: None,
                 amount_buy: Some(payload.amount),
                 max_sol_cost: Some(payload.max_sol_cost),
-                amount_sell: None,
-                min_sol_output: None,
-                bonding_curve: None,
-                associated_bonding_curve: None,
+                router: payload.router,
+                sol_amount: payload.trade.as_ref().map(|t| t.sol_amount),
+                token_amount: payload.trade.as_ref().map(|t| t.token_amount),
+                virtual_sol_reserves: payload.trade.as_ref().map(|t| t.virtual_sol_reserves),
+                virtual_token_reserves: payload.trade.as_ref().map(|t| t.virtual_token_reserves),
+                trade_timestamp: payload.trade.as_ref().map(|t| t.timestamp),
+                token_delta: payload.balance.as_ref().map(|b| b.token_delta),
+                sol_delta: payload.balance.as_ref().map(|b| b.sol_delta),
+                cu_limit: payload.fees.cu_limit,
+                priority_fee_micro_lamports: payload.fees.priority_fee_micro_lamports,
+                jito_tip_lamports: Some(payload.fees.jito_tip_lamports),
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
             },
-            WrapPayload::Sell(payload, _is_paper_trade) => BatchCsvRecord {
+            WrapPayload::Sell(payload, envelope) => BatchCsvRecord {
                 action: "sell".to_string(),
                 slot: payload.slot,
                 signature: payload.signature,
                 mint_pk: Some(payload.mint_pk),
                 user_pk: Some(payload.user_pk),
-                name: None,
-                symbol: None,
-                uri: None,
-                amount_buy: None,
-                max_sol_cost: None,
                 amount_sell: Some(payload.amount),
                 min_sol_output: Some(payload.min_sol_output),
-                bonding_curve: None,
-                associated_bonding_curve: None,
+                router: payload.router,
+                sol_amount: payload.trade.as_ref().map(|t| t.sol_amount),
+                token_amount: payload.trade.as_ref().map(|t| t.token_amount),
+                virtual_sol_reserves: payload.trade.as_ref().map(|t| t.virtual_sol_reserves),
+                virtual_token_reserves: payload.trade.as_ref().map(|t| t.virtual_token_reserves),
+                trade_timestamp: payload.trade.as_ref().map(|t| t.timestamp),
+                token_delta: payload.balance.as_ref().map(|b| b.token_delta),
+                sol_delta: payload.balance.as_ref().map(|b| b.sol_delta),
+                cu_limit: payload.fees.cu_limit,
+                priority_fee_micro_lamports: payload.fees.priority_fee_micro_lamports,
+                jito_tip_lamports: Some(payload.fees.jito_tip_lamports),
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
             },
-            WrapPayload::BuySell(payload, _is_paper_trade) => BatchCsvRecord {
+            WrapPayload::BuySell(payload, envelope) => BatchCsvRecord {
                 action: "buysell".to_string(),
                 slot: payload.slot,
                 signature: payload.signature,
                 mint_pk: Some(payload.mint_pk),
                 user_pk: Some(payload.user_pk),
-                name: None,
-                symbol: None,
-                uri: None,
                 amount_buy: Some(payload.amount_buy),
                 max_sol_cost: Some(payload.max_sol_cost),
                 amount_sell: Some(payload.amount_sell),
                 min_sol_output: Some(payload.min_sol_output),
-                bonding_curve: None,
-                associated_bonding_curve: None,
+                router: payload.router,
+                virtual_sol_reserves: payload.trade_sell.as_ref().map(|t| t.virtual_sol_reserves),
+                virtual_token_reserves: payload
+                    .trade_sell
+                    .as_ref()
+                    .map(|t| t.virtual_token_reserves),
+                trade_timestamp: payload.trade_sell.as_ref().map(|t| t.timestamp),
+                cu_limit: payload.fees.cu_limit,
+                priority_fee_micro_lamports: payload.fees.priority_fee_micro_lamports,
+                jito_tip_lamports: Some(payload.fees.jito_tip_lamports),
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
             },
-            WrapPayload::Withdraw(payload, _is_paper_trade) => BatchCsvRecord {
+            WrapPayload::Withdraw(payload, envelope) => BatchCsvRecord {
                 action: "withdraw".to_string(),
                 slot: payload.slot,
                 signature: payload.signature,
                 mint_pk: Some(payload.mint_pk),
-                user_pk: None,
-                name: None,
-                symbol: None,
-                uri: None,
-                amount_buy: None,
-                max_sol_cost: None,
-                amount_sell: None,
-                min_sol_output: None,
-                bonding_curve: None,
-                associated_bonding_curve: None,
+                router: payload.router,
+                cu_limit: payload.fees.cu_limit,
+                priority_fee_micro_lamports: payload.fees.priority_fee_micro_lamports,
+                jito_tip_lamports: Some(payload.fees.jito_tip_lamports),
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
+            },
+            WrapPayload::SetParams(payload, envelope) => BatchCsvRecord {
+                action: "setparams".to_string(),
+                slot: payload.slot,
+                signature: payload.signature,
+                user_pk: Some(payload.user_pk),
+                router: payload.router,
+                fee_recipient: Some(payload.fee_recipient),
+                fee_basis_points: Some(payload.fee_basis_points),
+                initial_virtual_token_reserves: Some(payload.initial_virtual_token_reserves),
+                initial_virtual_sol_reserves: Some(payload.initial_virtual_sol_reserves),
+                initial_real_token_reserves: Some(payload.initial_real_token_reserves),
+                token_total_supply: Some(payload.token_total_supply),
+                cu_limit: payload.fees.cu_limit,
+                priority_fee_micro_lamports: payload.fees.priority_fee_micro_lamports,
+                jito_tip_lamports: Some(payload.fees.jito_tip_lamports),
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
+            },
+            WrapPayload::Initialize(payload, envelope) => BatchCsvRecord {
+                action: "initialize".to_string(),
+                slot: payload.slot,
+                signature: payload.signature,
+                user_pk: Some(payload.user_pk),
+                router: payload.router,
+                cu_limit: payload.fees.cu_limit,
+                priority_fee_micro_lamports: payload.fees.priority_fee_micro_lamports,
+                jito_tip_lamports: Some(payload.fees.jito_tip_lamports),
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
+            },
+            WrapPayload::Failed(payload, envelope) => {
+                // the columns of the failed action, followed by the error
+                let action = payload.actions.into_iter().next();
+                let mut record: BatchCsvRecord = action
+                    .map_or(WrapPayload::Unknown, |action| action.into_payload(envelope))
+                    .into();
+                record.action = format!("failed_{}", record.action);
+                record.slot = payload.slot;
+                record.signature = payload.signature;
+                record.error_code = payload.error.error_code;
+                record.error_name = payload.error.error_name;
+                record.compute_units_consumed = payload.error.compute_units_consumed;
+                record
+            }
+            WrapPayload::PumpAmmSwap(payload, envelope) => BatchCsvRecord {
+                action: "pumpammswap".to_string(),
+                slot: payload.slot,
+                signature: payload.signature,
+                mint_pk: Some(payload.base_mint),
+                user_pk: Some(payload.user_pk),
+                amount_buy: payload.is_buy.then_some(payload.base_amount),
+                max_sol_cost: payload.is_buy.then_some(payload.quote_amount_limit),
+                amount_sell: (!payload.is_buy).then_some(payload.base_amount),
+                min_sol_output: (!payload.is_buy).then_some(payload.quote_amount_limit),
+                router: payload.router,
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
+            },
+            // the user pays amount_in of one token for amount_out of the other
+            WrapPayload::RaydiumSwap(payload, envelope) => BatchCsvRecord {
+                action: "raydiumswap".to_string(),
+                slot: payload.slot,
+                signature: payload.signature,
+                user_pk: Some(payload.user_pk),
+                amount_buy: Some(payload.amount_out),
+                amount_sell: Some(payload.amount_in),
+                router: payload.router,
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
+            },
+            WrapPayload::TokenTransfer(payload, envelope) => BatchCsvRecord {
+                action: "tokentransfer".to_string(),
+                slot: payload.slot,
+                signature: payload.signature,
+                mint_pk: payload.mint,
+                user_pk: Some(payload.authority),
+                token_amount: Some(payload.amount),
+                router: payload.router,
+                source: Some(envelope.source),
+                commitment: envelope.commitment,
+                received_at: Some(envelope.received_at),
+                block_time: envelope.block_time,
+                is_paper_trade: Some(envelope.is_paper_trade),
+                ..Default::default()
             },
             WrapPayload::Unknown => BatchCsvRecord {
                 action: "unknown".to_string(),
-                slot: 0,
-                signature: "".to_string(),
-                mint_pk: None,
-                user_pk: None,
-                name: None,
-                symbol: None,
-                uri: None,
-                amount_buy: None,
-                max_sol_cost: None,
-                amount_sell: None,
-                min_sol_output: None,
-                bonding_curve: None,
-                associated_bonding_curve: None,
+                ..Default::default()
             },
         }
     }
 }
 
+/// Parse a `--encoding` argument into a transaction subscribe encoding the parser can decode.
+pub fn parse_encoding(encoding: &str) -> Result<UiEnhancedTransactionEncoding, String> {
+    match encoding {
+        "jsonParsed" => Ok(UiEnhancedTransactionEncoding::JsonParsed),
+        "base58" => Ok(UiEnhancedTransactionEncoding::Base58),
+        "base64" => Ok(UiEnhancedTransactionEncoding::Base64),
+        _ => Err(format!(
+            "unsupported encoding {}, expected jsonParsed, base58 or base64",
+            encoding
+        )),
+    }
+}
+
+/// Parse a `--commitment` argument into a transaction subscribe commitment.
+pub fn parse_commitment(commitment: &str) -> Result<TransactionCommitment, String> {
+    match commitment {
+        "processed" => Ok(TransactionCommitment::Processed),
+        "confirmed" => Ok(TransactionCommitment::Confirmed),
+        "finalized" => Ok(TransactionCommitment::Finalized),
+        _ => Err(format!(
+            "unsupported commitment {}, expected processed, confirmed or finalized",
+            commitment
+        )),
+    }
+}
+
 pub fn parse_notification(
     notification: &TransactionNotification,
-    mint_pubkey: &str,
-) -> Option<WrapPayload> {
-    // 1. If the status is not OK, exit early.
-    // 2. If the program_id matches pump.fun, decode the notification and extract instructions. The following are the possible trading types:
+    program_id: &str,
+) -> Option<PayloadTransaction> {
+    parse_notification_with_options(notification, program_id, &ParseOptions::default())
+}
+
+pub fn parse_notification_with_options(
+    notification: &TransactionNotification,
+    program_id: &str,
+    options: &ParseOptions,
+) -> Option<PayloadTransaction> {
+    // 1. If the status is not OK, exit early, unless failed transactions are captured: then only
+    //    the failing instruction is decoded, together with the program error.
+    // 2. If the program_id matches pump.fun, decode the notification and extract instructions,
+    //    every pump.fun instruction becomes one action, in transaction order:
     // - Create ()
-    // - CreateBuy ()
     // - Buy ()
     // - Sell ()
-    // - BuySell ()
     // - Withdraw ()
+    // - SetParams ()
+    // - Initialize ()
+    // 3. Instructions invoked via CPI (trading bots, aggregators, Jupiter) are taken from the
+    //    inner instructions, and the outer program id is kept as the router.
+    // 4. The TradeEvents emitted by pump.fun carry the actual fills; they are matched to the
+    //    Buy/Sell instructions in order.
     let transaction = &notification.transaction.transaction;
 
-    let _transaction_meta = match &notification.transaction.meta {
-        Some(meta) if meta.status.is_ok() => meta,
+    let transaction_meta = match &notification.transaction.meta {
+        Some(meta) if meta.status.is_ok() || options.capture_failed => meta,
         _ => return None, // Early return if status is an error or meta is None
     };
+    let inner_instructions: Option<&Vec<UiInnerInstructions>> =
+        transaction_meta.inner_instructions.as_ref().into();
 
-    let mut wrap_instructions: Vec<WrapInstruction> = vec![];
+    let mut wrap_instructions: Vec<(InstructionIndex, WrapInstruction)> = vec![];
 
     let slot = notification.slot;
     let signature = &notification.signature;
 
-    // must be UiTransaction
-    if let EncodedTransaction::Json(ui_transaction) = transaction {
-        if let UiMessage::Parsed(ref ui_parsed_message) = ui_transaction.message {
-            for ui_instruction in &ui_parsed_message.instructions {
-                if let UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
-                    ui_partially_decoded_instruction,
-                )) = ui_instruction
-                {
-                    if ui_partially_decoded_instruction.program_id == mint_pubkey {
-                        if let Some(wrap_instruction) =
-                            decode_instruction(ui_partially_decoded_instruction, slot, signature)
-                        {
-                            wrap_instructions.push(wrap_instruction);
-                        }
-                    }
+    // jsonParsed, raw json and base58/base64 encodings all resolve to the same instructions
+    let (instructions, account_keys) = outer_instructions(transaction, transaction_meta)?;
+
+    let error = transaction_meta.err.as_ref().map(|err| {
+        let (instruction_index, error_code, error_name) = match err {
+            TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+                // anchor error codes are shared by every anchor program, a router failing on its
+                // own 6002 is not TooMuchSolRequired. A router propagates the error of the
+                // pump.fun instruction it invoked, only the logs tell which program failed.
+                let log_messages: Option<&Vec<String>> =
+                    transaction_meta.log_messages.as_ref().into();
+                let is_pumpfun = instructions
+                    .get(*index as usize)
+                    .and_then(|instruction| instruction_program_id(instruction, &account_keys))
+                    == Some(program_id)
+                    || log_messages
+                        .is_some_and(|logs| program_failed_with(logs, program_id, *code));
+                let error_name = is_pumpfun
+                    .then(|| pumpfun_error_name(*code))
+                    .flatten()
+                    .map(str::to_string);
+                (Some(*index), Some(*code), error_name)
+            }
+            TransactionError::InstructionError(index, instruction_error) => {
+                (Some(*index), None, Some(format!("{:?}", instruction_error)))
+            }
+            _ => (None, None, None),
+        };
+        PayloadError {
+            instruction_index,
+            error_code,
+            error_name,
+            error: format!("{:?}", err),
+            compute_units_consumed: transaction_meta.compute_units_consumed.clone().into(),
+        }
+    });
+
+    for (index, ui_instruction) in instructions.iter().enumerate() {
+        // a failed transaction is rolled back, only the instruction that failed is of interest
+        if let Some(PayloadError {
+            instruction_index: Some(failed_index),
+            ..
+        }) = &error
+        {
+            if *failed_index as usize != index {
+                continue;
+            }
+        }
+
+        let Some(outer_program_id) = instruction_program_id(ui_instruction, &account_keys) else {
+            continue;
+        };
+
+        if outer_program_id == program_id {
+            if let Some(wrap_instruction) =
+                decode_ui_instruction(ui_instruction, &account_keys, slot, signature, None)
+            {
+                let instruction = InstructionIndex {
+                    outer: index,
+                    inner: None,
+                };
+                wrap_instructions.push((instruction, wrap_instruction));
+            }
+            continue;
+        }
+
+        // pump.fun invoked via CPI by a router program
+        let Some(inner_instructions) = inner_instructions else {
+            continue;
+        };
+        for inner in inner_instructions
+            .iter()
+            .filter(|inner| inner.index as usize == index)
+        {
+            for (inner_index, inner_instruction) in inner.instructions.iter().enumerate() {
+                if instruction_program_id(inner_instruction, &account_keys) != Some(program_id) {
+                    continue;
+                }
+                if let Some(wrap_instruction) = decode_ui_instruction(
+                    inner_instruction,
+                    &account_keys,
+                    slot,
+                    signature,
+                    Some(outer_program_id),
+                ) {
+                    let instruction = InstructionIndex {
+                        outer: index,
+                        inner: Some(inner_index),
+                    };
+                    wrap_instructions.push((instruction, wrap_instruction));
                 }
             }
         }
     }
 
-    build_payload(wrap_instructions)
+    // no events are emitted by a failed transaction
+    let trade_events: Vec<TradeEvent> = parse_events(transaction_meta, &account_keys, program_id)
+        .into_iter()
+        .filter_map(|event| match event {
+            PumpfunEvent::Trade(trade_event) => Some(trade_event),
+            _ => None,
+        })
+        .collect();
+
+    let fees = parse_fees(&instructions, transaction_meta, &account_keys);
+    let mut actions = build_actions(wrap_instructions, trade_events, &fees);
+    if actions.is_empty() {
+        return None;
+    }
+    // a failed transaction only moves the fee
+    if error.is_none() {
+        attach_balances(&mut actions, transaction_meta, &account_keys);
+    }
+
+    Some(PayloadTransaction {
+        slot,
+        signature: signature.to_string(),
+        actions,
+        error,
+    })
+}
+
+/// Describe the instructions of `program_id` that carry a known discriminator but do not decode,
+/// e.g. after a program upgrade changed their layout. Instructions the parser does not know are
+/// not reported.
+pub fn undecodable_instructions(
+    notification: &TransactionNotification,
+    program_id: &str,
+) -> Vec<String> {
+    let Some(meta) = &notification.transaction.meta else {
+        return vec![];
+    };
+    let Some((instructions, account_keys)) =
+        outer_instructions(&notification.transaction.transaction, meta)
+    else {
+        return vec!["the transaction does not decode".to_string()];
+    };
+    let inner_instructions: Option<&Vec<UiInnerInstructions>> =
+        meta.inner_instructions.as_ref().into();
+    let inner_instructions = inner_instructions
+        .into_iter()
+        .flatten()
+        .flat_map(|inner| inner.instructions.iter());
+
+    instructions
+        .iter()
+        .chain(inner_instructions)
+        .filter(|i| instruction_program_id(i, &account_keys) == Some(program_id))
+        .filter_map(|ui_instruction| {
+            let instruction = resolve_instruction(ui_instruction, &account_keys)?;
+            let data = bs58::decode(instruction.data.as_str()).into_vec().ok()?;
+            let name = instruction_name(data.get(..8)?)?;
+            let decoded = decode_instruction(
+                &instruction,
+                notification.slot,
+                &notification.signature,
+                None,
+            );
+            match decoded {
+                Some(_) => None,
+                None => Some(format!(
+                    "{} instruction does not decode: {}",
+                    name, instruction.data
+                )),
+            }
+        })
+        .collect()
+}
+
+fn instruction_name(discriminator: &[u8]) -> Option<&'static str> {
+    match discriminator {
+        Create::DISCRIMINATOR => Some("create"),
+        Buy::DISCRIMINATOR => Some("buy"),
+        Sell::DISCRIMINATOR => Some("sell"),
+        Withdraw::DISCRIMINATOR => Some("withdraw"),
+        SetParams::DISCRIMINATOR => Some("setparams"),
+        Initialize::DISCRIMINATOR => Some("initialize"),
+        _ => None,
+    }
+}
+
+/// Set the balance change of the user on every Buy and Sell. With several trades of the same mint
+/// and user in a transaction, each of them carries the change of the whole transaction.
+fn attach_balances(
+    actions: &mut [PayloadAction],
+    meta: &UiTransactionStatusMeta,
+    account_keys: &[String],
+) {
+    for action in actions.iter_mut() {
+        match action {
+            PayloadAction::Buy(buy) => {
+                buy.balance = balance_change(meta, account_keys, &buy.mint_pk, &buy.user_pk);
+            }
+            PayloadAction::Sell(sell) => {
+                sell.balance = balance_change(meta, account_keys, &sell.mint_pk, &sell.user_pk);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Takes the first trade event matching the mint, user and direction.
+fn take_trade(
+    trade_events: &mut Vec<TradeEvent>,
+    mint_pk: &str,
+    user_pk: &str,
+    is_buy: bool,
+) -> Option<PayloadTrade> {
+    let position = trade_events.iter().position(|e| {
+        e.is_buy == is_buy && e.mint.to_string() == mint_pk && e.user.to_string() == user_pk
+    })?;
+    let trade_event = trade_events.remove(position);
+    Some(PayloadTrade::from(&trade_event))
+}
+
+/// The outer instructions of a transaction and the account keys their indexes refer to.
+pub(crate) fn outer_instructions<'a>(
+    transaction: &'a EncodedTransaction,
+    meta: &UiTransactionStatusMeta,
+) -> Option<(Cow<'a, [UiInstruction]>, Vec<String>)> {
+    match transaction {
+        EncodedTransaction::Json(ui_transaction) => match &ui_transaction.message {
+            UiMessage::Parsed(message) => {
+                // jsonParsed already lists the lookup table addresses in the account keys
+                let account_keys = message
+                    .account_keys
+                    .iter()
+                    .map(|account| account.pubkey.clone())
+                    .collect();
+                Some((Cow::Borrowed(&message.instructions), account_keys))
+            }
+            UiMessage::Raw(message) => {
+                let instructions = message
+                    .instructions
+                    .iter()
+                    .cloned()
+                    .map(UiInstruction::Compiled)
+                    .collect();
+                let account_keys = transaction_account_keys(message.account_keys.clone(), meta);
+                Some((Cow::Owned(instructions), account_keys))
+            }
+        },
+        EncodedTransaction::LegacyBinary(_) | EncodedTransaction::Binary(_, _) => {
+            let versioned_transaction = transaction.decode()?;
+            let message = &versioned_transaction.message;
+            let instructions = message
+                .instructions()
+                .iter()
+                .map(|i| {
+                    UiInstruction::Compiled(UiCompiledInstruction {
+                        program_id_index: i.program_id_index,
+                        accounts: i.accounts.clone(),
+                        data: bs58::encode(&i.data).into_string(),
+                        stack_height: None,
+                    })
+                })
+                .collect();
+            let static_keys = message
+                .static_account_keys()
+                .iter()
+                .map(|key| key.to_string())
+                .collect();
+            let account_keys = transaction_account_keys(static_keys, meta);
+            Some((Cow::Owned(instructions), account_keys))
+        }
+        EncodedTransaction::Accounts(_) => None,
+    }
+}
+
+/// Static account keys followed by the writable and readonly addresses loaded from lookup tables,
+/// the order compiled instruction indexes are resolved against.
+pub fn transaction_account_keys(
+    mut static_keys: Vec<String>,
+    meta: &UiTransactionStatusMeta,
+) -> Vec<String> {
+    let loaded_addresses: Option<&UiLoadedAddresses> = meta.loaded_addresses.as_ref().into();
+    if let Some(loaded_addresses) = loaded_addresses {
+        static_keys.extend(loaded_addresses.writable.iter().cloned());
+        static_keys.extend(loaded_addresses.readonly.iter().cloned());
+    }
+    static_keys
+}
+
+/// Resolve an instruction to its program id, accounts and bs58 data. Instructions parsed by the
+/// RPC (system, token, ...) have no raw data and are skipped.
+pub fn resolve_instruction<'a>(
+    ui_instruction: &'a UiInstruction,
+    account_keys: &[String],
+) -> Option<Cow<'a, UiPartiallyDecodedInstruction>> {
+    match ui_instruction {
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(i)) => Some(Cow::Borrowed(i)),
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(_)) => None,
+        UiInstruction::Compiled(i) => {
+            let program_id = account_keys.get(i.program_id_index as usize)?.clone();
+            let accounts = i
+                .accounts
+                .iter()
+                .map(|index| account_keys.get(*index as usize).cloned())
+                .collect::<Option<Vec<String>>>()?;
+            Some(Cow::Owned(UiPartiallyDecodedInstruction {
+                program_id,
+                accounts,
+                data: i.data.clone(),
+                stack_height: i.stack_height,
+            }))
+        }
+    }
+}
+
+// The runtime logs `Program <id> failed: custom program error: 0x<code>` for the program that
+// failed, then for each program up the CPI stack
+fn program_failed_with(log_messages: &[String], program_id: &str, code: u32) -> bool {
+    let failure = format!(
+        "Program {} failed: custom program error: {:#x}",
+        program_id, code
+    );
+    log_messages.iter().any(|log| *log == failure)
+}
+
+pub(crate) fn instruction_program_id<'a>(
+    ui_instruction: &'a UiInstruction,
+    account_keys: &'a [String],
+) -> Option<&'a str> {
+    match ui_instruction {
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(i)) => Some(&i.program_id),
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(i)) => Some(&i.program_id),
+        UiInstruction::Compiled(i) => account_keys
+            .get(i.program_id_index as usize)
+            .map(String::as_str),
+    }
+}
+
+fn decode_ui_instruction(
+    ui_instruction: &UiInstruction,
+    account_keys: &[String],
+    slot: u64,
+    signature: &str,
+    router: Option<&str>,
+) -> Option<WrapInstruction> {
+    let instruction = resolve_instruction(ui_instruction, account_keys)?;
+    decode_instruction(&instruction, slot, signature, router)
 }
 
 fn decode_instruction(
     i: &UiPartiallyDecodedInstruction,
     slot: u64,
     signature: &str,
+    router: Option<&str>,
 ) -> Option<WrapInstruction> {
     if i.data.is_empty() {
         return None;
@@ -302,6 +1355,7 @@ fn decode_instruction(
                 user_pk,
                 bonding_curve,
                 associated_bonding_curve,
+                router.map(str::to_string),
             ))
         }
         Buy::DISCRIMINATOR => {
@@ -315,6 +1369,7 @@ fn decode_instruction(
                 signature.to_string(),
                 mint_pk,
                 user_pk,
+                router.map(str::to_string),
             ))
         }
         Sell::DISCRIMINATOR => {
@@ -328,6 +1383,7 @@ fn decode_instruction(
                 signature.to_string(),
                 mint_pk,
                 user_pk,
+                router.map(str::to_string),
             ))
         }
         Withdraw::DISCRIMINATOR => {
@@ -338,6 +1394,31 @@ fn decode_instruction(
                 slot,
                 signature.to_string(),
                 mint_pk,
+                router.map(str::to_string),
+            ))
+        }
+        SetParams::DISCRIMINATOR => {
+            // _fee_recipient, _initial_virtual_token_reserves, _initial_virtual_sol_reserves,
+            // _initial_real_token_reserves, _token_total_supply, _fee_basis_points
+            let instruction = SetParams::try_from_slice(&data[8..]).ok()?;
+            let user_pk = i.accounts.get(1)?.to_string();
+            Some(WrapInstruction::SetParams(
+                instruction,
+                slot,
+                signature.to_string(),
+                user_pk,
+                router.map(str::to_string),
+            ))
+        }
+        Initialize::DISCRIMINATOR => {
+            let instruction = Initialize::try_from_slice(&data[8..]).ok()?;
+            let user_pk = i.accounts.get(1)?.to_string();
+            Some(WrapInstruction::Initialize(
+                instruction,
+                slot,
+                signature.to_string(),
+                user_pk,
+                router.map(str::to_string),
             ))
         }
         _ => {
@@ -347,10 +1428,15 @@ fn decode_instruction(
     }
 }
 
-fn build_payload(wrap_instructions: Vec<WrapInstruction>) -> Option<WrapPayload> {
-    if wrap_instructions.len() == 1 {
-        let wrap_instruction = wrap_instructions.index(0);
-        match wrap_instruction {
+fn build_actions(
+    wrap_instructions: Vec<(InstructionIndex, WrapInstruction)>,
+    mut trade_events: Vec<TradeEvent>,
+    fees: &PayloadFees,
+) -> Vec<PayloadAction> {
+    let mut actions = Vec::with_capacity(wrap_instructions.len());
+
+    for (index, wrap_instruction) in wrap_instructions {
+        let action = match wrap_instruction {
             WrapInstruction::Create(
                 instruction,
                 slot,
@@ -359,125 +1445,102 @@ fn build_payload(wrap_instructions: Vec<WrapInstruction>) -> Option<WrapPayload>
                 user_pk,
                 bonding_curve,
                 associated_bonding_curve,
-            ) => {
-                let wrap_payload = WrapPayload::Create(
-                    PayloadCreate {
-                        slot: *slot,
-                        signature: signature.to_string(),
-                        mint_pk: mint_pk.to_string(),
-                        user_pk: user_pk.to_string(),
-                        name: instruction._name.to_string(),
-                        symbol: instruction._symbol.to_string(),
-                        uri: instruction._uri.to_string(),
-                        bonding_curve: bonding_curve.to_string(),
-                        associated_bonding_curve: associated_bonding_curve.to_string(),
-                    },
-                    false,
-                );
-                return Some(wrap_payload);
-            }
-            WrapInstruction::Buy(instruction, slot, signature, mint_pk, user_pk) => {
-                let wrap_payload = WrapPayload::Buy(
-                    PayloadBuy {
-                        slot: *slot,
-                        signature: signature.to_string(),
-                        mint_pk: mint_pk.to_string(),
-                        user_pk: user_pk.to_string(),
-                        amount: instruction._amount,
-                        max_sol_cost: instruction._max_sol_cost,
-                    },
-                    false,
-                );
-                return Some(wrap_payload);
-            }
-            WrapInstruction::Sell(instruction, slot, signature, mint_pk, user_pk) => {
-                let wrap_payload = WrapPayload::Sell(
-                    PayloadSell {
-                        slot: *slot,
-                        signature: signature.to_string(),
-                        mint_pk: mint_pk.to_string(),
-                        user_pk: user_pk.to_string(),
-                        amount: instruction._amount,
-                        min_sol_output: instruction._min_sol_output,
-                    },
-                    false,
-                );
-                return Some(wrap_payload);
+                router,
+            ) => PayloadAction::Create(PayloadCreate {
+                slot,
+                signature,
+                instruction: index,
+                mint_pk,
+                user_pk,
+                name: instruction._name,
+                symbol: instruction._symbol,
+                uri: instruction._uri,
+                bonding_curve,
+                associated_bonding_curve,
+                router,
+                fees: fees.clone(),
+            }),
+            WrapInstruction::Buy(instruction, slot, signature, mint_pk, user_pk, router) => {
+                let trade = take_trade(&mut trade_events, &mint_pk, &user_pk, true);
+                PayloadAction::Buy(PayloadBuy {
+                    slot,
+                    signature,
+                    instruction: index,
+                    mint_pk,
+                    user_pk,
+                    amount: instruction._amount,
+                    max_sol_cost: instruction._max_sol_cost,
+                    router,
+                    trade,
+                    balance: None,
+                    fees: fees.clone(),
+                })
             }
-            WrapInstruction::Withdraw(_instruction, slot, signature, mint_pk) => {
-                let wrap_payload = WrapPayload::Withdraw(
-                    PayloadWithdraw {
-                        slot: *slot,
-                        signature: signature.to_string(),
-                        mint_pk: mint_pk.to_string(),
-                    },
-                    false,
-                );
-                return Some(wrap_payload);
+            WrapInstruction::Sell(instruction, slot, signature, mint_pk, user_pk, router) => {
+                let trade = take_trade(&mut trade_events, &mint_pk, &user_pk, false);
+                PayloadAction::Sell(PayloadSell {
+                    slot,
+                    signature,
+                    instruction: index,
+                    mint_pk,
+                    user_pk,
+                    amount: instruction._amount,
+                    min_sol_output: instruction._min_sol_output,
+                    router,
+                    trade,
+                    balance: None,
+                    fees: fees.clone(),
+                })
             }
-        }
-    } else if wrap_instructions.len() == 2 {
-        match (&wrap_instructions[0], &wrap_instructions[1]) {
-            (
-                WrapInstruction::Create(
-                    create_instruction,
+            WrapInstruction::Withdraw(_instruction, slot, signature, mint_pk, router) => {
+                PayloadAction::Withdraw(PayloadWithdraw {
                     slot,
                     signature,
+                    instruction: index,
                     mint_pk,
+                    router,
+                    fees: fees.clone(),
+                })
+            }
+            WrapInstruction::SetParams(instruction, slot, signature, user_pk, router) => {
+                PayloadAction::SetParams(PayloadSetParams {
+                    slot,
+                    signature,
+                    instruction: index,
                     user_pk,
-                    bonding_curve,
-                    associated_bonding_curve,
-                ),
-                WrapInstruction::Buy(buy_instruction, _, _, _, _),
-            ) => {
-                let wrap_payload = WrapPayload::CreateBuy(
-                    PayloadCreateBuy {
-                        slot: *slot,
-                        signature: signature.to_string(),
-                        mint_pk: mint_pk.to_string(),
-                        user_pk: user_pk.to_string(),
-                        name: create_instruction._name.to_string(),
-                        symbol: create_instruction._symbol.to_string(),
-                        uri: create_instruction._uri.to_string(),
-                        bonding_curve: bonding_curve.to_string(),
-                        associated_bonding_curve: associated_bonding_curve.to_string(),
-                        amount: buy_instruction._amount,
-                        max_sol_cost: buy_instruction._max_sol_cost,
-                    },
-                    false,
-                );
-                return Some(wrap_payload);
+                    fee_recipient: instruction._fee_recipient.to_string(),
+                    initial_virtual_token_reserves: instruction._initial_virtual_token_reserves,
+                    initial_virtual_sol_reserves: instruction._initial_virtual_sol_reserves,
+                    initial_real_token_reserves: instruction._initial_real_token_reserves,
+                    token_total_supply: instruction._token_total_supply,
+                    fee_basis_points: instruction._fee_basis_points,
+                    router,
+                    fees: fees.clone(),
+                })
             }
-            (
-                WrapInstruction::Buy(buy_instruction, slot, signature, mint_pk, user_pk),
-                WrapInstruction::Sell(sell_instruction, _, _, _, _),
-            ) => {
-                let wrap_payload = WrapPayload::BuySell(
-                    PayloadBuySell {
-                        slot: *slot,
-                        signature: signature.to_string(),
-                        mint_pk: mint_pk.to_string(),
-                        user_pk: user_pk.to_string(),
-                        amount_buy: buy_instruction._amount,
-                        max_sol_cost: buy_instruction._max_sol_cost,
-                        amount_sell: sell_instruction._amount,
-                        min_sol_output: sell_instruction._min_sol_output,
-                    },
-                    false,
-                );
-                return Some(wrap_payload);
+            WrapInstruction::Initialize(_instruction, slot, signature, user_pk, router) => {
+                PayloadAction::Initialize(PayloadInitialize {
+                    slot,
+                    signature,
+                    instruction: index,
+                    user_pk,
+                    router,
+                    fees: fees.clone(),
+                })
             }
-            _ => {}
-        }
+        };
+        actions.push(action);
     }
 
-    None
+    actions
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use anchor_lang::InstructionData;
+    use solana_transaction_status::option_serializer::OptionSerializer;
+    use std::str::FromStr;
     #[test]
     fn test_decode_base58_instruction() {
         let data = "AJTQ2h9DXrBkuDAB9Uhp439tdAdGa6cwZ";
@@ -569,6 +1632,7 @@ mod tests {
         let payload = PayloadCreate {
             slot: 307478317,
             signature: "2b1yDctRarzN5DTmLeYnZeBMwe3xNJxtc5mDQ8yNMJjgLJVA4VAX4AwnRynvLg7jXhHxQzH9pWy9wKGb5mwTatZD".to_string(),
+            instruction: InstructionIndex::default(),
             mint_pk: "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump".to_string(),
             user_pk: "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47".to_string(),
             name: "CHESS".to_string(),
@@ -576,6 +1640,8 @@ mod tests {
             uri: "test_uri".to_string(),
             bonding_curve: "8PkzhXamH8CkaxgGwjbi9HkkSjjA85bSbxmKhSKNbP98".to_string(),
             associated_bonding_curve: "B9LZLQv8eCAswjM8QZHeJpMS1PiLjrnDMvDZRDPVsRAA".to_string(),
+            router: None,
+            fees: PayloadFees::default(),
         };
 
         let serialized_payload = serde_json::to_string(&payload).unwrap();
@@ -592,10 +1658,15 @@ mod tests {
         let payload = PayloadBuy {
             slot: 307478317,
             signature: "2b1yDctRarzN5DTmLeYnZeBMwe3xNJxtc5mDQ8yNMJjgLJVA4VAX4AwnRynvLg7jXhHxQzH9pWy9wKGb5mwTatZD".to_string(),
+            instruction: InstructionIndex::default(),
             mint_pk: "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump".to_string(),
             user_pk: "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47".to_string(),
             amount: 35758322578,
             max_sol_cost: 37546238706,
+            router: None,
+            trade: None,
+            balance: None,
+            fees: PayloadFees::default(),
         };
 
         let serialized_payload = serde_json::to_string(&payload).unwrap();
@@ -608,10 +1679,15 @@ mod tests {
         let payload = PayloadSell {
             slot: 307478317,
             signature: "2b1yDctRarzN5DTmLeYnZeBMwe3xNJxtc5mDQ8yNMJjgLJVA4VAX4AwnRynvLg7jXhHxQzH9pWy9wKGb5mwTatZD".to_string(),
+            instruction: InstructionIndex::default(),
             mint_pk: "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump".to_string(),
             user_pk: "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47".to_string(),
             amount: 71523000000,
-            min_sol_output: 1940508
+            min_sol_output: 1940508,
+            router: None,
+            trade: None,
+            balance: None,
+            fees: PayloadFees::default(),
         };
 
         let serialized_payload = serde_json::to_string(&payload).unwrap();
@@ -628,7 +1704,10 @@ mod tests {
         let payload = PayloadWithdraw {
             slot: 308127030,
             signature: "5xdZk2LczUzW342aqxn9J2zCnXX4f4X8dCGUx3ekrBQ9CXsbfsdsCHeSve99g6V2bitDXrmKDza7enbTyAVgR4oz".to_string(),
+            instruction: InstructionIndex::default(),
             mint_pk: "86go6bCbiKz5gP1MZ4ERHyJMEm9gYuZoWvvgAji6mpAV".to_string(),
+            router: None,
+            fees: PayloadFees::default(),
         };
 
         let serialized_payload = serde_json::to_string(&payload).unwrap();
@@ -636,4 +1715,542 @@ mod tests {
             serde_json::from_str(&serialized_payload);
         assert!(deserialized_payload.is_ok());
     }
+
+    const ROUTER_PROGRAM_ID: &str = "routeUGWgWzqBWFcrCfv8tritsqukccJPu3q5GPP3xS";
+
+    fn buy_accounts() -> Vec<&'static str> {
+        vec![
+            "4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf", // global
+            "CebN5WGQ4jvEPvsVU4EoHEpgzq1VV7AbicfhtW4xC9iM", // fee_recipient
+            "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump", // mint
+            "8PkzhXamH8CkaxgGwjbi9HkkSjjA85bSbxmKhSKNbP98", // bonding_curve
+            "B9LZLQv8eCAswjM8QZHeJpMS1PiLjrnDMvDZRDPVsRAA", // associated_bonding_curve
+            "2SX6nwmusAzWTJkzawCiWM4cxZxBBQjLYeaVUhj8E5hx", // associated_user
+            "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47", // user
+            "11111111111111111111111111111111",
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            "SysvarRent111111111111111111111111111111111",
+            "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1", // event_authority
+            PUMPFUN_PROGRAM_ID,
+        ]
+    }
+
+    // A buy routed through a trading-bot program, pump.fun only shows up in the inner instructions
+    fn cpi_buy_notification() -> TransactionNotification {
+        serde_json::from_value(serde_json::json!({
+            "transaction": {
+                "transaction": {
+                    "signatures": ["2b1yDctRarzN5DTmLeYnZeBMwe3xNJxtc5mDQ8yNMJjgLJVA4VAX4AwnRynvLg7jXhHxQzH9pWy9wKGb5mwTatZD"],
+                    "message": {
+                        "accountKeys": [
+                            {"pubkey": "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47", "writable": true, "signer": true, "source": "transaction"}
+                        ],
+                        "recentBlockhash": "EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N",
+                        "instructions": [
+                            {
+                                "programId": ROUTER_PROGRAM_ID,
+                                "accounts": buy_accounts(),
+                                "data": "3Bxs4h24hBtQy9rw",
+                                "stackHeight": null
+                            }
+                        ]
+                    }
+                },
+                "meta": {
+                    "err": null,
+                    "status": {"Ok": null},
+                    "fee": 5000,
+                    "preBalances": [1000000000],
+                    "postBalances": [962448761],
+                    "innerInstructions": [
+                        {
+                            "index": 0,
+                            "instructions": [
+                                {
+                                    "programId": PUMPFUN_PROGRAM_ID,
+                                    "accounts": buy_accounts(),
+                                    "data": "AJTQ2h9DXrBwFqfGgyfiEhbWWqrqmcZmH",
+                                    "stackHeight": 2
+                                },
+                                {
+                                    "programId": PUMPFUN_PROGRAM_ID,
+                                    "accounts": ["Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1"],
+                                    "data": "2K7nL28PxCW8ejnyCeuMpbWpGM7NMMkahXEwHRxiLUwMeKZ6DXwYcaV2GY3MHnpb9jLqZNSPfvdjCzv232JNFGg84H4NJmfoJjfS7Pqot9RYhdg3BzB68SXjEWFCLwLpPsnxuhCpkPBVZaPYVkiCJKd9FxWYge6Px3gPLp8iwkA24vf4hvR4Z7hRUCdM",
+                                    "stackHeight": 3
+                                }
+                            ]
+                        }
+                    ]
+                },
+                "version": 0
+            },
+            "signature": "2b1yDctRarzN5DTmLeYnZeBMwe3xNJxtc5mDQ8yNMJjgLJVA4VAX4AwnRynvLg7jXhHxQzH9pWy9wKGb5mwTatZD",
+            "slot": 307478317
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_notification_cpi_buy() {
+        let notification = cpi_buy_notification();
+
+        let transaction = parse_notification(&notification, PUMPFUN_PROGRAM_ID).unwrap();
+        assert_eq!(1, transaction.actions.len());
+
+        let mut wrap_payloads = transaction.into_payloads(&envelope());
+        match wrap_payloads.pop() {
+            Some(WrapPayload::Buy(payload, _)) => {
+                assert_eq!(
+                    "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump",
+                    payload.mint_pk
+                );
+                assert_eq!(
+                    "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47",
+                    payload.user_pk
+                );
+                assert_eq!(35758322578, payload.amount);
+                assert_eq!(37546238706, payload.max_sol_cost);
+                assert_eq!(Some(ROUTER_PROGRAM_ID.to_string()), payload.router);
+                assert_eq!(
+                    InstructionIndex {
+                        outer: 0,
+                        inner: Some(0)
+                    },
+                    payload.instruction
+                );
+
+                let trade = payload.trade.expect("trade event");
+                assert!(trade.is_buy);
+                assert_eq!(37546238, trade.sol_amount);
+                assert_eq!(35758322578, trade.token_amount);
+                assert_eq!(30037546238, trade.virtual_sol_reserves);
+            }
+            other => panic!("expected a routed buy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_undecodable_instructions() {
+        let notification = cpi_buy_notification();
+        assert!(undecodable_instructions(&notification, PUMPFUN_PROGRAM_ID).is_empty());
+
+        // a buy cut short after the amount
+        let mut value = serde_json::to_value(&notification).unwrap();
+        let data = bs58::decode("AJTQ2h9DXrBwFqfGgyfiEhbWWqrqmcZmH")
+            .into_vec()
+            .unwrap();
+        value["transaction"]["meta"]["innerInstructions"][0]["instructions"][0]["data"] =
+            bs58::encode(&data[..16]).into_string().into();
+        let notification: TransactionNotification = serde_json::from_value(value).unwrap();
+
+        let undecodable = undecodable_instructions(&notification, PUMPFUN_PROGRAM_ID);
+        assert_eq!(1, undecodable.len());
+        assert!(undecodable[0].starts_with("buy instruction does not decode"));
+        assert!(parse_notification(&notification, PUMPFUN_PROGRAM_ID).is_none());
+    }
+
+    // The same buy as a base64 v0 transaction, every pump.fun account except the program and the
+    // user is loaded from an address lookup table
+    fn base64_alt_buy_notification() -> TransactionNotification {
+        serde_json::from_value(serde_json::json!({
+            "transaction": {
+                "transaction": [
+                    "AQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAACAAQABArz0AiIxBv7y5/cqAUZhZQNU9bhqc4JuG42WRlN9IT64AVbg9pNmWs9E2xVovxdbqlGJy5f10v87ZV0rtv1tGLDMSQ6SjNLjhzuzQ/yV2jMXnKYPTb9GwsNukSmdVdTmuQEBDAYCBwMEBQAICQoLARhmBj0SAdrr6pKzXFMIAAAA8iLuvQgAAAABNDLKbI5DGQAvM/OsWoeWp0wfXidkRiz/FKqN1m5CUzMEAAECAwYEBQYHCAk=",
+                    "base64"
+                ],
+                "meta": {
+                    "err": null,
+                    "status": {"Ok": null},
+                    "fee": 5000,
+                    "preBalances": [1000000000],
+                    "postBalances": [962448761],
+                    "innerInstructions": [
+                        {
+                            "index": 0,
+                            "instructions": [
+                                {
+                                    "programIdIndex": 1,
+                                    "accounts": [11],
+                                    "data": "2K7nL28PxCW8ejnyCeuMpbWpGM7NMMkahXEwHRxiLUwMeKZ6DXwYcaV2GY3MHnpb9jLqZNSPfvdjCzv232JNFGg84H4NJmfoJjfS7Pqot9RYhdg3BzB68SXjEWFCLwLpPsnxuhCpkPBVZaPYVkiCJKd9FxWYge6Px3gPLp8iwkA24vf4hvR4Z7hRUCdM",
+                                    "stackHeight": 2
+                                }
+                            ]
+                        }
+                    ],
+                    "loadedAddresses": {
+                        "writable": [
+                            "CebN5WGQ4jvEPvsVU4EoHEpgzq1VV7AbicfhtW4xC9iM",
+                            "8PkzhXamH8CkaxgGwjbi9HkkSjjA85bSbxmKhSKNbP98",
+                            "B9LZLQv8eCAswjM8QZHeJpMS1PiLjrnDMvDZRDPVsRAA",
+                            "2SX6nwmusAzWTJkzawCiWM4cxZxBBQjLYeaVUhj8E5hx"
+                        ],
+                        "readonly": [
+                            "4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf",
+                            "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump",
+                            "11111111111111111111111111111111",
+                            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                            "SysvarRent111111111111111111111111111111111",
+                            "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1"
+                        ]
+                    }
+                },
+                "version": 0
+            },
+            "signature": "2b1yDctRarzN5DTmLeYnZeBMwe3xNJxtc5mDQ8yNMJjgLJVA4VAX4AwnRynvLg7jXhHxQzH9pWy9wKGb5mwTatZD",
+            "slot": 307478317
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_notification_base64_lookup_table() {
+        let notification = base64_alt_buy_notification();
+
+        let transaction = parse_notification(&notification, PUMPFUN_PROGRAM_ID).unwrap();
+        match &transaction.actions[..] {
+            [PayloadAction::Buy(payload)] => {
+                assert_eq!(
+                    "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump",
+                    payload.mint_pk
+                );
+                assert_eq!(
+                    "DibT4jmj4HnMmdwxPaQt4kkRHX5S427d2oqe2cVTnp47",
+                    payload.user_pk
+                );
+                assert_eq!(35758322578, payload.amount);
+                assert_eq!(37546238706, payload.max_sol_cost);
+                assert_eq!(None, payload.router);
+                assert_eq!(
+                    Some(35758322578),
+                    payload.trade.as_ref().map(|t| t.token_amount)
+                );
+            }
+            other => panic!("expected a single buy, got {:?}", other),
+        }
+    }
+
+    fn envelope() -> PayloadEnvelope {
+        PayloadEnvelope::new(
+            PayloadSource::HeliusWs,
+            Some(TransactionCommitment::Confirmed),
+        )
+    }
+
+    fn create_action(mint_pk: &str, user_pk: &str) -> PayloadAction {
+        PayloadAction::Create(PayloadCreate {
+            slot: 308319709,
+            signature: "bundle".to_string(),
+            instruction: InstructionIndex::default(),
+            mint_pk: mint_pk.to_string(),
+            user_pk: user_pk.to_string(),
+            name: "GenZilla".to_string(),
+            symbol: "GENZ".to_string(),
+            uri: "test_uri".to_string(),
+            bonding_curve: "f9LWJCDCmKW2F3JmxoaBTdWxfK8z7EfYhATDGr5ct6R".to_string(),
+            associated_bonding_curve: "pCzEfmz3Z5hVyLVnacK29Fx5jMFgPdi7cogQrdt2ukT".to_string(),
+            router: None,
+            fees: PayloadFees::default(),
+        })
+    }
+
+    fn buy_action(mint_pk: &str, user_pk: &str) -> PayloadAction {
+        PayloadAction::Buy(PayloadBuy {
+            slot: 308319709,
+            signature: "bundle".to_string(),
+            instruction: InstructionIndex::default(),
+            mint_pk: mint_pk.to_string(),
+            user_pk: user_pk.to_string(),
+            amount: 35758322578,
+            max_sol_cost: 37546238706,
+            router: None,
+            trade: None,
+            balance: None,
+            fees: PayloadFees::default(),
+        })
+    }
+
+    fn sell_action(mint_pk: &str, user_pk: &str) -> PayloadAction {
+        PayloadAction::Sell(PayloadSell {
+            slot: 308319709,
+            signature: "bundle".to_string(),
+            instruction: InstructionIndex::default(),
+            mint_pk: mint_pk.to_string(),
+            user_pk: user_pk.to_string(),
+            amount: 71523000000,
+            min_sol_output: 1940508,
+            router: None,
+            trade: None,
+            balance: None,
+            fees: PayloadFees::default(),
+        })
+    }
+
+    #[test]
+    fn test_payload_transaction_bundle() {
+        // a bundled launch: create + creator buy, a sniper buy, and a flip on another mint
+        let transaction = PayloadTransaction {
+            slot: 308319709,
+            signature: "bundle".to_string(),
+            actions: vec![
+                create_action("mintA", "creator"),
+                buy_action("mintA", "creator"),
+                buy_action("mintA", "sniper"),
+                buy_action("mintB", "flipper"),
+                sell_action("mintB", "flipper"),
+            ],
+            error: None,
+        };
+        assert_eq!(5, transaction.actions.len());
+        assert!(transaction.is_create_buy());
+        assert!(transaction.is_round_trip());
+
+        let wrap_payloads = transaction.into_payloads(&envelope());
+        assert_eq!(3, wrap_payloads.len());
+        assert!(
+            matches!(&wrap_payloads[0], WrapPayload::CreateBuy(p, _) if p.user_pk == "creator")
+        );
+        assert!(matches!(&wrap_payloads[1], WrapPayload::Buy(p, _) if p.user_pk == "sniper"));
+        assert!(matches!(&wrap_payloads[2], WrapPayload::BuySell(p, _) if p.mint_pk == "mintB"));
+    }
+
+    #[test]
+    fn test_payload_transaction_unpaired() {
+        // a sell by another user and buys of different mints are not merged
+        let transaction = PayloadTransaction {
+            slot: 308319709,
+            signature: "bundle".to_string(),
+            actions: vec![
+                buy_action("mintA", "alice"),
+                sell_action("mintA", "bob"),
+                create_action("mintB", "carol"),
+                buy_action("mintC", "carol"),
+            ],
+            error: None,
+        };
+        assert!(!transaction.is_create_buy());
+        assert!(!transaction.is_round_trip());
+
+        let wrap_payloads = transaction.into_payloads(&envelope());
+        assert_eq!(4, wrap_payloads.len());
+    }
+
+    #[test]
+    fn test_payload_envelope_csv() {
+        let transaction = PayloadTransaction {
+            slot: 308319709,
+            signature: "bundle".to_string(),
+            actions: vec![buy_action("mintA", "alice"), buy_action("mintB", "bob")],
+            error: None,
+        };
+        let envelope = PayloadEnvelope::new(PayloadSource::Redis, None);
+
+        let records: Vec<BatchCsvRecord> = transaction
+            .into_payloads(&envelope)
+            .into_iter()
+            .map(BatchCsvRecord::from)
+            .collect();
+        assert_eq!(2, records.len());
+        for record in records {
+            assert_eq!(Some(PayloadSource::Redis), record.source);
+            assert_eq!(None, record.commitment);
+            assert_eq!(Some(envelope.received_at), record.received_at);
+            assert_eq!(None, record.block_time);
+            assert_eq!(Some(false), record.is_paper_trade);
+        }
+    }
+
+    #[test]
+    fn test_payload_envelope_block_time() {
+        let mut traded = buy_action("mintB", "bob");
+        if let PayloadAction::Buy(buy) = &mut traded {
+            buy.trade = Some(PayloadTrade {
+                sol_amount: 1000000000,
+                token_amount: 35758322578,
+                is_buy: true,
+                timestamp: 1735689600,
+                virtual_sol_reserves: 31000000000,
+                virtual_token_reserves: 1038000000000000,
+                real_sol_reserves: 1000000000,
+                real_token_reserves: 758000000000000,
+            });
+        }
+        let transaction = PayloadTransaction {
+            slot: 308319709,
+            signature: "bundle".to_string(),
+            actions: vec![buy_action("mintA", "alice"), traded],
+            error: None,
+        };
+
+        // the trade event dates the whole transaction
+        let envelope = PayloadEnvelope::new(PayloadSource::Redis, None);
+        for payload in transaction.clone().into_payloads(&envelope) {
+            assert_eq!(Some(1735689600), BatchCsvRecord::from(payload).block_time);
+        }
+
+        // a block time the source already knows is kept
+        let envelope = PayloadEnvelope {
+            block_time: Some(1735689599),
+            ..PayloadEnvelope::new(PayloadSource::Backfill, None)
+        };
+        for payload in transaction.into_payloads(&envelope) {
+            assert_eq!(Some(1735689599), BatchCsvRecord::from(payload).block_time);
+        }
+    }
+
+    // The routed buy, failed because the price moved past max_sol_cost
+    fn failed_cpi_buy_notification() -> TransactionNotification {
+        let mut notification = cpi_buy_notification();
+        let meta = notification.transaction.meta.as_mut().unwrap();
+        let err = TransactionError::InstructionError(0, InstructionError::Custom(6002));
+        meta.status = Err(err.clone());
+        meta.err = Some(err);
+        if let OptionSerializer::Some(inner_instructions) = &mut meta.inner_instructions {
+            // the failed buy never emits its trade event
+            inner_instructions[0].instructions.truncate(1);
+        }
+        meta.compute_units_consumed = OptionSerializer::Some(41234);
+        notification
+    }
+
+    #[test]
+    fn test_parse_notification_failed() {
+        let notification = failed_cpi_buy_notification();
+
+        // failed transactions are skipped by default
+        assert!(parse_notification(&notification, PUMPFUN_PROGRAM_ID).is_none());
+
+        let options = ParseOptions {
+            capture_failed: true,
+        };
+        let transaction =
+            parse_notification_with_options(&notification, PUMPFUN_PROGRAM_ID, &options).unwrap();
+        let mut wrap_payloads = transaction.into_payloads(&envelope());
+        match wrap_payloads.pop() {
+            Some(WrapPayload::Failed(payload, _)) => {
+                assert_eq!(Some(0), payload.error.instruction_index);
+                assert_eq!(Some(6002), payload.error.error_code);
+                // the failing instruction is the router's, its error code is not pump.fun's
+                assert_eq!(None, payload.error.error_name);
+                assert_eq!(Some(41234), payload.error.compute_units_consumed);
+            }
+            other => panic!("expected a failed payload, got {:?}", other),
+        }
+
+        // the logs show the error was pump.fun's, propagated by the router
+        let mut routed = failed_cpi_buy_notification();
+        routed.transaction.meta.as_mut().unwrap().log_messages = OptionSerializer::Some(vec![
+            format!("Program {} invoke [1]", ROUTER_PROGRAM_ID),
+            format!("Program {} invoke [2]", PUMPFUN_PROGRAM_ID),
+            format!(
+                "Program {} failed: custom program error: 0x1772",
+                PUMPFUN_PROGRAM_ID
+            ),
+            format!(
+                "Program {} failed: custom program error: 0x1772",
+                ROUTER_PROGRAM_ID
+            ),
+        ]);
+        let transaction =
+            parse_notification_with_options(&routed, PUMPFUN_PROGRAM_ID, &options).unwrap();
+        match transaction.into_payloads(&envelope()).pop() {
+            Some(WrapPayload::Failed(payload, _)) => assert_eq!(
+                Some("TooMuchSolRequired".to_string()),
+                payload.error.error_name
+            ),
+            other => panic!("expected a failed payload, got {:?}", other),
+        }
+
+        // the same buy sent to pump.fun directly
+        let mut value = serde_json::to_value(&notification).unwrap();
+        let instruction = &mut value["transaction"]["transaction"]["message"]["instructions"][0];
+        instruction["programId"] = PUMPFUN_PROGRAM_ID.into();
+        instruction["data"] = "AJTQ2h9DXrBwFqfGgyfiEhbWWqrqmcZmH".into();
+        let notification: TransactionNotification = serde_json::from_value(value).unwrap();
+        let transaction =
+            parse_notification_with_options(&notification, PUMPFUN_PROGRAM_ID, &options).unwrap();
+        match transaction.into_payloads(&envelope()).pop() {
+            Some(WrapPayload::Failed(payload, _)) => assert_eq!(
+                Some("TooMuchSolRequired".to_string()),
+                payload.error.error_name
+            ),
+            other => panic!("expected a failed payload, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_filled_amount() {
+        let trade = PayloadTrade {
+            sol_amount: 1_000_000,
+            token_amount: 35_000_000,
+            is_buy: true,
+            timestamp: 1735689600,
+            virtual_sol_reserves: 0,
+            virtual_token_reserves: 0,
+            real_sol_reserves: 0,
+            real_token_reserves: 0,
+        };
+        // two buys of the mint in one transaction, the balance covers both
+        let balance = PayloadBalance {
+            token_delta: 70_000_000,
+            sol_delta: -2_000_000,
+        };
+        assert_eq!(
+            35_000_000,
+            filled_amount(Some(&balance), Some(&trade), 40_000_000)
+        );
+        assert_eq!(70_000_000, filled_amount(Some(&balance), None, 40_000_000));
+        assert_eq!(40_000_000, filled_amount(None, None, 40_000_000));
+    }
+
+    #[test]
+    fn test_pumpfun_error_name() {
+        assert_eq!(Some("BondingCurveComplete"), pumpfun_error_name(6005));
+        assert_eq!(None, pumpfun_error_name(2006));
+    }
+
+    #[test]
+    fn test_decode_set_params() {
+        let set_params = SetParams {
+            _fee_recipient: solana_program::pubkey::Pubkey::from_str(
+                "CebN5WGQ4jvEPvsVU4EoHEpgzq1VV7AbicfhtW4xC9iM",
+            )
+            .unwrap(),
+            _initial_virtual_token_reserves: 1_073_000_000_000_000,
+            _initial_virtual_sol_reserves: 30_000_000_000,
+            _initial_real_token_reserves: 793_100_000_000_000,
+            _token_total_supply: 1_000_000_000_000_000,
+            _fee_basis_points: 95,
+        };
+        let instruction = UiPartiallyDecodedInstruction {
+            program_id: PUMPFUN_PROGRAM_ID.to_string(),
+            accounts: vec![
+                "4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf".to_string(), // global
+                "DCpJReAfonSrgohiQbTmKKbjbqVofspFRHz9WCGxW9Ke".to_string(), // user
+                "11111111111111111111111111111111".to_string(),
+                "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1".to_string(), // event_authority
+                PUMPFUN_PROGRAM_ID.to_string(),
+            ],
+            data: bs58::encode(set_params.data()).into_string(),
+            stack_height: None,
+        };
+
+        let wrap_instruction = decode_instruction(&instruction, 308319709, "sig", None).unwrap();
+        let wrap_instructions = vec![(InstructionIndex::default(), wrap_instruction)];
+        match build_actions(wrap_instructions, vec![], &PayloadFees::default()).pop() {
+            Some(PayloadAction::SetParams(payload)) => {
+                assert_eq!(InstructionIndex::default(), payload.instruction);
+                assert_eq!(
+                    "DCpJReAfonSrgohiQbTmKKbjbqVofspFRHz9WCGxW9Ke",
+                    payload.user_pk
+                );
+                assert_eq!(
+                    "CebN5WGQ4jvEPvsVU4EoHEpgzq1VV7AbicfhtW4xC9iM",
+                    payload.fee_recipient
+                );
+                assert_eq!(30_000_000_000, payload.initial_virtual_sol_reserves);
+                assert_eq!(95, payload.fee_basis_points);
+            }
+            other => panic!("expected set params, got {:?}", other),
+        }
+    }
 }
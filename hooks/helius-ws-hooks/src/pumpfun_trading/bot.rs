@@ -1,7 +1,9 @@
 #![allow(dead_code)]
 
+use crate::pumpfun_fee_parser::PayloadFees;
 use crate::pumpfun_trading::utils::PriceUtil;
 use log::{info, warn};
+use pumpfun_transaction_builder::{DEFAULT_JITO_TIPS, DEFAULT_PRIORITY_FEE};
 use serde::Deserialize;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -45,6 +47,40 @@ pub struct TradingConfig {
     pub pnl_loss_percentage: f64,        // Percentage of loss to stop trading
     pub initial_capital: f64,            // Initial capital for trading
     pub paper_trading: bool,             // Flag for paper trading mode
+    #[serde(default = "default_max_priority_fee")]
+    pub max_priority_fee: u64, // Cap on the copied priority fee, micro lamports per CU
+    #[serde(default = "default_max_jito_tip")]
+    pub max_jito_tip: u64, // Cap on the copied jito tip, lamports
+}
+
+// Without a cap configured the fees are never raised above the ones the builder pays by default
+fn default_max_priority_fee() -> u64 {
+    DEFAULT_PRIORITY_FEE
+}
+
+fn default_max_jito_tip() -> u64 {
+    DEFAULT_JITO_TIPS
+}
+
+// Fees of a mint nobody holds a position in are dropped after this long without a trade
+pub const OBSERVED_FEES_TTL_SECS: u64 = 600;
+
+// Fees paid by the landed trades of a mint
+#[derive(Debug, Default)]
+pub struct ObservedFees {
+    pub fees: Vec<PayloadFees>,
+    pub last_update_ts: u64, // The timestamp of the last trade
+}
+
+// The pump.fun global parameters, as last set by the authority
+#[derive(Debug, Clone)]
+pub struct GlobalParams {
+    pub fee_recipient: String,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    pub fee_basis_points: u64,
 }
 
 #[derive(Debug)]
@@ -52,6 +88,10 @@ pub struct Bot {
     pub config: RefCell<TradingConfig>,
     pub monitoring_token_pool: RefCell<HashMap<String, MonitorRecord>>,
     pub position_manager: RefCell<HashMap<String, Position>>,
+    // None until a SetParams is seen
+    pub global_params: RefCell<Option<GlobalParams>>,
+    // Fees paid by the landed trades of each monitored mint
+    pub observed_fees: RefCell<HashMap<String, ObservedFees>>,
     // TODO: shadow state for paper trading
 }
 
@@ -61,9 +101,63 @@ impl Bot {
             config: RefCell::new(config),
             monitoring_token_pool: RefCell::new(HashMap::new()),
             position_manager: RefCell::new(HashMap::new()),
+            global_params: RefCell::new(None),
+            observed_fees: RefCell::new(HashMap::new()),
         }
     }
 
+    pub fn record_fees(&self, mint_pk: &str, fees: &PayloadFees) {
+        let now_ts = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.prune_observed_fees(now_ts);
+        let mut observed_fees = self.observed_fees.borrow_mut();
+        let observed = observed_fees.entry(mint_pk.to_string()).or_default();
+        observed.fees.push(fees.clone());
+        observed.last_update_ts = now_ts;
+    }
+
+    // Drops the fees of mints without a position that have not traded for OBSERVED_FEES_TTL_SECS
+    pub fn prune_observed_fees(&self, now_ts: u64) {
+        let position_manager = self.position_manager.borrow();
+        self.observed_fees.borrow_mut().retain(|mint_pk, observed| {
+            position_manager.contains_key(mint_pk)
+                || now_ts.saturating_sub(observed.last_update_ts) < OBSERVED_FEES_TTL_SECS
+        });
+    }
+
+    // Median priority fee (micro lamports per CU) and jito tip (lamports) of the landed trades of
+    // a mint, capped by the config, None when no trade has paid one
+    pub fn winning_fees(&self, mint_pk: &str) -> (Option<u64>, Option<u64>) {
+        let observed_fees = self.observed_fees.borrow();
+        let Some(observed) = observed_fees.get(mint_pk) else {
+            return (None, None);
+        };
+        let config = self.config.borrow();
+        let priority_fee = median(
+            observed
+                .fees
+                .iter()
+                .filter_map(|f| f.priority_fee_micro_lamports),
+        )
+        .map(|fee| fee.min(config.max_priority_fee));
+        let jito_tips = median(
+            observed
+                .fees
+                .iter()
+                .map(|f| f.jito_tip_lamports)
+                .filter(|tip| *tip > 0),
+        )
+        .map(|tip| tip.min(config.max_jito_tip));
+        (priority_fee, jito_tips)
+    }
+
+    pub fn update_global_params(&self, global_params: GlobalParams) {
+        info!("INFO: pump.fun global params changed: {:?}", global_params);
+        self.global_params.replace(Some(global_params));
+    }
+
     pub fn insert_position(&self, mint_pk: String, token_to_buy: f64) {
         let mut position_manager = self.position_manager.borrow_mut();
         position_manager.insert(
@@ -171,6 +265,7 @@ impl Bot {
         if event_user_pk == &self.config.borrow().self_pub_key {
             self.position_manager.borrow_mut().remove(mint_pk);
             self.monitoring_token_pool.borrow_mut().remove(mint_pk);
+            self.observed_fees.borrow_mut().remove(mint_pk);
 
             if let Some(position) = self.position_manager.borrow_mut().get_mut(mint_pk) {
                 if position.filled_pos != token_sell {
@@ -201,3 +296,12 @@ impl Bot {
         }
     }
 }
+
+fn median(values: impl Iterator<Item = u64>) -> Option<u64> {
+    let mut values: Vec<u64> = values.collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    Some(values[values.len() / 2])
+}
@@ -1,9 +1,10 @@
 #![allow(dead_code)]
 
 use crate::pumpfun_instruction_parser::{
-    PayloadBuy, PayloadBuySell, PayloadCreate, PayloadCreateBuy, PayloadSell, PayloadWithdraw,
+    PayloadBuy, PayloadBuySell, PayloadCreate, PayloadCreateBuy, PayloadInitialize, PayloadSell,
+    PayloadSetParams, PayloadWithdraw, WrapPayload,
 };
-use crate::pumpfun_trading::bot::{Bot, Position, TradingConfig};
+use crate::pumpfun_trading::bot::{Bot, GlobalParams, Position, TradingConfig};
 use crate::pumpfun_trading::orders::{BotBuyOrder, BotOrder};
 use crate::pumpfun_trading::utils::PriceUtil;
 use log::info;
@@ -26,6 +27,46 @@ impl Controller {
         self.bot.borrow()
     }
 
+    /// Dispatches a payload to its handler, the buy order of a launch the bot follows
+    pub fn handle_payload(&mut self, wrap_payload: WrapPayload) -> Option<BotOrder> {
+        match wrap_payload {
+            WrapPayload::Create(event, envelope) => {
+                // to be finished
+                self.handle_create(&event, envelope.is_paper_trade);
+            }
+            WrapPayload::CreateBuy(event, envelope) => {
+                return self.handle_create_buy(&event, envelope.is_paper_trade);
+            }
+            WrapPayload::Buy(event, envelope) => {
+                self.handle_buy(&event, envelope.is_paper_trade);
+            }
+            WrapPayload::Sell(event, envelope) => {
+                self.handle_sell(&event, envelope.is_paper_trade);
+            }
+            WrapPayload::BuySell(event, envelope) => {
+                // to be finished
+                self.handle_buy_sell(&event, envelope.is_paper_trade);
+            }
+            WrapPayload::Withdraw(event, envelope) => {
+                // to be finished
+                self.handle_withdraw(&event, envelope.is_paper_trade);
+            }
+            WrapPayload::SetParams(event, envelope) => {
+                self.handle_set_params(&event, envelope.is_paper_trade);
+            }
+            WrapPayload::Initialize(event, envelope) => {
+                self.handle_initialize(&event, envelope.is_paper_trade);
+            }
+            // the bot trades the bonding curve only
+            WrapPayload::Failed(_, _)
+            | WrapPayload::PumpAmmSwap(_, _)
+            | WrapPayload::RaydiumSwap(_, _)
+            | WrapPayload::TokenTransfer(_, _)
+            | WrapPayload::Unknown => {}
+        }
+        None
+    }
+
     pub fn handle_create(&mut self, _event: &PayloadCreate, is_paper_trade: bool) {
         if is_paper_trade {
             info!("Paper trading, skip create event");
@@ -46,7 +87,12 @@ impl Controller {
         let create_buy_trigger_lamport: u64 = bot.config.borrow().create_buy_trigger_lamport;
         let mut _curt_capital = bot.config.borrow().initial_capital;
 
-        let create_buy_lamport = event.max_sol_cost;
+        // prefer the actual fill from the TradeEvent over the slippage bound
+        let create_buy_lamport = event
+            .trade
+            .as_ref()
+            .map_or(event.max_sol_cost, |trade| trade.sol_amount);
+        let create_buy_token = event.filled_token_amount();
         let now_ts = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
@@ -59,7 +105,7 @@ impl Controller {
         // case 1: big mint, buy and hold some time
         if create_buy_lamport >= create_buy_trigger_lamport {
             let creator_sol_cost = create_buy_lamport as f64 / PriceUtil::PRICE_SCALE;
-            let creator_buy_token_amount = event.amount as f64 / PriceUtil::TOKEN_SCALE;
+            let creator_buy_token_amount = create_buy_token as f64 / PriceUtil::TOKEN_SCALE;
 
             // 当前资金充足
             // TODO: send order with 0.2 sol, it better be an interface to choose paper trading or live trading
@@ -77,12 +123,17 @@ impl Controller {
                 PriceUtil::INIT_SOL_IN_POOL + creator_sol_cost,
                 PriceUtil::INIT_TOKEN - creator_buy_token_amount,
             );
+            // pay what the creator's transaction paid to land
+            bot.record_fees(&event.mint_pk, &event.fees);
+            let (priority_fee, jito_tips) = bot.winning_fees(&event.mint_pk);
             // For live trading, build and send transactions using non-blocking threads
             let buy_order = BotBuyOrder {
                 wallet_private_base58: bot.config.borrow().self_keypair.clone(),
                 mint_pk: event.mint_pk.clone(),
                 amount_sol: delta_sol,
                 slippage_basis_points: None,
+                priority_fee,
+                jito_tips,
             };
 
             // TODO: mint pk clone() cost
@@ -105,7 +156,8 @@ impl Controller {
 
         let mint_pk = &event.mint_pk;
         if bot.monitoring_token_pool.borrow().contains_key(mint_pk) {
-            let token_buy = event.amount as f64 / PriceUtil::TOKEN_SCALE;
+            let token_buy = event.filled_token_amount() as f64 / PriceUtil::TOKEN_SCALE;
+            bot.record_fees(mint_pk, &event.fees);
             // if we get front run, we may want to clear our position.
             if bot.is_self_pub_key(&event.user_pk) {
                 bot.update_position(mint_pk, token_buy);
@@ -136,7 +188,8 @@ impl Controller {
 
         let mint_pk = &event.mint_pk;
         if bot.monitoring_token_pool.borrow().contains_key(mint_pk) {
-            let token_sell = event.amount as f64 / PriceUtil::TOKEN_SCALE;
+            let token_sell = event.filled_token_amount() as f64 / PriceUtil::TOKEN_SCALE;
+            bot.record_fees(mint_pk, &event.fees);
             let (prev_token_in_pool, token_delta) =
                 bot.update_monitor_record_on_sell(mint_pk, token_sell);
 
@@ -165,6 +218,23 @@ impl Controller {
         }
     }
 
+    // Protocol parameters are real state, they are applied in paper trading too
+    pub fn handle_set_params(&mut self, event: &PayloadSetParams, _is_paper_trade: bool) {
+        let bot = self.bot.borrow();
+        bot.update_global_params(GlobalParams {
+            fee_recipient: event.fee_recipient.clone(),
+            initial_virtual_token_reserves: event.initial_virtual_token_reserves,
+            initial_virtual_sol_reserves: event.initial_virtual_sol_reserves,
+            initial_real_token_reserves: event.initial_real_token_reserves,
+            token_total_supply: event.token_total_supply,
+            fee_basis_points: event.fee_basis_points,
+        });
+    }
+
+    pub fn handle_initialize(&mut self, event: &PayloadInitialize, _is_paper_trade: bool) {
+        info!("pump.fun global initialized by {}", event.user_pk);
+    }
+
     // 获取当前bot开仓、成交数量
     pub fn get_position(&self, mint_pk: &str) -> Option<Position> {
         let bot = self.bot.borrow();
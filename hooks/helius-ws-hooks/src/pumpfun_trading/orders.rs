@@ -18,6 +18,8 @@ pub struct BotBuyOrder {
     pub mint_pk: String,
     pub amount_sol: f64,
     pub slippage_basis_points: Option<u64>,
+    pub priority_fee: Option<u64>, // micro lamports per compute unit, builder default if None
+    pub jito_tips: Option<u64>,    // lamports, builder default if None
 }
 
 impl BotBuyOrder {
@@ -26,12 +28,16 @@ impl BotBuyOrder {
         mint_pk: String,
         amount_sol: f64,
         slippage_basis_points: Option<u64>,
+        priority_fee: Option<u64>,
+        jito_tips: Option<u64>,
     ) -> Self {
         Self {
             wallet_private_base58,
             mint_pk,
             amount_sol,
             slippage_basis_points,
+            priority_fee,
+            jito_tips,
         }
     }
 
@@ -40,6 +46,8 @@ impl BotBuyOrder {
             wallet_private_base58: self.wallet_private_base58.clone(),
             mint_pk: self.mint_pk.clone(),
             slippage_basis_points: self.slippage_basis_points,
+            priority_fee: self.priority_fee,
+            jito_tips: self.jito_tips,
         }
     }
 }
@@ -48,6 +56,8 @@ pub struct BotSellOrder {
     pub wallet_private_base58: String,
     pub mint_pk: String,
     pub slippage_basis_points: Option<u64>,
+    pub priority_fee: Option<u64>, // micro lamports per compute unit, builder default if None
+    pub jito_tips: Option<u64>,    // lamports, builder default if None
 }
 
 #[derive(Debug)]
@@ -65,6 +75,8 @@ pub async fn execute_pumpfun_buy(buy_order: &BotBuyOrder) -> Result<(), anyhow::
         key: buy_order.wallet_private_base58.clone(),
         lamports: (buy_order.amount_sol * 1_000_000_000.0_f64) as u64,
         slippage: 500,
+        priority_fee: buy_order.priority_fee,
+        jito_tips: buy_order.jito_tips,
     };
 
     let api_key: &str = helius_api_key;
@@ -109,6 +121,8 @@ pub async fn execute_pumpfun_sell(sell_order: &BotSellOrder) -> Result<(), anyho
         key: sell_order.wallet_private_base58.clone(),
         amount: None,
         slippage: 500,
+        priority_fee: sell_order.priority_fee,
+        jito_tips: sell_order.jito_tips,
     };
 
     let api_key: &str = helius_api_key;
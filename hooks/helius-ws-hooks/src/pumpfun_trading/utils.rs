@@ -41,6 +41,7 @@ impl PriceUtil {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use pumpfun_transaction_builder::{DEFAULT_JITO_TIPS, DEFAULT_PRIORITY_FEE};
     use std::fs::File;
     use std::io::Write;
     use tempfile::tempdir;
@@ -61,6 +62,8 @@ mod tests {
         writeln!(file, "self_pub_key = \"abcde\"").unwrap();
         writeln!(file, "self_keypair = \"keypair\"").unwrap();
         writeln!(file, "paper_trading = true").unwrap();
+        writeln!(file, "max_priority_fee = 5000000").unwrap();
+        writeln!(file, "max_jito_tip = 1000000").unwrap();
 
         // Load the configuration using the load_trading_config function
         let config = load_trading_config(file_path.to_str().unwrap()).unwrap();
@@ -73,6 +76,28 @@ mod tests {
         assert_eq!(config.self_pub_key, "abcde");
         assert_eq!(config.self_keypair, "keypair");
         assert!(config.paper_trading);
+        assert_eq!(config.max_priority_fee, 5000000);
+        assert_eq!(config.max_jito_tip, 1000000);
+    }
+
+    #[test]
+    fn test_load_trading_config_default_caps() {
+        // a config written before the fee caps existed
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("config.toml");
+        let mut file = File::create(&file_path).unwrap();
+        writeln!(file, "[trading]").unwrap();
+        writeln!(file, "create_buy_trigger_lamport = 1000000000").unwrap();
+        writeln!(file, "create_buy_watch_lamport = 1500000000").unwrap();
+        writeln!(file, "pnl_loss_percentage = 0.05").unwrap();
+        writeln!(file, "initial_capital = 5").unwrap();
+        writeln!(file, "self_pub_key = \"abcde\"").unwrap();
+        writeln!(file, "self_keypair = \"keypair\"").unwrap();
+        writeln!(file, "paper_trading = true").unwrap();
+
+        let config = load_trading_config(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(config.max_priority_fee, DEFAULT_PRIORITY_FEE);
+        assert_eq!(config.max_jito_tip, DEFAULT_JITO_TIPS);
     }
     #[test]
     fn test_price_utils() {
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::UiInstruction;
+
+use crate::program_decoder::{DecodeContext, ProgramDecoder};
+use crate::pumpfun_instruction_parser::{resolve_instruction, InstructionIndex, PayloadAction};
+
+pub const RAYDIUM_AMM_V4_PROGRAM_ID: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+
+// AmmInstruction tags
+const SWAP_BASE_IN: u8 = 9;
+const SWAP_BASE_OUT: u8 = 11;
+
+/// A swap on a Raydium AMM v4 pool. The instruction does not name the mints, only the token
+/// accounts of the user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayloadRaydiumSwap {
+    pub slot: u64,
+    pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
+    pub amm: String,
+    pub user_pk: String,
+    pub user_source: String,      // token account paying amount_in
+    pub user_destination: String, // token account receiving amount_out
+    pub swap_base_in: bool,
+    pub amount_in: u64,  // exact for swap_base_in, the maximum for swap_base_out
+    pub amount_out: u64, // the minimum for swap_base_in, exact for swap_base_out
+    pub router: Option<String>,
+}
+
+pub struct RaydiumAmmDecoder;
+
+impl ProgramDecoder for RaydiumAmmDecoder {
+    fn program_id(&self) -> &str {
+        RAYDIUM_AMM_V4_PROGRAM_ID
+    }
+
+    fn decode(
+        &self,
+        instruction: &UiInstruction,
+        context: &DecodeContext,
+    ) -> Option<PayloadAction> {
+        let i = resolve_instruction(instruction, context.account_keys)?;
+        let data = bs58::decode(i.data.as_str()).into_vec().ok()?;
+        if data.len() < 17 {
+            return None;
+        }
+        // swap_base_in: amount_in, minimum_amount_out; swap_base_out: max_amount_in, amount_out
+        let swap_base_in = match data[0] {
+            SWAP_BASE_IN => true,
+            SWAP_BASE_OUT => false,
+            _ => return None,
+        };
+        // token_program, amm, ... user_source, user_destination, user_owner. The amm target
+        // orders account is optional, so the user accounts are counted from the end.
+        let accounts = &i.accounts;
+        if accounts.len() < 17 {
+            return None;
+        }
+        let n = accounts.len();
+
+        Some(PayloadAction::RaydiumSwap(PayloadRaydiumSwap {
+            slot: context.slot,
+            signature: context.signature.to_string(),
+            instruction: context.instruction,
+            amm: accounts[1].clone(),
+            user_pk: accounts[n - 1].clone(),
+            user_source: accounts[n - 3].clone(),
+            user_destination: accounts[n - 2].clone(),
+            swap_base_in,
+            amount_in: u64::from_le_bytes(data[1..9].try_into().unwrap()),
+            amount_out: u64::from_le_bytes(data[9..17].try_into().unwrap()),
+            router: context.router.map(str::to_string),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_raydium_swap_base_in() {
+        let mut data = vec![SWAP_BASE_IN];
+        data.extend(1_000_000_000u64.to_le_bytes());
+        data.extend(28_000_000_000u64.to_le_bytes());
+        let mut accounts: Vec<String> = (0..15).map(|n| format!("account{}", n)).collect();
+        accounts[1] = "amm".to_string();
+        accounts.extend(["user_wsol", "user_token", "user"].map(str::to_string));
+        let instruction: UiInstruction = serde_json::from_value(serde_json::json!({
+            "programId": RAYDIUM_AMM_V4_PROGRAM_ID,
+            "accounts": accounts,
+            "data": bs58::encode(data).into_string(),
+            "stackHeight": null
+        }))
+        .unwrap();
+        let context = DecodeContext {
+            slot: 320000000,
+            signature: "sig",
+            instruction: InstructionIndex::default(),
+            account_keys: &[],
+            router: Some("JUP6LkbZbjS1jKKwapdHNy74zcZ3tQVkTqUwjnvsTEn"),
+        };
+
+        match RaydiumAmmDecoder.decode(&instruction, &context) {
+            Some(PayloadAction::RaydiumSwap(swap)) => {
+                assert!(swap.swap_base_in);
+                assert_eq!("amm", swap.amm);
+                assert_eq!("user", swap.user_pk);
+                assert_eq!("user_wsol", swap.user_source);
+                assert_eq!("user_token", swap.user_destination);
+                assert_eq!(1_000_000_000, swap.amount_in);
+                assert_eq!(28_000_000_000, swap.amount_out);
+                assert_eq!(
+                    Some("JUP6LkbZbjS1jKKwapdHNy74zcZ3tQVkTqUwjnvsTEn".to_string()),
+                    swap.router
+                );
+            }
+            other => panic!("expected a raydium swap, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use helius::types::TransactionNotification;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::batch_csv_writer::partial_path;
+use crate::pumpfun_instruction_parser::WrapPayload;
+
+const SEGMENT_SUFFIX: &str = ".jsonl.zst";
+
+/// A notification as recorded, with when it was received
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedNotification {
+    pub received_at: i64, // local unix timestamp, milliseconds
+    pub notification: TransactionNotification,
+}
+
+/// The payloads a producer decoded from a notification, as recorded by a pipeline reading them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedPayloads {
+    pub received_at: i64, // local unix timestamp, milliseconds
+    pub payloads: Vec<WrapPayload>,
+}
+
+/// One line of a segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Recorded {
+    Notification(RecordedNotification),
+    Payloads(RecordedPayloads),
+}
+
+impl Recorded {
+    pub fn received_at(&self) -> i64 {
+        match self {
+            Recorded::Notification(recorded) => recorded.received_at,
+            Recorded::Payloads(recorded) => recorded.received_at,
+        }
+    }
+}
+
+/// Appends notifications, or payloads, to zstd compressed json lines segments under `base_dir`.
+/// Like the csv files, a segment is written as `<name>.jsonl.zst.partial` and renamed once closed.
+pub struct NotificationRecorder {
+    base_dir: String,
+    max_segment_age: Duration,
+    flush_interval: Duration,
+    last_flush: Instant,
+    segment: Option<Segment>,
+}
+
+struct Segment {
+    partial_path: PathBuf,
+    final_path: PathBuf,
+    opened: Instant,
+    encoder: zstd::Encoder<'static, File>,
+}
+
+impl Segment {
+    fn create(base_dir: &str) -> anyhow::Result<Self> {
+        let name = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let mut final_path = Path::new(base_dir).join(format!("{}{}", name, SEGMENT_SUFFIX));
+        let mut n = 1;
+        while final_path.exists() || partial_path(&final_path).exists() {
+            final_path = Path::new(base_dir).join(format!("{}-{}{}", name, n, SEGMENT_SUFFIX));
+            n += 1;
+        }
+        let partial_path = partial_path(&final_path);
+        let encoder = zstd::Encoder::new(File::create(&partial_path)?, 0)?;
+
+        Ok(Self {
+            partial_path,
+            final_path,
+            opened: Instant::now(),
+            encoder,
+        })
+    }
+
+    fn close(self) -> anyhow::Result<()> {
+        let file = self.encoder.finish()?;
+        file.sync_all()?;
+        fs::rename(&self.partial_path, &self.final_path)?;
+        Ok(())
+    }
+}
+
+impl NotificationRecorder {
+    /// Rotates to a new segment once the current one is `max_segment_age` old
+    pub fn new(base_dir: String, max_segment_age: Duration) -> anyhow::Result<Self> {
+        if !Path::new(&base_dir).is_dir() {
+            anyhow::bail!("recording directory {} does not exist", base_dir);
+        }
+        Ok(Self {
+            base_dir,
+            max_segment_age,
+            flush_interval: Duration::from_secs(5),
+            last_flush: Instant::now(),
+            segment: None,
+        })
+    }
+
+    /// Records a notification received at `received_at`
+    pub fn record(
+        &mut self,
+        notification: &TransactionNotification,
+        received_at: i64,
+    ) -> anyhow::Result<()> {
+        self.write(&Recorded::Notification(RecordedNotification {
+            received_at,
+            notification: notification.clone(),
+        }))
+    }
+
+    /// Records the payloads of a notification received at `received_at`
+    pub fn record_payloads(
+        &mut self,
+        payloads: &[WrapPayload],
+        received_at: i64,
+    ) -> anyhow::Result<()> {
+        self.write(&Recorded::Payloads(RecordedPayloads {
+            received_at,
+            payloads: payloads.to_vec(),
+        }))
+    }
+
+    pub fn write(&mut self, recorded: &Recorded) -> anyhow::Result<()> {
+        if self
+            .segment
+            .as_ref()
+            .is_some_and(|segment| segment.opened.elapsed() >= self.max_segment_age)
+        {
+            if let Some(segment) = self.segment.take() {
+                segment.close()?;
+            }
+        }
+        let segment = match self.segment.as_mut() {
+            Some(segment) => segment,
+            None => self.segment.insert(Segment::create(&self.base_dir)?),
+        };
+        serde_json::to_writer(&mut segment.encoder, recorded)?;
+        segment.encoder.write_all(b"\n")?;
+
+        // a flushed zstd block is readable, a crash loses at most `flush_interval`
+        if self.last_flush.elapsed() >= self.flush_interval {
+            segment.encoder.flush()?;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Close the current segment and rename it to its final name.
+    pub fn close(mut self) -> anyhow::Result<()> {
+        match self.segment.take() {
+            Some(segment) => segment.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for NotificationRecorder {
+    fn drop(&mut self) {
+        if let Some(segment) = self.segment.take() {
+            if let Err(e) = segment.close() {
+                error!("Failed to close recording segment: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Reads back the lines of a segment, or of every closed segment of a directory in name
+/// order, which is recording order
+pub struct RecordingReader {
+    paths: VecDeque<PathBuf>,
+    lines: Option<Lines<BufReader<zstd::Decoder<'static, BufReader<File>>>>>,
+}
+
+impl RecordingReader {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let path = Path::new(path);
+        let mut paths = vec![];
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let entry_path = entry?.path();
+                if entry_path.to_string_lossy().ends_with(SEGMENT_SUFFIX) {
+                    paths.push(entry_path);
+                }
+            }
+            paths.sort_by_key(|path| segment_order(path));
+        } else {
+            paths.push(path.to_path_buf());
+        }
+        Ok(Self {
+            paths: paths.into(),
+            lines: None,
+        })
+    }
+}
+
+// `<name>-<n>`, opened within the same second as `<name>`, sorts after it
+fn segment_order(path: &Path) -> (String, u32) {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = file_name.trim_end_matches(SEGMENT_SUFFIX);
+    match stem.rsplit_once('-') {
+        // the name itself is `%Y%m%d-%H%M%S`
+        Some((name, n)) if name.contains('-') => (name.to_string(), n.parse().unwrap_or(0)),
+        _ => (stem.to_string(), 0),
+    }
+}
+
+impl Iterator for RecordingReader {
+    type Item = anyhow::Result<Recorded>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(lines) = self.lines.as_mut() {
+                match lines.next() {
+                    Some(Ok(line)) => return Some(serde_json::from_str(&line).map_err(Into::into)),
+                    Some(Err(e)) => return Some(Err(e.into())),
+                    None => self.lines = None,
+                }
+            }
+            let path = self.paths.pop_front()?;
+            match File::open(&path).and_then(zstd::Decoder::new) {
+                Ok(decoder) => self.lines = Some(BufReader::new(decoder).lines()),
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+/// How fast recorded notifications are replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// With the gaps they were received with
+    Original,
+    /// With the gaps divided by the factor
+    Accelerated(f64),
+    /// Without waiting
+    Max,
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "original" => Ok(ReplaySpeed::Original),
+            "max" => Ok(ReplaySpeed::Max),
+            _ => match s.trim_end_matches('x').parse::<f64>() {
+                Ok(factor) if factor > 0.0 => Ok(ReplaySpeed::Accelerated(factor)),
+                _ => Err(format!(
+                    "unknown speed {}, expected original, max or a factor like 10x",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// Waits until a recorded notification is due, relative to the first one replayed
+pub struct ReplayPacer {
+    speed: ReplaySpeed,
+    start: Option<(i64, tokio::time::Instant)>, // the first received_at, and when it was replayed
+}
+
+impl ReplayPacer {
+    pub fn new(speed: ReplaySpeed) -> Self {
+        Self { speed, start: None }
+    }
+
+    pub async fn wait(&mut self, received_at: i64) {
+        let factor = match self.speed {
+            ReplaySpeed::Original => 1.0,
+            ReplaySpeed::Accelerated(factor) => factor,
+            ReplaySpeed::Max => return,
+        };
+        let (first_received_at, started) = *self
+            .start
+            .get_or_insert((received_at, tokio::time::Instant::now()));
+        let offset_ms = (received_at - first_received_at).max(0) as f64 / factor;
+        tokio::time::sleep_until(started + Duration::from_millis(offset_ms as u64)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(slot: u64) -> TransactionNotification {
+        serde_json::from_value(serde_json::json!({
+            "transaction": {
+                "transaction": ["AQ==", "base64"],
+                "meta": null
+            },
+            "signature": format!("sig{}", slot),
+            "slot": slot
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_record_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_dir = dir.path().to_str().unwrap().to_string();
+
+        // every write rotates
+        let mut recorder = NotificationRecorder::new(base_dir.clone(), Duration::ZERO).unwrap();
+        for slot in 0..3 {
+            recorder
+                .record(&notification(slot), 1000 + slot as i64)
+                .unwrap();
+        }
+        recorder
+            .record_payloads(&[WrapPayload::Unknown], 1003)
+            .unwrap();
+        recorder.close().unwrap();
+
+        let segments = fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(4, segments);
+        let recorded: Vec<Recorded> = RecordingReader::open(&base_dir)
+            .unwrap()
+            .map(|recorded| recorded.unwrap())
+            .collect();
+        assert_eq!(4, recorded.len());
+        for (slot, recorded) in recorded.iter().enumerate() {
+            assert_eq!(1000 + slot as i64, recorded.received_at());
+            match recorded {
+                Recorded::Notification(recorded) => {
+                    assert_eq!(format!("sig{}", slot), recorded.notification.signature)
+                }
+                Recorded::Payloads(recorded) => {
+                    assert_eq!(3, slot);
+                    assert!(matches!(recorded.payloads[..], [WrapPayload::Unknown]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay_speed() {
+        assert_eq!(Ok(ReplaySpeed::Original), "original".parse());
+        assert_eq!(Ok(ReplaySpeed::Max), "max".parse());
+        assert_eq!(Ok(ReplaySpeed::Accelerated(10.0)), "10x".parse());
+        assert!("0x".parse::<ReplaySpeed>().is_err());
+    }
+}
@@ -0,0 +1,311 @@
+use redis::aio::MultiplexedConnection;
+use redis::streams::{
+    StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamMaxlen, StreamReadOptions,
+    StreamReadReply,
+};
+use redis::{AsyncCommands, RedisResult};
+use serde::Deserialize;
+use std::collections::{HashSet, VecDeque};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// the stream entry field holding the message
+const PAYLOAD_FIELD: &str = "payload";
+
+/// How messages move through a redis queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisTransport {
+    /// RPUSH / BLPOP on a list. Consumers compete for messages, and a message is gone once popped.
+    #[default]
+    List,
+    /// XADD / XREADGROUP on a stream. Every consumer group sees every message, and a message
+    /// stays pending until acked.
+    Stream,
+}
+
+impl FromStr for RedisTransport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "list" => Ok(RedisTransport::List),
+            "stream" => Ok(RedisTransport::Stream),
+            _ => Err(format!("unknown transport {}, expected list or stream", s)),
+        }
+    }
+}
+
+/// Consumer name used when none is given, `<hostname>-<pid>`, unique to the process. The entries
+/// a previous process left pending are claimed once stale, pass the same name to resume them
+/// right away.
+pub fn default_consumer_name() -> String {
+    // HOSTNAME is a shell variable, usually not exported
+    let hostname = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+        .unwrap_or_else(|| "consumer".to_string());
+    format!("{}-{}", hostname, std::process::id())
+}
+
+pub struct RedisProducer {
+    conn: MultiplexedConnection,
+    queue: String,
+    transport: RedisTransport,
+    max_len: usize,
+}
+
+impl RedisProducer {
+    /// `max_len` caps a stream, approximately. A list is not capped.
+    pub fn new(
+        conn: MultiplexedConnection,
+        queue: String,
+        transport: RedisTransport,
+        max_len: usize,
+    ) -> Self {
+        Self {
+            conn,
+            queue,
+            transport,
+            max_len,
+        }
+    }
+
+    pub async fn push(&mut self, payload: &str) -> RedisResult<()> {
+        self.push_bytes(payload.as_bytes()).await
+    }
+
+    /// Pushes a binary message, see `payload_codec`
+    pub async fn push_bytes(&mut self, payload: &[u8]) -> RedisResult<()> {
+        match self.transport {
+            RedisTransport::List => self.conn.rpush(&self.queue, payload).await,
+            RedisTransport::Stream => {
+                let _id: String = self
+                    .conn
+                    .xadd_maxlen(
+                        &self.queue,
+                        StreamMaxlen::Approx(self.max_len),
+                        "*",
+                        &[(PAYLOAD_FIELD, payload)],
+                    )
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A message popped from a list, or read from a stream and pending until acked
+#[derive(Debug, Clone)]
+pub struct RedisMessage {
+    pub id: Option<String>, // the stream entry id, None for a list
+    pub payload: Vec<u8>,   // json or binary, see `payload_codec::decode_message`
+}
+
+pub struct RedisConsumer {
+    conn: MultiplexedConnection,
+    queue: String,
+    transport: RedisTransport,
+    group: String,
+    consumer: String,
+    claim_idle: Duration,
+    last_claim: Option<Instant>,
+    claim_cursor: Option<String>, // where the claim in progress goes on, claimed a page at a time
+    pending_cursor: Option<String>, // the last entry read of those left unacked before a restart
+    claimed: HashSet<String>,     // claimed while the pending entries are read, not read twice
+    messages: VecDeque<RedisMessage>,
+}
+
+impl RedisConsumer {
+    // `next` blocks this long at a time, so stale entries are still claimed on a quiet stream
+    const BLOCK: Duration = Duration::from_secs(5);
+    const READ_COUNT: usize = 64;
+
+    /// Creates the consumer group of a stream, starting at new messages, if it does not exist.
+    /// `group` and `consumer` are ignored for a list.
+    pub async fn new(
+        mut conn: MultiplexedConnection,
+        queue: String,
+        transport: RedisTransport,
+        group: String,
+        consumer: String,
+    ) -> RedisResult<Self> {
+        if transport == RedisTransport::Stream {
+            let created: RedisResult<()> = conn.xgroup_create_mkstream(&queue, &group, "$").await;
+            match created {
+                Err(e) if e.code() != Some("BUSYGROUP") => return Err(e),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            conn,
+            queue,
+            transport,
+            group,
+            consumer,
+            claim_idle: Duration::from_secs(60),
+            last_claim: None,
+            claim_cursor: None,
+            pending_cursor: Some("0".to_string()),
+            claimed: HashSet::new(),
+            messages: VecDeque::new(),
+        })
+    }
+
+    /// Entries pending this long in another consumer of the group are claimed, the other consumer
+    /// is assumed to have crashed.
+    pub fn with_claim_idle(mut self, claim_idle: Duration) -> Self {
+        self.claim_idle = claim_idle;
+        self
+    }
+
+    pub fn queue(&self) -> &str {
+        &self.queue
+    }
+
+    /// Waits for the next message. From a stream, this is first the stale entries of the group,
+    /// then the entries this consumer left unacked before a restart, then new entries.
+    pub async fn next(&mut self) -> RedisResult<RedisMessage> {
+        loop {
+            if let Some(message) = self.next_timeout(Self::BLOCK).await? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Like `next`, but None once `timeout` passed without a message, e.g. to flush while the
+    /// queue is idle. The wait is a blocking read on the server, unlike a timeout around `next`
+    /// it does not drop a message already popped. A zero `timeout` waits forever, as in redis.
+    pub async fn next_timeout(&mut self, timeout: Duration) -> RedisResult<Option<RedisMessage>> {
+        if let Some(message) = self.messages.pop_front() {
+            return Ok(Some(message));
+        }
+        match self.transport {
+            RedisTransport::List => {
+                let event: Option<(String, Vec<u8>)> =
+                    self.conn.blpop(&self.queue, timeout.as_secs_f64()).await?;
+                Ok(event.map(|(_key, payload)| RedisMessage { id: None, payload }))
+            }
+            RedisTransport::Stream => {
+                if self.claim_cursor.is_some()
+                    || self
+                        .last_claim
+                        .is_none_or(|last_claim| last_claim.elapsed() >= self.claim_idle)
+                {
+                    self.claim_stale().await?;
+                } else {
+                    self.read_group(timeout).await?;
+                }
+                Ok(self.messages.pop_front())
+            }
+        }
+    }
+
+    /// Acknowledges processed stream entries. A no-op for a list.
+    pub async fn ack(&mut self, messages: &[RedisMessage]) -> RedisResult<()> {
+        let ids: Vec<&str> = messages
+            .iter()
+            .filter_map(|message| message.id.as_deref())
+            .collect();
+        if self.transport == RedisTransport::List || ids.is_empty() {
+            return Ok(());
+        }
+        let _acked: u64 = self.conn.xack(&self.queue, &self.group, &ids).await?;
+        Ok(())
+    }
+
+    async fn read_group(&mut self, block: Duration) -> RedisResult<()> {
+        // an id reads the entries already delivered to this consumer after it, ">" new ones
+        let options = StreamReadOptions::default()
+            .group(&self.group, &self.consumer)
+            .count(Self::READ_COUNT);
+        let (id, options) = match &self.pending_cursor {
+            Some(cursor) => (cursor.clone(), options),
+            None => (">".to_string(), options.block(block.as_millis() as usize)),
+        };
+        let reply: Option<StreamReadReply> = self
+            .conn
+            .xread_options(&[&self.queue], &[id], &options)
+            .await?;
+        let ids: Vec<StreamId> = reply
+            .into_iter()
+            .flat_map(|reply| reply.keys)
+            .flat_map(|key| key.ids)
+            .collect();
+        if self.pending_cursor.is_some() {
+            self.pending_cursor = ids.last().map(|stream_id| stream_id.id.clone());
+            let ids = ids
+                .into_iter()
+                .filter(|stream_id| !self.claimed.contains(&stream_id.id))
+                .collect();
+            if self.pending_cursor.is_none() {
+                self.claimed.clear();
+            }
+            return self.push_messages(ids).await;
+        }
+        self.push_messages(ids).await
+    }
+
+    // one page of the pending entries, so a long pending list is not loaded at once
+    async fn claim_stale(&mut self) -> RedisResult<()> {
+        let start = self
+            .claim_cursor
+            .take()
+            .unwrap_or_else(|| "0-0".to_string());
+        let reply: StreamAutoClaimReply = self
+            .conn
+            .xautoclaim_options(
+                &self.queue,
+                &self.group,
+                &self.consumer,
+                self.claim_idle.as_millis() as u64,
+                &start,
+                StreamAutoClaimOptions::default().count(Self::READ_COUNT),
+            )
+            .await?;
+        if self.pending_cursor.is_some() {
+            self.claimed
+                .extend(reply.claimed.iter().map(|stream_id| stream_id.id.clone()));
+        }
+        if reply.next_stream_id == "0-0" {
+            self.last_claim = Some(Instant::now());
+        } else {
+            self.claim_cursor = Some(reply.next_stream_id);
+        }
+        self.push_messages(reply.claimed).await
+    }
+
+    // entries without a payload were not written by a RedisProducer, they are acked right away
+    async fn push_messages(&mut self, ids: Vec<StreamId>) -> RedisResult<()> {
+        let mut malformed = vec![];
+        for stream_id in ids {
+            match stream_id.get::<Vec<u8>>(PAYLOAD_FIELD) {
+                Some(payload) => self.messages.push_back(RedisMessage {
+                    id: Some(stream_id.id),
+                    payload,
+                }),
+                None => malformed.push(RedisMessage {
+                    id: Some(stream_id.id),
+                    payload: vec![],
+                }),
+            }
+        }
+        self.ack(&malformed).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_consumer_name() {
+        let name = default_consumer_name();
+        let (hostname, pid) = name.rsplit_once('-').unwrap();
+        assert!(!hostname.is_empty());
+        assert_eq!(std::process::id().to_string(), pid);
+    }
+}
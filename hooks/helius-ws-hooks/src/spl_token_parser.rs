@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::{UiInstruction, UiParsedInstruction};
+
+use crate::program_decoder::{DecodeContext, ProgramDecoder};
+use crate::pumpfun_instruction_parser::{resolve_instruction, InstructionIndex, PayloadAction};
+
+pub const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+// TokenInstruction tags
+const TRANSFER: u8 = 3;
+const TRANSFER_CHECKED: u8 = 12;
+
+/// A Transfer or TransferChecked between two token accounts. Only TransferChecked names the mint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayloadTokenTransfer {
+    pub slot: u64,
+    pub signature: String,
+    #[serde(default)]
+    pub instruction: InstructionIndex,
+    pub source: String,
+    pub destination: String,
+    pub authority: String,
+    pub mint: Option<String>,
+    pub amount: u64,
+    pub decimals: Option<u8>,
+    pub router: Option<String>,
+}
+
+pub struct SplTokenDecoder;
+
+impl ProgramDecoder for SplTokenDecoder {
+    fn program_id(&self) -> &str {
+        SPL_TOKEN_PROGRAM_ID
+    }
+
+    fn decode(
+        &self,
+        instruction: &UiInstruction,
+        context: &DecodeContext,
+    ) -> Option<PayloadAction> {
+        let transfer = match instruction {
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(i)) => {
+                decode_parsed(&i.parsed, context)
+            }
+            _ => decode_compiled(instruction, context),
+        }?;
+        Some(PayloadAction::TokenTransfer(transfer))
+    }
+}
+
+// jsonParsed: {"type": "transfer" | "transferChecked", "info": {...}}, amounts are strings
+fn decode_parsed(
+    parsed: &serde_json::Value,
+    context: &DecodeContext,
+) -> Option<PayloadTokenTransfer> {
+    let info = &parsed["info"];
+    let field = |name: &str| info[name].as_str().map(str::to_string);
+    let (amount, mint, decimals) = match parsed["type"].as_str()? {
+        "transfer" => (info["amount"].as_str()?, None, None),
+        "transferChecked" => (
+            info["tokenAmount"]["amount"].as_str()?,
+            field("mint"),
+            info["tokenAmount"]["decimals"]
+                .as_u64()
+                .and_then(|decimals| u8::try_from(decimals).ok()),
+        ),
+        _ => return None,
+    };
+    // a multisig transfer names the multisig authority
+    let authority = field("authority").or_else(|| field("multisigAuthority"))?;
+
+    Some(PayloadTokenTransfer {
+        slot: context.slot,
+        signature: context.signature.to_string(),
+        instruction: context.instruction,
+        source: field("source")?,
+        destination: field("destination")?,
+        authority,
+        mint,
+        amount: amount.parse().ok()?,
+        decimals,
+        router: context.router.map(str::to_string),
+    })
+}
+
+fn decode_compiled(
+    instruction: &UiInstruction,
+    context: &DecodeContext,
+) -> Option<PayloadTokenTransfer> {
+    let i = resolve_instruction(instruction, context.account_keys)?;
+    let data = bs58::decode(i.data.as_str()).into_vec().ok()?;
+    let amount = u64::from_le_bytes(data.get(1..9)?.try_into().ok()?);
+    let accounts = &i.accounts;
+    let (source, mint, destination, authority, decimals) = match data[0] {
+        // source, destination, authority
+        TRANSFER => (
+            accounts.first()?,
+            None,
+            accounts.get(1)?,
+            accounts.get(2)?,
+            None,
+        ),
+        // source, mint, destination, authority
+        TRANSFER_CHECKED => (
+            accounts.first()?,
+            Some(accounts.get(1)?.clone()),
+            accounts.get(2)?,
+            accounts.get(3)?,
+            Some(*data.get(9)?),
+        ),
+        _ => return None,
+    };
+
+    Some(PayloadTokenTransfer {
+        slot: context.slot,
+        signature: context.signature.to_string(),
+        instruction: context.instruction,
+        source: source.clone(),
+        destination: destination.clone(),
+        authority: authority.clone(),
+        mint,
+        amount,
+        decimals,
+        router: context.router.map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINT_PK: &str = "8wGN8aEKcuSJ3qxjPZWsK87TGqqqRGCWp8CftPGtpump";
+    const NO_ACCOUNT_KEYS: &[String] = &[];
+
+    fn context() -> DecodeContext<'static> {
+        DecodeContext {
+            slot: 320000000,
+            signature: "sig",
+            instruction: InstructionIndex::default(),
+            account_keys: NO_ACCOUNT_KEYS,
+            router: None,
+        }
+    }
+
+    #[test]
+    fn test_decode_transfer_checked_json_parsed() {
+        let instruction: UiInstruction = serde_json::from_value(serde_json::json!({
+            "program": "spl-token",
+            "programId": SPL_TOKEN_PROGRAM_ID,
+            "parsed": {
+                "type": "transferChecked",
+                "info": {
+                    "source": "source_ata",
+                    "mint": MINT_PK,
+                    "destination": "destination_ata",
+                    "authority": "owner",
+                    "tokenAmount": {
+                        "amount": "35758322578",
+                        "decimals": 6,
+                        "uiAmount": 35758.322578,
+                        "uiAmountString": "35758.322578"
+                    }
+                }
+            },
+            "stackHeight": null
+        }))
+        .unwrap();
+
+        match SplTokenDecoder.decode(&instruction, &context()) {
+            Some(PayloadAction::TokenTransfer(transfer)) => {
+                assert_eq!("source_ata", transfer.source);
+                assert_eq!("destination_ata", transfer.destination);
+                assert_eq!("owner", transfer.authority);
+                assert_eq!(Some(MINT_PK.to_string()), transfer.mint);
+                assert_eq!(35758322578, transfer.amount);
+                assert_eq!(Some(6), transfer.decimals);
+            }
+            other => panic!("expected a token transfer, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_transfer_compiled() {
+        let account_keys = [
+            "owner".to_string(),
+            "source_ata".to_string(),
+            "destination_ata".to_string(),
+            SPL_TOKEN_PROGRAM_ID.to_string(),
+        ];
+        let mut data = vec![TRANSFER];
+        data.extend(1_000_000u64.to_le_bytes());
+        let instruction: UiInstruction = serde_json::from_value(serde_json::json!({
+            "programIdIndex": 3,
+            "accounts": [1, 2, 0],
+            "data": bs58::encode(data).into_string(),
+            "stackHeight": null
+        }))
+        .unwrap();
+        let context = DecodeContext {
+            account_keys: &account_keys,
+            ..context()
+        };
+
+        match SplTokenDecoder.decode(&instruction, &context) {
+            Some(PayloadAction::TokenTransfer(transfer)) => {
+                assert_eq!("source_ata", transfer.source);
+                assert_eq!("destination_ata", transfer.destination);
+                assert_eq!("owner", transfer.authority);
+                assert_eq!(None, transfer.mint);
+                assert_eq!(1_000_000, transfer.amount);
+                assert_eq!(None, transfer.decimals);
+            }
+            other => panic!("expected a token transfer, got {:?}", other),
+        }
+
+        // an approve is not a transfer
+        let mut data = vec![4];
+        data.extend(1_000_000u64.to_le_bytes());
+        let approve: UiInstruction = serde_json::from_value(serde_json::json!({
+            "programIdIndex": 3,
+            "accounts": [1, 2, 0],
+            "data": bs58::encode(data).into_string(),
+            "stackHeight": null
+        }))
+        .unwrap();
+        assert!(SplTokenDecoder.decode(&approve, &context).is_none());
+    }
+}
@@ -0,0 +1,384 @@
+use helius::types::{
+    RpcTransactionsConfig, TransactionNotification, TransactionSubscribeFilter,
+    TransactionSubscribeOptions,
+};
+use helius::websocket::{EnhancedWebsocket, StreamEvent, StreamGap, UnsubscribeFn};
+use log::{info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+
+use crate::dedup::{DedupOutcome, FirstCopy, MemoryStore};
+
+/// Which transactions a filter matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountFilter {
+    /// Any of the accounts, merged with the other `Include` accounts of the tag
+    Include(Vec<String>),
+    /// All of the accounts, a subscription of its own
+    Required(Vec<String>),
+}
+
+/// Provider limits the filters are sharded within
+#[derive(Debug, Clone, Copy)]
+pub struct ShardLimits {
+    pub max_accounts_per_subscription: usize,
+    pub max_subscriptions_per_connection: usize, // one of them is kept free to replace a filter
+    pub max_connections: usize,
+}
+
+impl Default for ShardLimits {
+    fn default() -> Self {
+        Self {
+            max_accounts_per_subscription: 50_000, // helius caps accountInclude at 50k addresses
+            max_subscriptions_per_connection: 20,
+            max_connections: 5,
+        }
+    }
+}
+
+/// An event of the merged stream, with the tag of the filter it matched
+#[derive(Debug, Clone)]
+pub struct TaggedEvent {
+    pub tag: String,
+    pub event: StreamEvent<TransactionNotification>,
+}
+
+// One transactionSubscribe, the accounts of a single tag
+struct Shard {
+    tag: String,
+    required: bool,
+    accounts: BTreeSet<String>,
+    connection: usize,
+    unsubscribe: UnsubscribeFn,
+}
+
+/// Tracks a changing set of tagged account filters over `transactionSubscribe`, sharded across
+/// connections within `ShardLimits`. A changed shard is subscribed again before the old
+/// subscription is dropped, in the slot its connection keeps free, so no event is lost. With a
+/// single subscription per connection there is no such slot: the old subscription is dropped
+/// first and a `StreamEvent::Gap` marks where events may be missing.
+pub struct SubscriptionManager {
+    url: String, // with the api key
+    options: TransactionSubscribeOptions,
+    limits: ShardLimits,
+    connections: Vec<EnhancedWebsocket>,
+    subscriptions: Vec<usize>, // per connection
+    shards: BTreeMap<u64, Shard>,
+    next_shard: u64,
+    event_tx: mpsc::Sender<TaggedEvent>,
+}
+
+impl SubscriptionManager {
+    /// A manager without filters, and the merged stream of the filters to be added
+    pub fn new(
+        url: String,
+        options: TransactionSubscribeOptions,
+        limits: ShardLimits,
+    ) -> (Self, TaggedReceiver) {
+        let (event_tx, event_rx) = mpsc::channel(1024);
+        let manager = Self {
+            url,
+            options,
+            limits,
+            connections: vec![],
+            subscriptions: vec![],
+            shards: BTreeMap::new(),
+            next_shard: 0,
+            event_tx,
+        };
+        let receiver = TaggedReceiver {
+            event_rx,
+            seen: MemoryStore::new(Duration::from_secs(60), 100_000),
+        };
+        (manager, receiver)
+    }
+
+    pub async fn add(&mut self, tag: &str, filter: AccountFilter) -> anyhow::Result<()> {
+        match filter {
+            AccountFilter::Include(accounts) => {
+                let included = self.included(tag);
+                let accounts: Vec<String> = accounts
+                    .into_iter()
+                    .filter(|account| !included.contains(account))
+                    .collect::<BTreeSet<String>>()
+                    .into_iter()
+                    .collect();
+                if accounts.is_empty() {
+                    return Ok(());
+                }
+
+                let max = self.limits.max_accounts_per_subscription;
+                let shard_with_room = self
+                    .shards
+                    .iter()
+                    .find(|(_, shard)| {
+                        shard.tag == tag && !shard.required && shard.accounts.len() < max
+                    })
+                    .map(|(id, shard)| (*id, max - shard.accounts.len()));
+                let room = shard_with_room.map(|(_, room)| room).unwrap_or(0);
+                let (top_up, chunks) = chunk_accounts(accounts, room, max);
+
+                if let Some((id, _)) = shard_with_room {
+                    if !top_up.is_empty() {
+                        let mut accounts = self.shards[&id].accounts.clone();
+                        accounts.extend(top_up);
+                        self.replace(id, accounts).await?;
+                    }
+                }
+                for chunk in chunks {
+                    self.subscribe(tag, false, chunk.into_iter().collect())
+                        .await?;
+                }
+            }
+            AccountFilter::Required(accounts) => {
+                let accounts: BTreeSet<String> = accounts.into_iter().collect();
+                if self.find_required(tag, &accounts).is_none() {
+                    self.subscribe(tag, true, accounts).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn remove(&mut self, tag: &str, filter: AccountFilter) -> anyhow::Result<()> {
+        match filter {
+            AccountFilter::Include(accounts) => {
+                let accounts: BTreeSet<String> = accounts.into_iter().collect();
+                let changed: Vec<(u64, BTreeSet<String>)> = self
+                    .shards
+                    .iter()
+                    .filter(|(_, shard)| shard.tag == tag && !shard.required)
+                    .filter(|(_, shard)| !shard.accounts.is_disjoint(&accounts))
+                    .map(|(id, shard)| (*id, &shard.accounts - &accounts))
+                    .collect();
+                for (id, remaining) in changed {
+                    if remaining.is_empty() {
+                        self.unsubscribe(id).await;
+                    } else {
+                        self.replace(id, remaining).await?;
+                    }
+                }
+            }
+            AccountFilter::Required(accounts) => {
+                let accounts: BTreeSet<String> = accounts.into_iter().collect();
+                if let Some(id) = self.find_required(tag, &accounts) {
+                    self.unsubscribe(id).await;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Accounts of the `Include` filters of `tag`
+    pub fn included(&self, tag: &str) -> BTreeSet<String> {
+        self.shards
+            .values()
+            .filter(|shard| shard.tag == tag && !shard.required)
+            .flat_map(|shard| shard.accounts.iter().cloned())
+            .collect()
+    }
+
+    /// Subscriptions per connection
+    pub fn subscriptions(&self) -> &[usize] {
+        &self.subscriptions
+    }
+
+    fn find_required(&self, tag: &str, accounts: &BTreeSet<String>) -> Option<u64> {
+        self.shards
+            .iter()
+            .find(|(_, shard)| shard.tag == tag && shard.required && shard.accounts == *accounts)
+            .map(|(id, _)| *id)
+    }
+
+    // make before break, events delivered by both subscriptions meanwhile are deduplicated
+    async fn replace(&mut self, id: u64, accounts: BTreeSet<String>) -> anyhow::Result<()> {
+        let Some(shard) = self.shards.get(&id) else {
+            return Ok(());
+        };
+        let (tag, required, connection) = (shard.tag.clone(), shard.required, shard.connection);
+        if self.subscriptions[connection] < self.limits.max_subscriptions_per_connection {
+            self.subscribe_on(connection, &tag, required, accounts)
+                .await?;
+            self.unsubscribe(id).await;
+            return Ok(());
+        }
+
+        // break before make, events until subscribed again are lost
+        warn!("No free slot to replace the filter of {}", tag);
+        let disconnected_at = chrono::Utc::now();
+        self.unsubscribe(id).await;
+        self.subscribe_on(connection, &tag, required, accounts)
+            .await?;
+        let gap = StreamGap {
+            disconnected_at,
+            resubscribed_at: chrono::Utc::now(),
+            reason: "filter replaced without a free subscription slot".to_string(),
+        };
+        let tagged = TaggedEvent {
+            tag,
+            event: StreamEvent::Gap(gap),
+        };
+        let _ = self.event_tx.send(tagged).await;
+        Ok(())
+    }
+
+    async fn subscribe(
+        &mut self,
+        tag: &str,
+        required: bool,
+        accounts: BTreeSet<String>,
+    ) -> anyhow::Result<()> {
+        let connection = self.connection_with_room().await?;
+        self.subscribe_on(connection, tag, required, accounts).await
+    }
+
+    async fn subscribe_on(
+        &mut self,
+        connection: usize,
+        tag: &str,
+        required: bool,
+        accounts: BTreeSet<String>,
+    ) -> anyhow::Result<()> {
+        let account_list: Vec<String> = accounts.iter().cloned().collect();
+        let filter = if required {
+            TransactionSubscribeFilter {
+                account_required: Some(account_list),
+                ..Default::default()
+            }
+        } else {
+            TransactionSubscribeFilter {
+                account_include: Some(account_list),
+                ..Default::default()
+            }
+        };
+        let config = RpcTransactionsConfig {
+            filter,
+            options: self.options.clone(),
+        };
+        let (mut stream, unsubscribe) = self.connections[connection]
+            .transaction_subscribe_with_gaps(config)
+            .await?;
+
+        let event_tx = self.event_tx.clone();
+        let event_tag = tag.to_string();
+        tokio::spawn(async move {
+            while let Some(event) = stream.next().await {
+                let tagged = TaggedEvent {
+                    tag: event_tag.clone(),
+                    event,
+                };
+                if event_tx.send(tagged).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.subscriptions[connection] += 1;
+        self.shards.insert(
+            self.next_shard,
+            Shard {
+                tag: tag.to_string(),
+                required,
+                accounts,
+                connection,
+                unsubscribe,
+            },
+        );
+        self.next_shard += 1;
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, id: u64) {
+        if let Some(shard) = self.shards.remove(&id) {
+            (shard.unsubscribe)().await;
+            self.subscriptions[shard.connection] -= 1;
+        }
+    }
+
+    async fn connection_with_room(&mut self) -> anyhow::Result<usize> {
+        // the free slot of each connection is left to `replace`
+        let max = self
+            .limits
+            .max_subscriptions_per_connection
+            .saturating_sub(1)
+            .max(1);
+        if let Some(connection) = self.subscriptions.iter().position(|count| *count < max) {
+            return Ok(connection);
+        }
+        if self.connections.len() >= self.limits.max_connections {
+            anyhow::bail!(
+                "all {} connections hold {} subscriptions",
+                self.connections.len(),
+                max
+            );
+        }
+        info!("Opening connection {}", self.connections.len() + 1);
+        self.connections
+            .push(EnhancedWebsocket::new(&self.url, Some(15), Some(45)).await?);
+        self.subscriptions.push(0);
+        Ok(self.connections.len() - 1)
+    }
+}
+
+/// The merged stream. A notification delivered twice for a tag, while its filter is replaced, is
+/// passed once.
+pub struct TaggedReceiver {
+    event_rx: mpsc::Receiver<TaggedEvent>,
+    seen: MemoryStore,
+}
+
+impl TaggedReceiver {
+    /// The next event, None once the manager and its subscriptions are dropped
+    pub async fn recv(&mut self) -> Option<TaggedEvent> {
+        loop {
+            let tagged = self.event_rx.recv().await?;
+            if let StreamEvent::Notification(notification) = &tagged.event {
+                let key = format!("{}:{}", tagged.tag, notification.signature);
+                let copy = FirstCopy {
+                    upstream: tagged.tag.clone(),
+                    received_at: chrono::Utc::now().timestamp_millis(),
+                };
+                if let DedupOutcome::Duplicate(_) = self.seen.insert(&key, copy) {
+                    continue;
+                }
+            }
+            return Some(tagged);
+        }
+    }
+}
+
+// The accounts topping up a shard with `room` left, and the rest in chunks of at most `max`
+fn chunk_accounts(
+    mut accounts: Vec<String>,
+    room: usize,
+    max: usize,
+) -> (Vec<String>, Vec<Vec<String>>) {
+    let rest = accounts.split_off(room.min(accounts.len()));
+    let chunks = rest
+        .chunks(max.max(1))
+        .map(|chunk| chunk.to_vec())
+        .collect();
+    (accounts, chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("account{}", i)).collect()
+    }
+
+    #[test]
+    fn test_chunk_accounts() {
+        let (top_up, chunks) = chunk_accounts(accounts(7), 2, 3);
+        assert_eq!(accounts(2), top_up);
+        assert_eq!(vec![3, 2], chunks.iter().map(Vec::len).collect::<Vec<_>>());
+
+        let (top_up, chunks) = chunk_accounts(accounts(2), 5, 3);
+        assert_eq!(2, top_up.len());
+        assert!(chunks.is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use helius::websocket::{ConnectionState, WsHealth};
+use log::{error, info};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How long the websocket has been connected without a notification, once over `stall_after`. It
+/// counts from the connection when none came since.
+pub fn stalled_for(
+    health: &WsHealth,
+    now: DateTime<Utc>,
+    stall_after: Duration,
+) -> Option<Duration> {
+    if health.state != ConnectionState::Connected {
+        return None;
+    }
+    let since = health.last_notification_at.max(health.connected_at)?;
+    let silent = (now - since).to_std().ok()?;
+    (silent >= stall_after).then_some(silent)
+}
+
+/// Logs the health of the websocket `name` every `interval`, and an error every `stall_after` while
+/// it is stalled, until it is closed. The pong round trip of each upstream tells the fastest one.
+pub async fn monitor_health(
+    name: String,
+    health: watch::Receiver<WsHealth>,
+    interval: Duration,
+    stall_after: Duration,
+) {
+    let mut report = tokio::time::interval(interval);
+    // a stall is told within `stall_after`, whatever the report interval
+    let mut check = tokio::time::interval(stall_after);
+    loop {
+        let reporting = tokio::select! {
+            _ = report.tick() => true,
+            _ = check.tick() => false,
+        };
+        let current = health.borrow().clone();
+        if current.state == ConnectionState::Closed || health.has_changed().is_err() {
+            info!("Websocket {} closed", name);
+            return;
+        }
+        if !reporting {
+            if let Some(silent) = stalled_for(&current, Utc::now(), stall_after) {
+                error!(
+                    "Websocket {} stalled, no notification for {}s",
+                    name,
+                    silent.as_secs()
+                );
+            }
+            continue;
+        }
+        info!(
+            "Websocket {}: {:?}, {} reconnects, pong rtt {:?}, {:.1} messages/s, last slot {:?}, {} parse errors",
+            name,
+            current.state,
+            current.reconnects,
+            current.pong_rtt,
+            current.messages_per_second,
+            current.last_notification_slot,
+            current.parse_errors
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stalled_for() {
+        let now = Utc::now();
+        let mut health = WsHealth {
+            last_notification_at: Some(now - chrono::Duration::seconds(90)),
+            ..Default::default()
+        };
+        let stall_after = Duration::from_secs(60);
+        assert_eq!(
+            Some(Duration::from_secs(90)),
+            stalled_for(&health, now, stall_after)
+        );
+        assert_eq!(None, stalled_for(&health, now, Duration::from_secs(120)));
+
+        // counted from the last connection, with or without a notification before it
+        health.connected_at = Some(now - chrono::Duration::seconds(70));
+        assert_eq!(
+            Some(Duration::from_secs(70)),
+            stalled_for(&health, now, stall_after)
+        );
+        health.last_notification_at = None;
+        assert_eq!(
+            Some(Duration::from_secs(70)),
+            stalled_for(&health, now, stall_after)
+        );
+        health.connected_at = Some(now - chrono::Duration::seconds(30));
+        assert_eq!(None, stalled_for(&health, now, stall_after));
+
+        // reconnecting is reported as such, not as a stall
+        health.connected_at = Some(now - chrono::Duration::seconds(90));
+        health.state = ConnectionState::Reconnecting { attempt: 1 };
+        assert_eq!(None, stalled_for(&health, now, stall_after));
+    }
+}
@@ -1,6 +1,9 @@
 #![allow(dead_code)]
 
-use helius_ws_hooks::pumpfun_instruction_parser::{PayloadBuy, PayloadCreateBuy, PayloadSell};
+use helius_ws_hooks::pumpfun_fee_parser::PayloadFees;
+use helius_ws_hooks::pumpfun_instruction_parser::{
+    InstructionIndex, PayloadBuy, PayloadCreateBuy, PayloadSell,
+};
 use helius_ws_hooks::pumpfun_trading::bot::TradingConfig;
 
 pub const DEFAULT_SELF_PUB_KEY: &str = "8idEav1ZWKZifvbv7EavDPpmvgfdqaTNgArWSimyoiFR";
@@ -18,6 +21,8 @@ pub fn default_trading_config() -> TradingConfig {
         self_pub_key: DEFAULT_SELF_PUB_KEY.to_string(),
         self_keypair: "random_keypair".to_string(),
         paper_trading: true,
+        max_priority_fee: 5_000_000,
+        max_jito_tip: 1_000_000, // 0.001 sol
     }
 }
 
@@ -25,6 +30,7 @@ pub fn default_big_mint_event(amount: u64, max_sol_cost: u64) -> PayloadCreateBu
     PayloadCreateBuy {
         slot: 308319709,
         signature: DEFAULT_SIGNATURE.to_string(),
+        instruction: InstructionIndex::default(),
         mint_pk: DEFAULT_MINT_PK.to_string(),
         user_pk: "3Fhmws3fJjjwGwBiEQxRUHJvj4SqNNaxe8nWqpJmjKNk".to_string(),
         name: "GenZilla".to_string(),
@@ -34,6 +40,10 @@ pub fn default_big_mint_event(amount: u64, max_sol_cost: u64) -> PayloadCreateBu
         associated_bonding_curve: "pCzEfmz3Z5hVyLVnacK29Fx5jMFgPdi7cogQrdt2ukT".to_string(),
         amount,       // 57542586750788,
         max_sol_cost, // 1717000000,
+        router: None,
+        trade: None,
+        balance: None,
+        fees: PayloadFees::default(),
     }
 }
 
@@ -41,6 +51,7 @@ pub fn create_big_mint_event(mint_pk: &str, amount: u64, max_sol_cost: u64) -> P
     PayloadCreateBuy {
         slot: 308319709,
         signature: DEFAULT_SIGNATURE.to_string(),
+        instruction: InstructionIndex::default(),
         mint_pk: mint_pk.to_string(),
         user_pk: "3Fhmws3fJjjwGwBiEQxRUHJvj4SqNNaxe8nWqpJmjKNk".to_string(),
         name: "GenZilla".to_string(),
@@ -50,6 +61,10 @@ pub fn create_big_mint_event(mint_pk: &str, amount: u64, max_sol_cost: u64) -> P
         associated_bonding_curve: "pCzEfmz3Z5hVyLVnacK29Fx5jMFgPdi7cogQrdt2ukT".to_string(),
         amount,       // 57542586750788
         max_sol_cost, // 1717000000,
+        router: None,
+        trade: None,
+        balance: None,
+        fees: PayloadFees::default(),
     }
 }
 
@@ -57,10 +72,15 @@ pub fn default_buy_event(mint_pk: &str, user_pk: &str, max_sol_cost: u64) -> Pay
     PayloadBuy {
         slot: 308319709,
         signature: DEFAULT_SIGNATURE.to_string(),
+        instruction: InstructionIndex::default(),
         mint_pk: mint_pk.to_string(),
         user_pk: user_pk.to_string(),
         amount: 57542586750788,
         max_sol_cost, // 1717000000,j
+        router: None,
+        trade: None,
+        balance: None,
+        fees: PayloadFees::default(),
     }
 }
 
@@ -68,9 +88,14 @@ pub fn default_sell_event(mint_pk: &str, user_pk: &str, amount: u64) -> PayloadS
     PayloadSell {
         slot: 308319709,
         signature: DEFAULT_SIGNATURE.to_string(),
+        instruction: InstructionIndex::default(),
         mint_pk: mint_pk.to_string(),
         user_pk: user_pk.to_string(),
         amount,
         min_sol_output: 0, // 1717000000,j
+        router: None,
+        trade: None,
+        balance: None,
+        fees: PayloadFees::default(),
     }
 }
@@ -4,8 +4,11 @@ mod tests {
     use super::*;
     use crate::helpers::{
         create_big_mint_event, default_big_mint_event, default_buy_event, default_sell_event,
-        DEFAULT_MINT_PK, DEFAULT_MINT_PK2, DEFAULT_SELF_PUB_KEY,
+        default_trading_config, DEFAULT_MINT_PK, DEFAULT_MINT_PK2, DEFAULT_SELF_PUB_KEY,
     };
+    use helius_ws_hooks::pumpfun_fee_parser::PayloadFees;
+    use helius_ws_hooks::pumpfun_trading::bot::OBSERVED_FEES_TTL_SECS;
+    use helius_ws_hooks::pumpfun_trading::orders::BotOrder;
     use helius_ws_hooks::pumpfun_trading::utils::PriceUtil;
 
     #[test]
@@ -18,6 +21,8 @@ mod tests {
             self_pub_key: DEFAULT_SELF_PUB_KEY.to_string(),
             self_keypair: "random_key".to_string(),
             paper_trading: false,
+            max_priority_fee: 5_000_000,
+            max_jito_tip: 1_000_000,
         };
         let mut controller = Controller::new(config);
         let is_paper_trade = false;
@@ -70,6 +75,8 @@ mod tests {
             self_pub_key: DEFAULT_SELF_PUB_KEY.to_string(),
             self_keypair: "random_key".to_string(),
             paper_trading: false,
+            max_priority_fee: 5_000_000,
+            max_jito_tip: 1_000_000,
         };
         let mut controller = Controller::new(config);
         let is_paper_trade = false;
@@ -86,4 +93,70 @@ mod tests {
 
         assert!(controller.get_bot().position_manager.borrow().len() == 1);
     }
+
+    #[test]
+    fn test_snipe_create_buy_observed_fees() {
+        let mut config = default_trading_config();
+        config.create_buy_trigger_lamport = 20_000_000; // 0.02 SOL
+        config.paper_trading = false;
+        let mut controller = Controller::new(config);
+
+        let mut event = default_big_mint_event(57542586750788, 1_717_000_000);
+        event.fees = PayloadFees {
+            cu_limit: Some(72000),
+            priority_fee_micro_lamports: Some(1_500_000),
+            jito_tip_lamports: 100_000,
+        };
+
+        match controller.handle_create_buy(&event, false) {
+            Some(BotOrder::Buy(order)) => {
+                assert_eq!(Some(1_500_000), order.priority_fee);
+                assert_eq!(Some(100_000), order.jito_tips);
+            }
+            other => panic!("expected a buy order, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_snipe_create_buy_fees_capped() {
+        let mut config = default_trading_config();
+        config.create_buy_trigger_lamport = 20_000_000; // 0.02 SOL
+        config.paper_trading = false;
+        config.max_jito_tip = 200_000;
+        let mut controller = Controller::new(config);
+
+        // a creator tipping 5 SOL is not followed
+        let mut event = default_big_mint_event(57542586750788, 1_717_000_000);
+        event.fees = PayloadFees {
+            cu_limit: Some(72000),
+            priority_fee_micro_lamports: Some(900_000_000),
+            jito_tip_lamports: 5_000_000_000,
+        };
+
+        match controller.handle_create_buy(&event, false) {
+            Some(BotOrder::Buy(order)) => {
+                assert_eq!(Some(5_000_000), order.priority_fee);
+                assert_eq!(Some(200_000), order.jito_tips);
+            }
+            other => panic!("expected a buy order, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_observed_fees_expire() {
+        let controller = Controller::new(default_trading_config());
+        let bot = controller.get_bot();
+        bot.record_fees(DEFAULT_MINT_PK, &PayloadFees::default());
+        bot.record_fees(DEFAULT_MINT_PK2, &PayloadFees::default());
+        bot.insert_position(DEFAULT_MINT_PK.to_string(), 1.0);
+
+        let later =
+            bot.observed_fees.borrow()[DEFAULT_MINT_PK2].last_update_ts + OBSERVED_FEES_TTL_SECS;
+        bot.prune_observed_fees(later);
+
+        // only the mint with a position keeps its fees
+        let observed_fees = bot.observed_fees.borrow();
+        assert!(observed_fees.contains_key(DEFAULT_MINT_PK));
+        assert!(!observed_fees.contains_key(DEFAULT_MINT_PK2));
+    }
 }
@@ -0,0 +1,149 @@
+use futures_util::{SinkExt, StreamExt};
+use helius::types::TransactionSubscribeOptions;
+use helius::websocket::StreamEvent;
+use helius_ws_hooks::subscription_manager::{AccountFilter, ShardLimits, SubscriptionManager};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+// answers every subscribe with a new sid and every unsubscribe with true, passing on the method and
+// params of each request
+async fn serve() -> (String, mpsc::UnboundedReceiver<(String, Value)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (request_sender, request_receiver) = mpsc::unbounded_channel();
+    let next_sid = Arc::new(AtomicU64::new(1));
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let request_sender = request_sender.clone();
+            let next_sid = next_sid.clone();
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                while let Some(Ok(message)) = ws.next().await {
+                    let Message::Text(text) = message else {
+                        continue;
+                    };
+                    let request: Value = serde_json::from_str(&text).unwrap();
+                    let method = request["method"].as_str().unwrap().to_string();
+                    let result = if method.ends_with("Unsubscribe") {
+                        json!(true)
+                    } else {
+                        json!(next_sid.fetch_add(1, Ordering::SeqCst))
+                    };
+                    let _ = request_sender.send((method, request["params"].clone()));
+                    let response = json!({"jsonrpc": "2.0", "result": result, "id": request["id"]});
+                    ws.send(Message::Text(response.to_string())).await.unwrap();
+                }
+            });
+        }
+    });
+    (url, request_receiver)
+}
+
+fn include(accounts: &[&str]) -> AccountFilter {
+    AccountFilter::Include(accounts.iter().map(|account| account.to_string()).collect())
+}
+
+fn limits(max_subscriptions_per_connection: usize) -> ShardLimits {
+    ShardLimits {
+        max_subscriptions_per_connection,
+        max_connections: 1,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_add_replace_remove() {
+    let (url, mut requests) = serve().await;
+    let options = TransactionSubscribeOptions::default();
+    let (mut manager, _receiver) = SubscriptionManager::new(url, options, limits(3));
+
+    manager.add("whales", include(&["a", "b"])).await.unwrap();
+    let (method, params) = requests.recv().await.unwrap();
+    assert_eq!("transactionSubscribe", method);
+    assert_eq!(json!(["a", "b"]), params[0]["accountInclude"]);
+    assert_eq!(&[1], manager.subscriptions());
+
+    // subscribed with the merged accounts before the old subscription is dropped
+    manager.add("whales", include(&["b", "c"])).await.unwrap();
+    let (method, params) = requests.recv().await.unwrap();
+    assert_eq!("transactionSubscribe", method);
+    assert_eq!(json!(["a", "b", "c"]), params[0]["accountInclude"]);
+    assert_eq!(
+        ("transactionUnsubscribe".to_string(), json!([1])),
+        requests.recv().await.unwrap()
+    );
+    assert_eq!(&[1], manager.subscriptions());
+
+    manager.remove("whales", include(&["a"])).await.unwrap();
+    let (method, params) = requests.recv().await.unwrap();
+    assert_eq!("transactionSubscribe", method);
+    assert_eq!(json!(["b", "c"]), params[0]["accountInclude"]);
+    assert_eq!(
+        ("transactionUnsubscribe".to_string(), json!([2])),
+        requests.recv().await.unwrap()
+    );
+
+    manager
+        .remove("whales", include(&["b", "c"]))
+        .await
+        .unwrap();
+    assert_eq!(
+        ("transactionUnsubscribe".to_string(), json!([3])),
+        requests.recv().await.unwrap()
+    );
+    assert_eq!(&[0], manager.subscriptions());
+    assert!(manager.included("whales").is_empty());
+}
+
+#[tokio::test]
+async fn test_replace_at_capacity() {
+    let (url, mut requests) = serve().await;
+    let options = TransactionSubscribeOptions::default();
+    let (mut manager, _receiver) = SubscriptionManager::new(url, options, limits(2));
+
+    manager.add("whales", include(&["a"])).await.unwrap();
+    requests.recv().await.unwrap();
+    // the second slot is kept free
+    assert!(manager.add("pairs", include(&["x"])).await.is_err());
+
+    manager.add("whales", include(&["b"])).await.unwrap();
+    let (method, params) = requests.recv().await.unwrap();
+    assert_eq!("transactionSubscribe", method);
+    assert_eq!(json!(["a", "b"]), params[0]["accountInclude"]);
+    assert_eq!(
+        ("transactionUnsubscribe".to_string(), json!([1])),
+        requests.recv().await.unwrap()
+    );
+    assert_eq!(&[1], manager.subscriptions());
+}
+
+#[tokio::test]
+async fn test_replace_without_free_slot() {
+    let (url, mut requests) = serve().await;
+    let options = TransactionSubscribeOptions::default();
+    let (mut manager, mut receiver) = SubscriptionManager::new(url, options, limits(1));
+
+    manager.add("whales", include(&["a"])).await.unwrap();
+    requests.recv().await.unwrap();
+
+    // dropped before subscribing again, with a gap in the stream
+    manager.add("whales", include(&["b"])).await.unwrap();
+    assert_eq!(
+        ("transactionUnsubscribe".to_string(), json!([1])),
+        requests.recv().await.unwrap()
+    );
+    let (method, params) = requests.recv().await.unwrap();
+    assert_eq!("transactionSubscribe", method);
+    assert_eq!(json!(["a", "b"]), params[0]["accountInclude"]);
+    assert_eq!(&[1], manager.subscriptions());
+
+    let tagged = receiver.recv().await.unwrap();
+    assert_eq!("whales", tagged.tag);
+    assert!(matches!(tagged.event, StreamEvent::Gap(_)));
+}
@@ -15,14 +15,16 @@ async fn main() -> anyhow::Result<()> {
             key: "base58-kp".to_string(),
             lamports: 100000,
             slippage: 500, // (1 bp = 0.01%). Defaults to 500
-                           // jito_tips: 50_000, // 0.00005 sol
+            priority_fee: None,
+            jito_tips: None, // DEFAULT_JITO_TIPS, 0.00001 sol
         },
         // eva
         BuyTx {
             key: "base58-kp".to_string(),
             lamports: 100000,
             slippage: 500,
-            // jito_tips: 50_000,
+            priority_fee: None,
+            jito_tips: None,
         },
     ];
     let cluster_url = "https://mainnet.helius-rpc.com/?api-key=<api-key>";
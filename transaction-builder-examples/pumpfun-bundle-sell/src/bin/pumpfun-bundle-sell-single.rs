@@ -20,7 +20,8 @@ async fn main() -> anyhow::Result<()> {
         key: "".to_string(),
         amount: None,
         slippage: 500, // (1 bp = 0.01%). Defaults to 500
-                       // jito_tips: 50_000, // 0.00005 sol
+        priority_fee: None,
+        jito_tips: None, // DEFAULT_JITO_TIPS, 0.00001 sol
     };
     let close_token_ata = true;
     let cluster_url = "https://mainnet.helius-rpc.com/?api-key=<api-key>";
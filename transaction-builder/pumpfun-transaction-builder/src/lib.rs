@@ -111,7 +111,8 @@ pub struct BuyTx {
     pub key: String,
     pub lamports: u64, // 1 lamport = 0.000000001 SOL
     pub slippage: u64,
-    // pub jito_tips: u64, // 1 jito_tip = 0.000000001 SOL
+    pub priority_fee: Option<u64>, // micro lamports per compute unit, DEFAULT_PRIORITY_FEE if None
+    pub jito_tips: Option<u64>,    // 1 jito_tip = 0.000000001 SOL, DEFAULT_JITO_TIPS if None
 }
 
 #[derive(Debug)]
@@ -119,10 +120,16 @@ pub struct SellTx {
     pub key: String,
     pub amount: Option<u64>, // 1 lamport = 0.000000001 SOL
     pub slippage: u64,
+    pub priority_fee: Option<u64>, // micro lamports per compute unit, DEFAULT_PRIORITY_FEE if None
+    pub jito_tips: Option<u64>,    // 1 jito_tip = 0.000000001 SOL, DEFAULT_JITO_TIPS if None
 }
 
 pub const JITO_TIP_PUBKEY_MAINNET: &str = "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt";
 
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 63909;
+pub const DEFAULT_PRIORITY_FEE: u64 = 140999; // 0.14 lamports per compute unit
+pub const DEFAULT_JITO_TIPS: u64 = 10_000; // 0.00001 sol
+
 pub async fn build_bundle_buy_batch(
     mint_key: &str,
     tx_params: &Vec<BuyTx>,
@@ -162,8 +169,8 @@ pub async fn build_bundle_buy_single(
         None => anchor_client::Cluster::Mainnet,
     };
     let priority_fee = PriorityFee {
-        limit: Some(63909),
-        price: Some(140999), // Set 0.14 lamports per compute unit
+        limit: Some(DEFAULT_COMPUTE_UNIT_LIMIT),
+        price: Some(tx_param.priority_fee.unwrap_or(DEFAULT_PRIORITY_FEE)),
     };
     let client: PumpFun = PumpFun::new(cluster, payer.insecure_clone(), None, None);
 
@@ -179,8 +186,8 @@ pub async fn build_bundle_buy_single(
             .await
             .map_err(|e| anyhow::Error::msg(format!("Error buying tokens: {:?}", e)))?,
     );
-    // jito tips 0.00001 sol
-    instructions.push(transfer(&payer.pubkey(), jito_tip_pubkey, 10_000));
+    let jito_tips = tx_param.jito_tips.unwrap_or(DEFAULT_JITO_TIPS);
+    instructions.push(transfer(&payer.pubkey(), jito_tip_pubkey, jito_tips));
 
     let tx = VersionedTransaction::from(Transaction::new_signed_with_payer(
         &instructions,
@@ -214,8 +221,8 @@ pub async fn build_bundle_sell_single(
         None => anchor_client::Cluster::Mainnet,
     };
     let priority_fee = PriorityFee {
-        limit: Some(63909),
-        price: Some(140999), // Set 0.14 lamports per compute unit
+        limit: Some(DEFAULT_COMPUTE_UNIT_LIMIT),
+        price: Some(tx_param.priority_fee.unwrap_or(DEFAULT_PRIORITY_FEE)),
     };
     let client: PumpFun = PumpFun::new(cluster, payer.insecure_clone(), None, None);
 
@@ -232,8 +239,8 @@ pub async fn build_bundle_sell_single(
             .await
             .map_err(|e| anyhow::Error::msg(format!("Error selling tokens: {:?}", e)))?,
     );
-    // jito tips 0.00001 sol
-    instructions.push(transfer(&payer.pubkey(), jito_tip_pubkey, 10_000));
+    let jito_tips = tx_param.jito_tips.unwrap_or(DEFAULT_JITO_TIPS);
+    instructions.push(transfer(&payer.pubkey(), jito_tip_pubkey, jito_tips));
 
     let tx = VersionedTransaction::from(Transaction::new_signed_with_payer(
         &instructions,